@@ -0,0 +1,223 @@
+use bmp_rust::bmp::BMP;
+use std::env;
+use std::fs;
+use std::process;
+
+use bmp_editor::color::parse_hex;
+use bmp_editor::editor::EditorCommand;
+use bmp_editor::rle::decode_rle;
+use bmp_editor::script::parse_script;
+use bmp_editor::validate::validate_bmp;
+
+//command line version of the editor, to do the same edits as the web app without a browser
+//eg: bmp-edit in.bmp out.bmp --gaussian 3 --rotate 90 --rect 0,0,10,10:#ff0000
+//operations are done in the order they are given
+
+const USAGE: &str = "Usage: bmp-edit <input.bmp> <output.bmp> [operations]
+
+Operations:
+  --invert
+  --greyscale
+  --rotate <90|180|270>
+  --gaussian <radius>
+  --box <radius>
+  --median <radius>
+  --pixel <x,y:color>
+  --fill <x,y:color>                 bucket fill
+  --line <x1,y1,x2,y2:color>
+  --rect <x1,y1,x2,y2:color>
+  --ellipse <x,y,xlength,ylength:color>
+  --resolution <x,y>                 horizontal and vertical pixels per meter
+  --script <edits.txt>               run an edit script exported from the web editor
+
+Colors are hex, #rrggbb or #rrggbbaa";
+
+fn main() {
+  let args: Vec<String> = env::args().skip(1).collect();
+  if args.len() < 2 || args.iter().any(|arg| arg == "-h" || arg == "--help") {
+    println!("{}", USAGE);
+    return;
+  }
+  let commands = match parse_operations(&args[2..]) {
+    Ok(commands) => commands,
+    Err(e) => exit_with_error(&e),
+  };
+  let mut bmp = BMP::new(1, 1, None);
+  bmp.contents = match fs::read(&args[0]) {
+    Ok(contents) => contents,
+    Err(e) => exit_with_error(&format!("Could not read {}: {}", args[0], e)),
+  };
+  if let Err(e) = validate_bmp(&bmp.contents) {
+    exit_with_error(&format!("{} can't be opened: {}", args[0], e));
+  }
+  //run length encoded images are saved uncompressed
+  match decode_rle(&bmp) {
+    Ok(Some(decoded)) => bmp = decoded,
+    Ok(None) => {},
+    Err(e) => exit_with_error(&format!("Could not decode {}: {}", args[0], e)),
+  }
+  if let Err(e) = run_commands(&mut bmp, &commands) {
+    exit_with_error(&e);
+  }
+  if let Err(e) = fs::write(&args[1], &bmp.contents) {
+    exit_with_error(&format!("Could not write {}: {}", args[1], e));
+  }
+}
+
+fn exit_with_error(message: &str) -> ! {
+  eprintln!("{}", message);
+  process::exit(1);
+}
+
+fn run_commands(bmp: &mut BMP, commands: &[EditorCommand]) -> Result<(), String> {
+  for command in commands {
+    command.run(bmp).map_err(|e| format!("{} failed: {}", command.name(), e))?;
+  }
+  Ok(())
+}
+
+fn parse_operations(args: &[String]) -> Result<Vec<EditorCommand>, String> {
+  let mut commands: Vec<EditorCommand> = Vec::new();
+  let mut args_iter = args.iter();
+  while let Some(arg) = args_iter.next() {
+    //operations without a value
+    match arg.as_str() {
+      "--invert" => {
+        commands.push(EditorCommand::Invert);
+        continue;
+      },
+      "--greyscale" | "--grayscale" => {
+        commands.push(EditorCommand::Greyscale);
+        continue;
+      },
+      _ => {},
+    }
+    let value = args_iter.next().ok_or(format!("Missing value for {}", arg))?;
+    match arg.as_str() {
+      "--rotate" => {
+        let degrees = match value.as_str() {
+          "90" => 90,
+          "180" => 180,
+          "270" => 270,
+          _ => return Err(format!("Can only rotate by 90, 180 or 270 degrees, not {}", value)),
+        };
+        for _ in 0..degrees/90 {
+          commands.push(EditorCommand::Rotate);
+        }
+      },
+      "--gaussian" => commands.push(EditorCommand::GaussianBlur(parse_radius(value)?)),
+      "--box" => commands.push(EditorCommand::BoxBlur(parse_radius(value)?)),
+      "--median" => commands.push(EditorCommand::MedianFilter(parse_radius(value)?)),
+      "--pixel" => {
+        let (coords, color) = parse_shape(value, 2)?;
+        commands.push(EditorCommand::ChangePixel([coords[0], coords[1]], color));
+      },
+      "--fill" => {
+        let (coords, color) = parse_shape(value, 2)?;
+        commands.push(EditorCommand::FillBucket([coords[0], coords[1]], color));
+      },
+      "--line" => {
+        let (coords, color) = parse_shape(value, 4)?;
+        commands.push(EditorCommand::DrawLine([[coords[0], coords[1]], [coords[2], coords[3]]], color));
+      },
+      "--rect" => {
+        let (coords, color) = parse_shape(value, 4)?;
+        commands.push(EditorCommand::DrawRect([[coords[0], coords[1]], [coords[2], coords[3]]], color));
+      },
+      "--ellipse" => {
+        let (coords, color) = parse_shape(value, 4)?;
+        commands.push(EditorCommand::DrawEllipse([[coords[0], coords[1]], [coords[2], coords[3]]], color));
+      },
+      "--resolution" => {
+        let resolution = value.split(',').map(|number| number.trim().parse::<u32>()).collect::<Result<Vec<u32>, _>>().map_err(|_| format!("Invalid resolution {}", value))?;
+        if resolution.len() != 2 {
+          return Err(format!("Expected 2 numbers, found {} in {}", resolution.len(), value));
+        }
+        commands.push(EditorCommand::SetResolution(resolution[0], resolution[1]));
+      },
+      "--script" => {
+        let script = fs::read_to_string(value).map_err(|e| format!("Could not read {}: {}", value, e))?;
+        let script_commands = parse_script(&script).map_err(|errors| {
+          format!("Errors in {}:\n{}", value, errors.iter().map(|error| error.to_string()).collect::<Vec<String>>().join("\n"))
+        })?;
+        commands.extend(script_commands);
+      },
+      _ => return Err(format!("Unknown operation {}\n\n{}", arg, USAGE)),
+    }
+  }
+  Ok(commands)
+}
+
+fn parse_radius(value: &str) -> Result<u8, String> {
+  value.parse::<u8>().map_err(|_| format!("Invalid radius {}", value))
+}
+
+//"x,y,...:color", with `coord_num` numbers before the color
+fn parse_shape(value: &str, coord_num: usize) -> Result<(Vec<u16>, [u8; 4]), String> {
+  let (coords_text, color_text) = value.split_once(':').ok_or(format!("Missing color in {}", value))?;
+  let coords = coords_text.split(',').map(|coord| coord.trim().parse::<u16>()).collect::<Result<Vec<u16>, _>>().map_err(|_| format!("Invalid coordinates {}", coords_text))?;
+  if coords.len() != coord_num {
+    return Err(format!("Expected {} numbers, found {} in {}", coord_num, coords.len(), coords_text));
+  }
+  Ok((coords, parse_hex(color_text)?))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn args(text: &str) -> Vec<String> {
+    text.split(' ').map(|arg| arg.to_string()).collect()
+  }
+
+  #[test]
+  fn parses_operations_in_order() {
+    let commands = parse_operations(&args("--gaussian 3 --rotate 180 --rect 0,0,10,10:#ff0000 --invert --resolution 3780,3780")).unwrap();
+    assert_eq!(commands, vec![
+      EditorCommand::GaussianBlur(3),
+      EditorCommand::Rotate,
+      EditorCommand::Rotate,
+      EditorCommand::DrawRect([[0, 0], [10, 10]], [255, 0, 0, 255]),
+      EditorCommand::Invert,
+      EditorCommand::SetResolution(3780, 3780),
+    ]);
+  }
+
+  #[test]
+  fn parses_colors() {
+    assert_eq!(parse_shape("1,2:#0a0B0c", 2).unwrap().1, [10, 11, 12, 255]);
+    assert_eq!(parse_shape("1,2:01020304", 2).unwrap().1, [1, 2, 3, 4]);
+    assert!(parse_shape("1,2:#fff", 2).is_err());
+    assert!(parse_shape("1,2:#gggggg", 2).is_err());
+  }
+
+  #[test]
+  fn operations_outside_the_image_are_errors() {
+    let mut bmp = BMP::new(3, 6, None);
+    for operation in ["--pixel 100,100:#000000", "--line 0,0,100,100:#000000", "--fill 100,100:#000000", "--rect 0,0,100,100:#000000", "--ellipse 2,2,10,10:#000000"] {
+      let commands = parse_operations(&args(operation)).unwrap();
+      assert!(run_commands(&mut bmp, &commands).is_err());
+    }
+    assert_eq!(run_commands(&mut bmp, &parse_operations(&args("--pixel 100,100:#000000")).unwrap()), Err("Pixel (100, 100) to (0, 0, 0, 255) failed: Error: Invalid Parameter: (100, 100) is outside the 6x3 image".to_string()));
+    assert!(run_commands(&mut bmp, &parse_operations(&args("--pixel 5,2:#000000")).unwrap()).is_ok());
+  }
+
+  #[test]
+  fn rects_with_any_corners() {
+    let mut bmp = BMP::new(8, 8, None);
+    for operation in ["--rect 5,5,5,5:#ff0000", "--rect 6,6,2,2:#ff0000", "--rect 2,2,3,3:#ff0000"] {
+      assert_eq!(run_commands(&mut bmp, &parse_operations(&args(operation)).unwrap()), Ok(()));
+    }
+    assert_eq!(bmp.get_color_of_pixel(5, 5).unwrap(), [255, 0, 0, 255]);
+    assert!(run_commands(&mut bmp, &parse_operations(&args("--rect 9,9,2,2:#ff0000")).unwrap()).is_err());
+  }
+
+  #[test]
+  fn rejects_bad_operations() {
+    assert!(parse_operations(&args("--rotate 45")).is_err());
+    assert!(parse_operations(&args("--gaussian")).is_err());
+    assert!(parse_operations(&args("--line 0,0,1:#000000")).is_err());
+    assert!(parse_operations(&args("--rect 0,0,1,1")).is_err());
+    assert!(parse_operations(&args("--sharpen 2")).is_err());
+  }
+}
@@ -0,0 +1,289 @@
+use bmp_rust::bmp::{BMP, ErrorKind};
+
+use crate::export::{ExportFormat, export_bmp};
+use crate::render::PixelReader;
+
+//16, 24 and 32 bit images, where every pixel is its color. which bits of a pixel are which channel is given by masks
+//(in V2 and later headers, or right after the info header for BI_BITFIELDS), or by the defaults for BI_RGB.
+//bmp_rust guesses the channel order by comparing the masks, and can only change pixels properly in the 32 bit bgra
+//images it makes itself. so pixels are read on the bytes here, and other images are edited as a bgra copy
+//whose pixels are then written back with the original headers (keeping the masks, color space and icc profile)
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct ChannelMasks {
+  pub red: u32,
+  pub green: u32,
+  pub blue: u32,
+  //0 if there is no alpha, and every pixel is opaque
+  pub alpha: u32,
+}
+
+impl ChannelMasks {
+  //what BMP::new uses
+  pub const BGRA: ChannelMasks = ChannelMasks { red: 0x00FF0000, green: 0x0000FF00, blue: 0x000000FF, alpha: 0xFF000000 };
+
+  //BI_RGB: 16 bit is 555, 24 and 32 bit are bgr (the fourth byte of 32 bit is unused, not alpha)
+  fn default_for(bitcount: u16) -> Self {
+    if bitcount == 16 {
+      ChannelMasks { red: 0x7C00, green: 0x03E0, blue: 0x001F, alpha: 0 }
+    } else {
+      ChannelMasks { alpha: 0, ..ChannelMasks::BGRA }
+    }
+  }
+
+  pub fn decode(&self, value: u32) -> [u8; 4] {
+    let alpha = if self.alpha == 0 { 255 } else { read_channel(value, self.alpha) };
+    [read_channel(value, self.red), read_channel(value, self.green), read_channel(value, self.blue), alpha]
+  }
+
+  pub fn encode(&self, color: [u8; 4]) -> u32 {
+    write_channel(color[0], self.red) | write_channel(color[1], self.green) | write_channel(color[2], self.blue) | write_channel(color[3], self.alpha)
+  }
+}
+
+//the masked bits, scaled so the largest value they can have is 255
+fn read_channel(value: u32, mask: u32) -> u8 {
+  if mask == 0 {
+    return 0;
+  }
+  let max = mask >> mask.trailing_zeros();
+  let bits = (value & mask) >> mask.trailing_zeros();
+  (f64::from(bits)*255.0/f64::from(max)).round() as u8
+}
+
+fn write_channel(channel: u8, mask: u32) -> u32 {
+  if mask == 0 {
+    return 0;
+  }
+  let max = mask >> mask.trailing_zeros();
+  ((f64::from(channel)*f64::from(max)/255.0).round() as u32) << mask.trailing_zeros() & mask
+}
+
+//where everything is in a 16, 24 or 32 bit image
+#[derive(PartialEq, Clone, Debug)]
+pub struct MaskedLayout {
+  pub bitcount: u16,
+  pub width: u32,
+  pub height: u32,
+  top_down: bool,
+  dib_size: usize,
+  pixels_offset: usize,
+  row_size: usize,
+  pub masks: ChannelMasks,
+}
+
+impl MaskedLayout {
+  //none if the image isn't 16, 24 or 32 bit, or is compressed (eg BI_JPEG)
+  pub fn of(bmp: &BMP) -> Result<Option<Self>, ErrorKind> {
+    let dib_header = bmp.get_dib_header()?;
+    if ![16, 24, 32].contains(&dib_header.bitcount) {
+      return Ok(None);
+    }
+    //the old 12 byte header has no compression
+    let masks = match dib_header.compression.as_deref().unwrap_or("BI_RGB") {
+      "BI_RGB" => ChannelMasks::default_for(dib_header.bitcount),
+      "BI_BITFIELDS" => read_masks(bmp, dib_header.size as usize >= 56)?,
+      "BI_ALPHABITFIELDS" => read_masks(bmp, true)?,
+      _ => return Ok(None),
+    };
+    let pixels_offset = bmp.get_offset() as usize;
+    let row_size = row_size(dib_header.bitcount, dib_header.width);
+    let height = dib_header.height.unsigned_abs();
+    if bmp.contents.len() < pixels_offset+row_size*height as usize {
+      return Err(ErrorKind::InvalidParameter("image is missing pixels".to_string()));
+    }
+    Ok(Some(Self {
+      bitcount: dib_header.bitcount,
+      width: dib_header.width,
+      height,
+      top_down: dib_header.height < 0,
+      dib_size: dib_header.size as usize,
+      pixels_offset,
+      row_size,
+      masks,
+    }))
+  }
+
+  fn pixel_start(&self, x: u16, y: u16) -> Result<usize, ErrorKind> {
+    if u32::from(x) >= self.width || u32::from(y) >= self.height {
+      return Err(ErrorKind::InvalidParameter(format!("pixel ({}, {}) is outside the image", x, y)));
+    }
+    //y is from the top, but most images store the bottom row first
+    let row = if self.top_down { u32::from(y) } else { self.height-u32::from(y)-1 };
+    Ok(self.pixels_offset+row as usize*self.row_size+usize::from(x)*usize::from(self.bitcount/8))
+  }
+
+  pub fn color_at(&self, bmp: &BMP, x: u16, y: u16) -> Result<[u8; 4], ErrorKind> {
+    let start = self.pixel_start(x, y)?;
+    let mut value = [0u8; 4];
+    let bytes_per_pixel = usize::from(self.bitcount/8);
+    value[..bytes_per_pixel].copy_from_slice(&bmp.contents[start..start+bytes_per_pixel]);
+    Ok(self.masks.decode(u32::from_le_bytes(value)))
+  }
+
+  pub fn set_color(&self, bmp: &mut BMP, x: u16, y: u16, color: [u8; 4]) -> Result<(), ErrorKind> {
+    let start = self.pixel_start(x, y)?;
+    let bytes_per_pixel = usize::from(self.bitcount/8);
+    bmp.contents[start..start+bytes_per_pixel].copy_from_slice(&self.masks.encode(color).to_le_bytes()[..bytes_per_pixel]);
+    Ok(())
+  }
+
+  //anything after this (like an icc profile) isn't pixels
+  fn pixels_end(&self) -> usize {
+    self.pixels_offset+self.row_size*self.height as usize
+  }
+}
+
+//V2 and later headers have the masks at the same place as the extra masks after a 40 byte info header
+fn read_masks(bmp: &BMP, has_alpha: bool) -> Result<ChannelMasks, ErrorKind> {
+  let mask = |index: usize| {
+    let start = 14+40+index*4;
+    bmp.contents.get(start..start+4).map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])).ok_or(ErrorKind::Missing)
+  };
+  Ok(ChannelMasks {
+    red: mask(0)?,
+    green: mask(1)?,
+    blue: mask(2)?,
+    alpha: if has_alpha { mask(3)? } else { 0 },
+  })
+}
+
+//rows are padded to a multiple of 4 bytes
+fn row_size(bitcount: u16, width: u32) -> usize {
+  (bitcount as usize*width as usize).div_ceil(32)*4
+}
+
+//a bottom up 32 bit bgra image with nothing after the pixels, like BMP::new makes, which bmp_rust can edit
+pub fn is_standard(bmp: &BMP) -> bool {
+  match (MaskedLayout::of(bmp), bmp.get_dib_header()) {
+    (Ok(Some(layout)), Ok(dib_header)) => {
+      layout.bitcount == 32 && layout.masks == ChannelMasks::BGRA && dib_header.AlphaMask.is_some() && !layout.top_down && bmp.contents.len() == layout.pixels_end()
+    },
+    _ => false,
+  }
+}
+
+//do the edit on a standard copy of the image, then put the pixels back in the image's own format.
+//the edit can change the size (eg rotating)
+pub fn edit_as_standard<F>(bmp: &mut BMP, layout: &MaskedLayout, edit: F) -> Result<(), ErrorKind> where F: FnOnce(&mut BMP) -> Result<(), ErrorKind> {
+  let mut standard = export_bmp(bmp, ExportFormat::Bgra32, false)?;
+  edit(&mut standard)?;
+  *bmp = write_back(bmp, layout, &standard)?;
+  Ok(())
+}
+
+//the original's headers (with the width, height and sizes updated), the edited pixels, then whatever came after the original's pixels
+fn write_back(original: &BMP, layout: &MaskedLayout, edited: &BMP) -> Result<BMP, ErrorKind> {
+  let dib_header = edited.get_dib_header()?;
+  let width = dib_header.width;
+  let height = dib_header.height.unsigned_abs();
+  let row_size = row_size(layout.bitcount, width);
+  let bytes_per_pixel = usize::from(layout.bitcount/8);
+  let reader = PixelReader::new(edited)?;
+  let mut pixel_bytes: Vec<u8> = vec![0; row_size*height as usize];
+  for y in 0..height {
+    let row = if layout.top_down { y } else { height-y-1 } as usize;
+    for x in 0..width {
+      let start = row*row_size+x as usize*bytes_per_pixel;
+      let value = layout.masks.encode(reader.color(x as u16, y as u16)?).to_le_bytes();
+      pixel_bytes[start..start+bytes_per_pixel].copy_from_slice(&value[..bytes_per_pixel]);
+    }
+  }
+  let mut contents = original.contents[..layout.pixels_offset].to_vec();
+  let signed_height = if layout.top_down { -(height as i32) } else { height as i32 };
+  //headers are only changed if they have to be
+  let resized = [width, height] != [layout.width, layout.height];
+  if resized && layout.dib_size == 12 {
+    contents[18..20].copy_from_slice(&(width as u16).to_le_bytes());
+    contents[20..22].copy_from_slice(&(signed_height as i16).to_le_bytes());
+  } else if resized {
+    contents[18..22].copy_from_slice(&width.to_le_bytes());
+    contents[22..26].copy_from_slice(&signed_height.to_le_bytes());
+    contents[34..38].copy_from_slice(&(pixel_bytes.len() as u32).to_le_bytes());
+  }
+  let tail = &original.contents[layout.pixels_end()..];
+  //a V5 profile after the pixels moves with the end of them. its offset is from the start of the dib header
+  if layout.dib_size >= 124 {
+    let profile_data = u32::from_le_bytes([contents[126], contents[127], contents[128], contents[129]]) as usize;
+    if profile_data > 0 && 14+profile_data >= layout.pixels_end() {
+      let moved = 14+profile_data-layout.pixels_end()+layout.pixels_offset+pixel_bytes.len()-14;
+      contents[126..130].copy_from_slice(&(moved as u32).to_le_bytes());
+    }
+  }
+  contents.extend(pixel_bytes);
+  contents.extend(tail);
+  let file_size = contents.len() as u32;
+  contents[2..6].copy_from_slice(&file_size.to_le_bytes());
+  let mut written = BMP::new(1, 1, None);
+  written.contents = contents;
+  Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::editor::EditorCommand;
+
+  //a V5 image with the channels in argb order (alpha in the lowest byte), a color space and an icc profile after the pixels
+  fn argb_v5(width: u32, height: u32, colors: &[[u8; 4]]) -> BMP {
+    let mut bmp = BMP::new(height as i32, width, None);
+    let masks = ChannelMasks { red: 0x0000FF00, green: 0x00FF0000, blue: 0xFF000000, alpha: 0x000000FF };
+    for (index, mask) in [masks.red, masks.green, masks.blue, masks.alpha].iter().enumerate() {
+      bmp.contents[54+index*4..58+index*4].copy_from_slice(&mask.to_le_bytes());
+    }
+    bmp.contents[70..74].copy_from_slice(b"BGRs");
+    let pixels_end = bmp.contents.len();
+    for (index, color) in colors.iter().enumerate() {
+      let (x, y) = (index as u32 % width, index as u32 / width);
+      let start = 138+((height-y-1)*width+x) as usize*4;
+      bmp.contents[start..start+4].copy_from_slice(&masks.encode(*color).to_le_bytes());
+    }
+    bmp.contents[126..130].copy_from_slice(&((pixels_end-14) as u32).to_le_bytes());
+    bmp.contents[130..134].copy_from_slice(&4u32.to_le_bytes());
+    bmp.contents.extend(b"icc!");
+    bmp
+  }
+
+  #[test]
+  fn channels_scale_to_8_bits() {
+    let rgb565 = ChannelMasks { red: 0xF800, green: 0x07E0, blue: 0x001F, alpha: 0 };
+    assert_eq!(rgb565.decode(0xF800), [255, 0, 0, 255]);
+    assert_eq!(rgb565.decode(0x07E0 | 0x0010), [0, 255, 132, 255]);
+    assert_eq!(rgb565.encode([255, 255, 0, 0]), 0xFFE0);
+    assert_eq!(ChannelMasks::BGRA.decode(ChannelMasks::BGRA.encode([1, 2, 3, 4])), [1, 2, 3, 4]);
+  }
+
+  #[test]
+  fn read_masked_pixels() {
+    let bmp = argb_v5(2, 1, &[[255, 0, 0, 128], [0, 10, 20, 255]]);
+    let layout = MaskedLayout::of(&bmp).unwrap().unwrap();
+    assert!(!is_standard(&bmp));
+    assert_eq!(layout.color_at(&bmp, 0, 0).unwrap(), [255, 0, 0, 128]);
+    assert_eq!(PixelReader::new(&bmp).unwrap().color(1, 0).unwrap(), [0, 10, 20, 255]);
+    assert!(is_standard(&BMP::new(2, 2, None)));
+    //24 bit BI_RGB uses the default masks
+    let bmp = export_bmp(&bmp, ExportFormat::Bgr24, false).unwrap();
+    assert_eq!(MaskedLayout::of(&bmp).unwrap().unwrap().color_at(&bmp, 1, 0).unwrap(), [0, 10, 20, 255]);
+  }
+
+  #[test]
+  fn edits_keep_headers_and_profile() {
+    let mut bmp = argb_v5(3, 2, &[[255, 0, 0, 128], [0, 255, 0, 255], [0, 0, 255, 255], [0, 0, 0, 255], [0, 0, 0, 0], [9, 9, 9, 255]]);
+    let headers = bmp.contents[14..126].to_vec();
+    EditorCommand::ChangePixel([1, 1], [1, 2, 3, 200]).run(&mut bmp).unwrap();
+    assert_eq!(bmp.contents[14..126], headers);
+    assert!(bmp.contents.ends_with(b"icc!"));
+    let layout = MaskedLayout::of(&bmp).unwrap().unwrap();
+    assert_eq!(layout.color_at(&bmp, 1, 1).unwrap(), [1, 2, 3, 200]);
+    assert_eq!(layout.color_at(&bmp, 0, 0).unwrap(), [255, 0, 0, 128]);
+    //if an edit changes the size, the profile moves after the new pixels
+    let bmp = write_back(&bmp, &layout, &BMP::new(3, 2, Some([0, 0, 255, 255]))).unwrap();
+    let layout = MaskedLayout::of(&bmp).unwrap().unwrap();
+    assert_eq!(layout.color_at(&bmp, 1, 2).unwrap(), [0, 0, 255, 255]);
+    assert_eq!([layout.width, layout.height], [2, 3]);
+    assert_eq!(layout.masks, MaskedLayout::of(&argb_v5(1, 1, &[])).unwrap().unwrap().masks);
+    let profile_data = u32::from_le_bytes([bmp.contents[126], bmp.contents[127], bmp.contents[128], bmp.contents[129]]) as usize;
+    assert_eq!(&bmp.contents[14+profile_data..], b"icc!");
+    assert_eq!(bmp.get_header().bfSize as usize, bmp.contents.len());
+  }
+}
@@ -0,0 +1,30 @@
+//reads through bytes in order, returns none instead of panicking if there aren't enough bytes left
+//used to read the serialized history and macros
+pub(crate) struct ByteReader<'a> {
+  bytes: &'a [u8],
+  position: usize,
+}
+
+impl<'a> ByteReader<'a> {
+  pub fn new(bytes: &'a [u8]) -> Self {
+    Self { bytes, position: 0 }
+  }
+
+  pub fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+    let taken = self.bytes.get(self.position..self.position.checked_add(len)?)?;
+    self.position += len;
+    Some(taken)
+  }
+
+  pub fn take_array<const N: usize>(&mut self) -> Option<[u8; N]> {
+    self.take(N)?.try_into().ok()
+  }
+
+  pub fn take_u16(&mut self) -> Option<u16> {
+    Some(u16::from_le_bytes(self.take_array()?))
+  }
+
+  pub fn take_u32(&mut self) -> Option<u32> {
+    Some(u32::from_le_bytes(self.take_array()?))
+  }
+}
@@ -0,0 +1,124 @@
+//converting colors between rgb(a), hex, hsv and hsl, for the color picker
+//hue is in degrees (0 to 360), saturation, value and lightness are 0 to 1
+
+pub fn to_hex(color: [u8; 4]) -> String {
+  format!("#{:02X}{:02X}{:02X}{:02X}", color[0], color[1], color[2], color[3])
+}
+
+//#RRGGBB or #RRGGBBAA (the # is optional), alpha is 255 if left out
+pub fn parse_hex(input: &str) -> Result<[u8; 4], String> {
+  let hex = input.trim().trim_start_matches('#');
+  if (hex.len() != 6 && hex.len() != 8) || !hex.is_ascii() {
+    return Err(format!("Invalid hex color \"{}\": should be #RRGGBB or #RRGGBBAA", input.trim()));
+  }
+  let mut color: [u8; 4] = [0, 0, 0, 255];
+  for i in 0..hex.len()/2 {
+    color[i] = u8::from_str_radix(&hex[i*2..i*2+2], 16).map_err(|_| format!("Invalid hex color \"{}\"", input.trim()))?;
+  }
+  Ok(color)
+}
+
+fn hue_of(rgb: [f64; 3], max: f64, delta: f64) -> f64 {
+  if delta == 0.0 {
+    return 0.0;
+  }
+  let hue = if max == rgb[0] {
+    ((rgb[1]-rgb[2])/delta).rem_euclid(6.0)
+  } else if max == rgb[1] {
+    (rgb[2]-rgb[0])/delta+2.0
+  } else {
+    (rgb[0]-rgb[1])/delta+4.0
+  };
+  hue*60.0
+}
+
+fn rgb_fraction(rgb: [u8; 3]) -> [f64; 3] {
+  rgb.map(|channel| f64::from(channel)/255.0)
+}
+
+//rgb from a hue and the chroma, plus the amount added to every channel
+fn rgb_from_chroma(hue: f64, chroma: f64, add: f64) -> [u8; 3] {
+  let hue = hue.rem_euclid(360.0)/60.0;
+  let x = chroma*(1.0-(hue.rem_euclid(2.0)-1.0).abs());
+  let rgb = match hue as u8 {
+    0 => [chroma, x, 0.0],
+    1 => [x, chroma, 0.0],
+    2 => [0.0, chroma, x],
+    3 => [0.0, x, chroma],
+    4 => [x, 0.0, chroma],
+    _ => [chroma, 0.0, x],
+  };
+  rgb.map(|channel| ((channel+add)*255.0).round().clamp(0.0, 255.0) as u8)
+}
+
+pub fn rgb_to_hsv(rgb: [u8; 3]) -> [f64; 3] {
+  let rgb = rgb_fraction(rgb);
+  let max = rgb[0].max(rgb[1]).max(rgb[2]);
+  let min = rgb[0].min(rgb[1]).min(rgb[2]);
+  let delta = max-min;
+  let saturation = if max == 0.0 { 0.0 } else { delta/max };
+  [hue_of(rgb, max, delta), saturation, max]
+}
+
+pub fn hsv_to_rgb(hsv: [f64; 3]) -> [u8; 3] {
+  let chroma = hsv[2]*hsv[1];
+  rgb_from_chroma(hsv[0], chroma, hsv[2]-chroma)
+}
+
+pub fn rgb_to_hsl(rgb: [u8; 3]) -> [f64; 3] {
+  let rgb = rgb_fraction(rgb);
+  let max = rgb[0].max(rgb[1]).max(rgb[2]);
+  let min = rgb[0].min(rgb[1]).min(rgb[2]);
+  let delta = max-min;
+  let lightness = (max+min)/2.0;
+  let saturation = if delta == 0.0 { 0.0 } else { delta/(1.0-(2.0*lightness-1.0).abs()) };
+  [hue_of(rgb, max, delta), saturation, lightness]
+}
+
+pub fn hsl_to_rgb(hsl: [f64; 3]) -> [u8; 3] {
+  let chroma = (1.0-(2.0*hsl[2]-1.0).abs())*hsl[1];
+  rgb_from_chroma(hsl[0], chroma, hsl[2]-chroma/2.0)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn hex_roundtrip() {
+    assert_eq!(to_hex([255, 16, 0, 128]), "#FF100080");
+    assert_eq!(parse_hex("#FF100080").unwrap(), [255, 16, 0, 128]);
+    assert_eq!(parse_hex("0a0b0c").unwrap(), [10, 11, 12, 255]);
+    assert!(parse_hex("#fff").is_err());
+    assert!(parse_hex("#zzzzzz").is_err());
+  }
+
+  #[test]
+  fn hsv_conversions() {
+    assert_eq!(rgb_to_hsv([255, 0, 0]), [0.0, 1.0, 1.0]);
+    assert_eq!(rgb_to_hsv([0, 0, 0]), [0.0, 0.0, 0.0]);
+    assert_eq!(hsv_to_rgb([120.0, 1.0, 1.0]), [0, 255, 0]);
+    assert_eq!(hsv_to_rgb([240.0, 0.5, 0.5]), [64, 64, 128]);
+    assert_eq!(hsv_to_rgb([360.0, 1.0, 1.0]), [255, 0, 0]);
+  }
+
+  #[test]
+  fn hsl_conversions() {
+    assert_eq!(rgb_to_hsl([255, 255, 255]), [0.0, 0.0, 1.0]);
+    assert_eq!(hsl_to_rgb([0.0, 1.0, 0.5]), [255, 0, 0]);
+    assert_eq!(hsl_to_rgb([180.0, 1.0, 0.25]), [0, 128, 128]);
+  }
+
+  #[test]
+  fn roundtrips_every_few_colors() {
+    for r in (0..=255).step_by(15) {
+      for g in (0..=255).step_by(17) {
+        for b in (0..=255).step_by(51) {
+          let rgb = [r as u8, g as u8, b as u8];
+          assert_eq!(hsv_to_rgb(rgb_to_hsv(rgb)), rgb);
+          assert_eq!(hsl_to_rgb(rgb_to_hsl(rgb)), rgb);
+        }
+      }
+    }
+  }
+}
@@ -0,0 +1,223 @@
+use yew::prelude::*;
+use web_sys::HtmlInputElement;
+use gloo_console::log;
+
+use bmp_editor::color::{hsl_to_rgb, hsv_to_rgb, parse_hex, rgb_to_hsl, rgb_to_hsv, to_hex};
+use bmp_editor::parse::input_to_color;
+
+//pick a color by saturation/value square and hue and alpha sliders, or type it as hex, hsv, hsl or (r, g, b, a)
+//emits the new color every time it changes
+
+//size of the saturation/value square in pixels
+const SQUARE_SIZE: f64 = 150.0;
+
+#[derive(PartialEq, Properties)]
+pub struct ColorPickerProps {
+  pub color: [u8; 4],
+  pub on_change: Callback<[u8; 4]>,
+}
+
+pub enum ColorPickerMessage {
+  SetHsv([f64; 3]),
+  SetColor([u8; 4]),
+  SetAlpha(u8),
+  SquareStart([f64; 2]),
+  SquareMove([f64; 2]),
+  SquareEnd,
+}
+
+pub struct ColorPicker {
+  //kept as hsv instead of rgb so the hue isn't lost when the color is grey
+  hsv: [f64; 3],
+  alpha: u8,
+  dragging: bool,
+}
+
+impl ColorPicker {
+  fn color(&self) -> [u8; 4] {
+    let rgb = hsv_to_rgb(self.hsv);
+    [rgb[0], rgb[1], rgb[2], self.alpha]
+  }
+
+  fn set_color(&mut self, color: [u8; 4]) {
+    let hsv = rgb_to_hsv([color[0], color[1], color[2]]);
+    //greys (and black) have no hue, keep the one from before
+    if hsv[1] == 0.0 || hsv[2] == 0.0 {
+      self.hsv = [self.hsv[0], hsv[1], hsv[2]];
+    } else {
+      self.hsv = hsv;
+    }
+    self.alpha = color[3];
+  }
+
+  fn set_from_square(&mut self, ctx: &Context<Self>, point: [f64; 2]) {
+    let saturation = (point[0]/SQUARE_SIZE).clamp(0.0, 1.0);
+    let value = 1.0-(point[1]/SQUARE_SIZE).clamp(0.0, 1.0);
+    self.hsv = [self.hsv[0], saturation, value];
+    ctx.props().on_change.emit(self.color());
+  }
+}
+
+impl Component for ColorPicker {
+  type Message = ColorPickerMessage;
+  type Properties = ColorPickerProps;
+
+  fn create(ctx: &Context<Self>) -> Self {
+    let mut color_picker = Self { hsv: [0.0, 0.0, 0.0], alpha: 255, dragging: false };
+    color_picker.set_color(ctx.props().color);
+    color_picker
+  }
+
+  fn changed(&mut self, ctx: &Context<Self>, _old_props: &Self::Properties) -> bool {
+    //changed from outside (eg, the eyedropper)
+    if ctx.props().color != self.color() {
+      self.set_color(ctx.props().color);
+    }
+    true
+  }
+
+  fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+    match msg {
+      Self::Message::SetHsv(hsv) => {
+        self.hsv = [hsv[0].clamp(0.0, 360.0), hsv[1].clamp(0.0, 1.0), hsv[2].clamp(0.0, 1.0)];
+        ctx.props().on_change.emit(self.color());
+        true
+      },
+      Self::Message::SetColor(color) => {
+        self.set_color(color);
+        ctx.props().on_change.emit(self.color());
+        true
+      },
+      Self::Message::SetAlpha(alpha) => {
+        self.alpha = alpha;
+        ctx.props().on_change.emit(self.color());
+        true
+      },
+      Self::Message::SquareStart(point) => {
+        self.dragging = true;
+        self.set_from_square(ctx, point);
+        true
+      },
+      Self::Message::SquareMove(point) => {
+        if self.dragging {
+          self.set_from_square(ctx, point);
+          true
+        } else {
+          false
+        }
+      },
+      Self::Message::SquareEnd => {
+        self.dragging = false;
+        false
+      },
+    }
+  }
+
+  fn view(&self, ctx: &Context<Self>) -> Html {
+    let color = self.color();
+    let hsv = self.hsv;
+    let hsl = rgb_to_hsl([color[0], color[1], color[2]]);
+    let rgba_text = format!("({}, {}, {}, {})", color[0], color[1], color[2], color[3]);
+
+    let square_mousedown = ctx.link().callback(|e: MouseEvent| {
+      e.prevent_default();
+      Self::Message::SquareStart([f64::from(e.offset_x()), f64::from(e.offset_y())])
+    });
+    let square_mousemove = ctx.link().callback(|e: MouseEvent| Self::Message::SquareMove([f64::from(e.offset_x()), f64::from(e.offset_y())]));
+    let square_mouseup = ctx.link().callback(|_e: MouseEvent| Self::Message::SquareEnd);
+
+    let hue_input = ctx.link().batch_callback(move |e: InputEvent| {
+      let input: HtmlInputElement = e.target_unchecked_into();
+      input.value().parse::<f64>().ok().map(|hue| Self::Message::SetHsv([hue, hsv[1], hsv[2]]))
+    });
+
+    let alpha_input = ctx.link().batch_callback(|e: InputEvent| {
+      let input: HtmlInputElement = e.target_unchecked_into();
+      input.value().parse::<u8>().ok().map(Self::Message::SetAlpha)
+    });
+
+    let hex_change = ctx.link().batch_callback(|e: Event| {
+      let input: HtmlInputElement = e.target_unchecked_into();
+      match parse_hex(&input.value()) {
+        Ok(color) => Some(Self::Message::SetColor(color)),
+        Err(e) => {
+          log!(e);
+          None
+        },
+      }
+    });
+
+    let rgba_change = ctx.link().batch_callback(|e: Event| {
+      let input: HtmlInputElement = e.target_unchecked_into();
+      match input_to_color(&input.value()) {
+        Ok(color) => Some(Self::Message::SetColor(color)),
+        Err(e) => {
+          log!(e);
+          None
+        },
+      }
+    });
+
+    //one of the h, s, v or h, s, l number fields. hue is in degrees, the others in percent
+    let number_field = |name: &'static str, value: f64, index: usize, is_hsl: bool| {
+      let alpha = color[3];
+      let onchange = ctx.link().batch_callback(move |e: Event| {
+        let input: HtmlInputElement = e.target_unchecked_into();
+        let number = match input.value().trim().parse::<f64>() {
+          Ok(number) => number,
+          Err(_) => {
+            log!(format!("\"{}\" is not a number", input.value()));
+            return None;
+          },
+        };
+        let number = if index == 0 { number } else { number/100.0 };
+        if is_hsl {
+          let mut new_hsl = hsl;
+          new_hsl[index] = number;
+          let rgb = hsl_to_rgb([new_hsl[0].clamp(0.0, 360.0), new_hsl[1].clamp(0.0, 1.0), new_hsl[2].clamp(0.0, 1.0)]);
+          Some(Self::Message::SetColor([rgb[0], rgb[1], rgb[2], alpha]))
+        } else {
+          let mut new_hsv = hsv;
+          new_hsv[index] = number;
+          Some(Self::Message::SetHsv(new_hsv))
+        }
+      });
+      let shown_value = if index == 0 { value.round() } else { (value*100.0).round() };
+      html! {
+        <label>{ name }<input type="number" min="0" max={if index == 0 { "360" } else { "100" }} value={shown_value.to_string()} {onchange}/></label>
+      }
+    };
+
+    let hue_rgb = hsv_to_rgb([hsv[0], 1.0, 1.0]);
+    let square_style = format!("width: {}px; height: {}px; background: linear-gradient(to top, #000, transparent), linear-gradient(to right, #fff, rgb({}, {}, {}));", SQUARE_SIZE, SQUARE_SIZE, hue_rgb[0], hue_rgb[1], hue_rgb[2]);
+    let marker_style = format!("left: {}px; top: {}px;", hsv[1]*SQUARE_SIZE, (1.0-hsv[2])*SQUARE_SIZE);
+    let swatch_style = format!("background-color: rgba({}, {}, {}, {});", color[0], color[1], color[2], f64::from(color[3])/255.0);
+
+    html! {
+      <div class="color-picker">
+        <div class="sv-square" style={square_style} onmousedown={square_mousedown} onmousemove={square_mousemove} onmouseup={square_mouseup.clone()} onmouseleave={square_mouseup}>
+          <div class="sv-marker" style={marker_style}></div>
+        </div>
+        <div class="color-sliders">
+          <div class="color-swatch"><div style={swatch_style}></div></div>
+          <label>{ "Hue" }<input type="range" min="0" max="360" value={hsv[0].round().to_string()} oninput={hue_input}/></label>
+          <label>{ "Alpha" }<input type="range" min="0" max="255" value={color[3].to_string()} oninput={alpha_input}/></label>
+        </div>
+        <div class="color-fields">
+          <label>{ "Hex " }<input class="hex-input" value={to_hex(color)} onchange={hex_change}/></label>
+          <label>{ "RGBA " }<input class="rgba-input" value={rgba_text} onchange={rgba_change}/></label>
+          <br/>
+          { "HSV " }
+          { number_field("H", hsv[0], 0, false) }
+          { number_field("S", hsv[1], 1, false) }
+          { number_field("V", hsv[2], 2, false) }
+          <br/>
+          { "HSL " }
+          { number_field("H", hsl[0], 0, true) }
+          { number_field("S", hsl[1], 1, true) }
+          { number_field("L", hsl[2], 2, true) }
+        </div>
+      </div>
+    }
+  }
+}
@@ -0,0 +1,95 @@
+use yew::prelude::*;
+
+use bmp_editor::editor::color_text;
+
+//color table of an indexed (1, 2, 4 or 8 bit) image. clicking an entry makes it the tool color, so drawing uses that index,
+//and an entry can be set to the tool color, which changes every pixel using it
+
+#[derive(PartialEq, Properties)]
+pub struct ColorTablePanelProps {
+  //none if the image isn't indexed
+  pub color_table: Option<Vec<[u8; 4]>>,
+  pub tool_color: [u8; 4],
+  pub select_color_callback: Callback<[u8; 4]>,
+  pub change_entry_callback: Callback<(u8, [u8; 4])>,
+}
+
+pub enum ColorTablePanelMessage {
+  Select(usize),
+  ChangeEntry,
+}
+
+pub struct ColorTablePanel {
+  selected: Option<usize>,
+}
+
+impl Component for ColorTablePanel {
+  type Message = ColorTablePanelMessage;
+  type Properties = ColorTablePanelProps;
+
+  fn create(_ctx: &Context<Self>) -> Self {
+    Self { selected: None }
+  }
+
+  fn changed(&mut self, ctx: &Context<Self>, _old_props: &Self::Properties) -> bool {
+    //a different image might have a smaller table
+    let table_len = ctx.props().color_table.as_ref().map(|color_table| color_table.len()).unwrap_or(0);
+    if self.selected.map(|selected| selected >= table_len).unwrap_or(false) {
+      self.selected = None;
+    }
+    true
+  }
+
+  fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+    match msg {
+      Self::Message::Select(index) => {
+        self.selected = Some(index);
+        if let Some(color) = ctx.props().color_table.as_ref().and_then(|color_table| color_table.get(index)) {
+          ctx.props().select_color_callback.emit(*color);
+        }
+        true
+      },
+      Self::Message::ChangeEntry => {
+        if let Some(selected) = self.selected {
+          ctx.props().change_entry_callback.emit((selected as u8, ctx.props().tool_color));
+        }
+        false
+      },
+    }
+  }
+
+  fn view(&self, ctx: &Context<Self>) -> Html {
+    let color_table = match &ctx.props().color_table {
+      Some(color_table) => color_table,
+      None => return html! {},
+    };
+
+    let entries = color_table.iter().enumerate().map(|(index, color)| {
+      let onclick = ctx.link().callback(move |_: MouseEvent| Self::Message::Select(index));
+      let class = if self.selected == Some(index) { "swatch selected-swatch" } else { "swatch" };
+      let style = format!("background-color: rgb({}, {}, {});", color[0], color[1], color[2]);
+      html! {
+        <button {class} {style} title={format!("{}: {}", index, color_text(*color))} {onclick}></button>
+      }
+    }).collect::<Html>();
+
+    let change_entry = ctx.link().callback(|_| Self::Message::ChangeEntry);
+
+    html! {
+      <div id={"color-table-panel"}>
+        <h3>{ format!("Color Table ({} colors)", color_table.len()) }</h3>
+        <div class="swatches">
+          { entries }
+        </div>
+        if let Some(selected) = self.selected {
+          <p>
+            { format!("Entry {}: {} ", selected, color_text(color_table[selected])) }
+            <button onclick={change_entry}>{ "Set to Tool Color" }</button>
+          </p>
+        } else {
+          <p class="palette-tip">{ "Click an entry to draw with it." }</p>
+        }
+      </div>
+    }
+  }
+}
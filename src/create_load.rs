@@ -4,6 +4,12 @@ use gloo_console::log;
 use web_sys::HtmlInputElement;
 use gloo::file::callbacks::FileReader;
 use gloo::file::File;
+use wasm_bindgen_futures::spawn_local;
+
+use bmp_editor::rle::decode_rle;
+use bmp_editor::validate::validate_bmp;
+
+use crate::image_import::{IMPORT_TYPES, decode_image, is_bmp};
 
 // create
 
@@ -92,6 +98,8 @@ impl Component for Create {
 pub struct LoadProps {
   pub send_bmp_callback: Callback<BMP>,
   pub show: bool,
+  //what is wrong with a file that couldn't be opened
+  pub error_callback: Callback<String>,
 }
 
 pub enum LoadMessage {
@@ -99,6 +107,7 @@ pub enum LoadMessage {
   Hide,
   GenBMP(File),
   LoadBMP(BMP),
+  LoadFailed(String),
 }
 
 pub struct Load {
@@ -126,10 +135,43 @@ impl Component for Load {
       },
       Self::Message::GenBMP(file) => {
         let link = ctx.link().clone();
+        //other formats are converted by the browser
+        if !is_bmp(&file.raw_mime_type(), &file.name()) {
+          spawn_local(async move {
+            match decode_image(file.as_ref()).await {
+              Ok(new_bmp) => link.send_message(Self::Message::LoadBMP(new_bmp)),
+              Err(e) => {
+                log!("Could not decode image", e);
+                link.send_message(Self::Message::LoadFailed("The browser couldn't decode the image".to_string()));
+              },
+            }
+          });
+          return false;
+        }
         self.reader = Some(gloo::file::callbacks::read_as_bytes(&file, move |res| {
-          //res.expect("Error reading file as bytes");
+          let contents = match res {
+            Ok(contents) => contents,
+            Err(e) => {
+              link.send_message(Self::Message::LoadFailed(format!("The file couldn't be read: {}", e)));
+              return;
+            },
+          };
+          //check everything before bmp_rust reads it, it panics on broken files
+          if let Err(e) = validate_bmp(&contents) {
+            link.send_message(Self::Message::LoadFailed(e));
+            return;
+          }
           let mut new_bmp = BMP::new(1, 1, None);
-          new_bmp.contents = res.unwrap();
+          new_bmp.contents = contents;
+          //compressed indexed images are decoded, since nothing else can read them
+          match decode_rle(&new_bmp) {
+            Ok(Some(decoded)) => new_bmp = decoded,
+            Ok(None) => {},
+            Err(e) => {
+              link.send_message(Self::Message::LoadFailed(format!("The run length encoded pixels couldn't be decoded: {}", e)));
+              return;
+            },
+          }
           link.send_message(Self::Message::LoadBMP(new_bmp));
           link.send_message(Self::Message::Hide);
         }));
@@ -139,6 +181,10 @@ impl Component for Load {
         let _ = ctx.props().send_bmp_callback.emit(bmp);
         true
       }
+      Self::Message::LoadFailed(error) => {
+        ctx.props().error_callback.emit(error);
+        true
+      }
     }
   }
 
@@ -175,7 +221,7 @@ impl Component for Load {
     html! {
       <div style={"display: ".to_string()+&self.display}>
         <label for="file-upload-initial">{ "File upload:" }</label>
-        <input ref={file_input_ref} id="file-upload-initial" type="file" name="file-upload-initial" accept="image/bmp" multiple={false} />
+        <input ref={file_input_ref} id="file-upload-initial" type="file" name="file-upload-initial" accept={IMPORT_TYPES} multiple={false} />
         <br class="mobile-only"/>
         <button onclick={load_bmp}>{ "Load" }</button>
       </div>
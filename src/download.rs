@@ -0,0 +1,33 @@
+use wasm_bindgen::JsCast;
+use web_sys::{Blob, Url, HtmlElement};
+use js_sys::{Uint8Array, Array};
+use gloo_utils::document;
+use gloo::timers::callback::Timeout;
+
+//make the browser download the bytes as a file
+pub fn download_bytes(bytes: &[u8], file_name: &str) {
+  let bytes_array = Array::new();
+  bytes_array.push(&Uint8Array::from(bytes).buffer());
+  let blob = Blob::new_with_u8_array_sequence(&bytes_array).unwrap();
+  let obj_url = Url::create_object_url_with_blob(&blob).unwrap();
+  let download_link: HtmlElement = document().create_element("a").unwrap().unchecked_into();
+  download_link.set_attribute("href", &obj_url).unwrap();
+  download_link.set_attribute("download", file_name).unwrap();
+  //firefox and safari cancel the download if the link isn't in the page, or the url is revoked right after clicking
+  let body = document().body().unwrap();
+  body.append_child(&download_link).unwrap();
+  download_link.click();
+  Timeout::new(1000, move || {
+    download_link.remove();
+    Url::revoke_object_url(&obj_url).unwrap();
+  }).forget();
+}
+
+//prompt for the file name, then download. nothing is downloaded if the prompt is cancelled or left empty
+pub fn prompt_download(bytes: &[u8], default_file_name: &str) {
+  let window = web_sys::window().unwrap();
+  let file_name = window.prompt_with_message_and_default("File name?", default_file_name).unwrap_or(None).unwrap_or_default();
+  if file_name != "" {
+    download_bytes(bytes, &file_name);
+  }
+}
@@ -0,0 +1,793 @@
+use bmp_rust::bmp::{BMP, ErrorKind};
+
+use crate::bitfields::{MaskedLayout, edit_as_standard, is_standard};
+use crate::bytes::ByteReader;
+use crate::header::set_resolution;
+use crate::history::{History, HistoryEntry, HistoryInfo};
+use crate::indexed::{IndexedLayout, nearest_index};
+use crate::render::PixelReader;
+use crate::script::{ScriptError, parse_script_lines, to_script};
+use crate::shapes::{DragShape, shape_rows};
+use crate::stroke::line_pixels;
+
+//the editing operations and the document they are done on, without anything yew or browser specific
+//App (main.rs) turns its messages into these commands, and the commands can also be run without a ui
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum EditorCommand {
+  ChangePixels(Vec<[u16; 2]>, [u8; 4]),
+  ChangePixel([u16; 2], [u8; 4]),
+  FillBucket([u16; 2], [u8; 4]),
+  Invert,
+  Greyscale,
+  Rotate,
+  DrawLine([[u16; 2]; 2], [u8; 4]),
+  DrawRect([[u16; 2]; 2], [u8; 4]),
+  //center, then x and y lengths
+  DrawEllipse([[u16; 2]; 2], [u8; 4]),
+  GaussianBlur(u8),
+  BoxBlur(u8),
+  MedianFilter(u8),
+  //change an entry of an indexed image's color table
+  ChangeTableColor(u8, [u8; 4]),
+  //horizontal and vertical pixels per meter, only changes the header
+  SetResolution(u32, u32),
+}
+
+impl EditorCommand {
+  //do the operation on the bmp
+  pub fn run(&self, bmp: &mut BMP) -> Result<(), ErrorKind> {
+    //bmp_rust panics on pixels outside the image, so they are an error here instead
+    let dib_header = bmp.get_dib_header()?;
+    self.check_coords(dib_header.width, dib_header.height.unsigned_abs())?;
+    if let Some(layout) = IndexedLayout::of(bmp)? {
+      return self.run_indexed(bmp, &layout);
+    }
+    //16 and 24 bit images, and 32 bit ones with other masks or headers, are edited as a copy bmp_rust can change
+    //(the resolution is only in the header, so it is changed directly)
+    if !is_standard(bmp) && !matches!(self, EditorCommand::SetResolution(..)) {
+      if let Some(layout) = MaskedLayout::of(bmp)? {
+        return edit_as_standard(bmp, &layout, |standard| self.run(standard));
+      }
+    }
+    match self {
+      EditorCommand::ChangePixels(pixels, color) => {
+        //BMP::change_color_of_pixels overflows on large images, so change them one by one
+        let dib_header = bmp.get_dib_header()?;
+        let header = bmp.get_header();
+        for pixel in pixels {
+          bmp.change_color_of_pixel_efficient(pixel[0], pixel[1], *color, &dib_header, &header)?;
+        }
+        Ok(())
+      },
+      EditorCommand::ChangePixel(coord, color) => {
+        bmp.change_color_of_pixel(coord[0], coord[1], *color)
+      },
+      EditorCommand::FillBucket(coord, color) => {
+        bmp.fill_bucket(*color, coord[0] as usize, coord[1] as usize).map(|_| ())
+      },
+      EditorCommand::Invert => {
+        bmp.invert(None)
+      },
+      EditorCommand::Greyscale => {
+        bmp.greyscale()
+      },
+      EditorCommand::Rotate => {
+        //rotate 90 degrees around the center
+        let dib_header = bmp.get_dib_header()?;
+        let center_pixel: [u16; 2] = [(f64::from(dib_header.width)/2.0).round() as u16, (f64::from(dib_header.height.abs())/2.0).round() as u16];
+        bmp.rotate(90.0, Some(center_pixel))
+      },
+      EditorCommand::DrawLine(endpoints, color) => {
+        //BMP::draw_line panics on some diagonal lines, so do the line here
+        let dib_header = bmp.get_dib_header()?;
+        let header = bmp.get_header();
+        for pixel in line_pixels(endpoints[0], endpoints[1]) {
+          bmp.change_color_of_pixel_efficient(pixel[0], pixel[1], *color, &dib_header, &header)?;
+        }
+        Ok(())
+      },
+      EditorCommand::DrawRect(endpoints, color) => {
+        //BMP::draw_rectangle underflows on reversed corners and rects under 3 pixels wide or tall,
+        //and the inside is the same color as the edges anyway, so fill the rows here
+        let dib_header = bmp.get_dib_header()?;
+        let header = bmp.get_header();
+        for row in shape_rows(DragShape::Rect, *endpoints) {
+          for x in row[1]..=row[2] {
+            bmp.change_color_of_pixel_efficient(x, row[0], *color, &dib_header, &header)?;
+          }
+        }
+        Ok(())
+      },
+      EditorCommand::DrawEllipse(ellipse_args, color) => {
+        bmp.draw_ellipse(ellipse_args[0], ellipse_args[1][0], ellipse_args[1][1], *color, Some(*color), true)
+      },
+      EditorCommand::GaussianBlur(radius) => {
+        bmp.gaussian_blur(*radius)
+      },
+      EditorCommand::BoxBlur(radius) => {
+        bmp.box_blur(*radius)
+      },
+      EditorCommand::MedianFilter(radius) => {
+        bmp.median_filter(*radius)
+      },
+      EditorCommand::ChangeTableColor(..) => {
+        Err(ErrorKind::InvalidParameter("only indexed (1, 2, 4 or 8 bit) images have a color table".to_string()))
+      },
+      EditorCommand::SetResolution(x, y) => {
+        set_resolution(bmp, [*x, *y])
+      },
+    }
+  }
+
+  //every pixel the command gives (and the whole ellipse) has to be inside the image
+  fn check_coords(&self, width: u32, height: u32) -> Result<(), ErrorKind> {
+    let inside = |coord: &[u16; 2]| u32::from(coord[0]) < width && u32::from(coord[1]) < height;
+    let outside = match self {
+      EditorCommand::ChangePixels(pixels, _) => pixels.iter().find(|pixel| !inside(pixel)).copied(),
+      EditorCommand::ChangePixel(coord, _) | EditorCommand::FillBucket(coord, _) => Some(*coord).filter(|coord| !inside(coord)),
+      EditorCommand::DrawLine(endpoints, _) | EditorCommand::DrawRect(endpoints, _) => endpoints.iter().find(|endpoint| !inside(endpoint)).copied(),
+      EditorCommand::DrawEllipse(ellipse_args, _) => {
+        let [center, lengths] = ellipse_args;
+        let fits = center[0] >= lengths[0] && center[1] >= lengths[1] && u32::from(center[0])+u32::from(lengths[0]) < width && u32::from(center[1])+u32::from(lengths[1]) < height;
+        if !fits {
+          return Err(ErrorKind::InvalidParameter(format!("{} doesn't fit in the {}x{} image", self.name(), width, height)));
+        }
+        None
+      },
+      _ => None,
+    };
+    match outside {
+      Some(coord) => Err(ErrorKind::InvalidParameter(format!("({}, {}) is outside the {}x{} image", coord[0], coord[1], width, height))),
+      None => Ok(()),
+    }
+  }
+
+  //indexed images are painted with the color table index closest to the color,
+  //and invert and greyscale change the color table instead of the pixels
+  fn run_indexed(&self, bmp: &mut BMP, layout: &IndexedLayout) -> Result<(), ErrorKind> {
+    let colors = layout.colors(bmp);
+    match self {
+      EditorCommand::ChangePixels(pixels, color) => {
+        let index = nearest_index(&colors, *color);
+        for pixel in pixels {
+          layout.set_index(bmp, pixel[0], pixel[1], index)?;
+        }
+        Ok(())
+      },
+      EditorCommand::ChangePixel(coord, color) => {
+        layout.set_index(bmp, coord[0], coord[1], nearest_index(&colors, *color))
+      },
+      EditorCommand::FillBucket(coord, color) => {
+        fill_indexes(bmp, layout, *coord, nearest_index(&colors, *color))
+      },
+      EditorCommand::Invert | EditorCommand::Greyscale => {
+        for (index, color) in colors.iter().enumerate() {
+          let new_color = if *self == EditorCommand::Invert {
+            [255-color[0], 255-color[1], 255-color[2], color[3]]
+          } else {
+            BMP::rgb_to_grayscale(*color)
+          };
+          layout.set_color(bmp, index as u8, new_color)?;
+        }
+        Ok(())
+      },
+      EditorCommand::DrawLine(endpoints, color) | EditorCommand::DrawRect(endpoints, color) | EditorCommand::DrawEllipse(endpoints, color) => {
+        let shape = match self {
+          EditorCommand::DrawLine(..) => DragShape::Line,
+          EditorCommand::DrawRect(..) => DragShape::Rect,
+          _ => DragShape::Ellipse,
+        };
+        let index = nearest_index(&colors, *color);
+        for row in shape_rows(shape, *endpoints) {
+          for x in row[1]..=row[2] {
+            layout.set_index(bmp, x, row[0], index)?;
+          }
+        }
+        Ok(())
+      },
+      EditorCommand::ChangeTableColor(index, color) => {
+        layout.set_color(bmp, *index, *color)
+      },
+      EditorCommand::SetResolution(x, y) => {
+        set_resolution(bmp, [*x, *y])
+      },
+      EditorCommand::Rotate | EditorCommand::GaussianBlur(_) | EditorCommand::BoxBlur(_) | EditorCommand::MedianFilter(_) => {
+        Err(ErrorKind::InvalidParameter(format!("{} needs a 24 or 32 bit image, not an indexed one", self.name())))
+      },
+    }
+  }
+
+  //name shown in the history
+  pub fn name(&self) -> String {
+    match self {
+      EditorCommand::ChangePixels(pixels, color) => format!("Change {} pixels to {}", pixels.len(), color_text(*color)),
+      EditorCommand::ChangePixel(coord, color) => format!("Pixel ({}, {}) to {}", coord[0], coord[1], color_text(*color)),
+      EditorCommand::FillBucket(coord, color) => format!("Bucket fill ({}, {}) with {}", coord[0], coord[1], color_text(*color)),
+      EditorCommand::Invert => "Invert".to_string(),
+      EditorCommand::Greyscale => "Greyscale".to_string(),
+      EditorCommand::Rotate => "Rotate 90 degrees".to_string(),
+      EditorCommand::DrawLine(endpoints, _) => format!("Line ({}, {})-({}, {})", endpoints[0][0], endpoints[0][1], endpoints[1][0], endpoints[1][1]),
+      EditorCommand::DrawRect(endpoints, _) => format!("Rect ({}, {})-({}, {})", endpoints[0][0], endpoints[0][1], endpoints[1][0], endpoints[1][1]),
+      EditorCommand::DrawEllipse(ellipse_args, _) => format!("Ellipse ({}, {}) x={} y={}", ellipse_args[0][0], ellipse_args[0][1], ellipse_args[1][0], ellipse_args[1][1]),
+      EditorCommand::GaussianBlur(radius) => format!("Gaussian blur r={}", radius),
+      EditorCommand::BoxBlur(radius) => format!("Box blur r={}", radius),
+      EditorCommand::MedianFilter(radius) => format!("Median filter r={}", radius),
+      EditorCommand::ChangeTableColor(index, color) => format!("Color table {} to {}", index, color_text(*color)),
+      EditorCommand::SetResolution(x, y) => format!("Resolution {}x{} pixels/meter", x, y),
+    }
+  }
+
+  //color the command draws with, if it has one
+  pub fn color(&self) -> Option<[u8; 4]> {
+    match self {
+      EditorCommand::ChangePixels(_, color) | EditorCommand::ChangePixel(_, color) | EditorCommand::FillBucket(_, color) => Some(*color),
+      EditorCommand::DrawLine(_, color) | EditorCommand::DrawRect(_, color) | EditorCommand::DrawEllipse(_, color) => Some(*color),
+      EditorCommand::ChangeTableColor(_, color) => Some(*color),
+      _ => None,
+    }
+  }
+
+  //one byte for the type of command, then its coordinates, color, or radius
+  pub fn write_bytes(&self, bytes: &mut Vec<u8>) {
+    match self {
+      EditorCommand::ChangePixels(pixels, color) => {
+        bytes.push(0);
+        bytes.extend((pixels.len() as u32).to_le_bytes());
+        for pixel in pixels {
+          write_coords(bytes, pixel);
+        }
+        bytes.extend(color);
+      },
+      EditorCommand::ChangePixel(coord, color) => {
+        bytes.push(1);
+        write_coords(bytes, coord);
+        bytes.extend(color);
+      },
+      EditorCommand::FillBucket(coord, color) => {
+        bytes.push(2);
+        write_coords(bytes, coord);
+        bytes.extend(color);
+      },
+      EditorCommand::Invert => bytes.push(3),
+      EditorCommand::Greyscale => bytes.push(4),
+      EditorCommand::Rotate => bytes.push(5),
+      EditorCommand::DrawLine(coords, color) | EditorCommand::DrawRect(coords, color) | EditorCommand::DrawEllipse(coords, color) => {
+        bytes.push(match self {
+          EditorCommand::DrawLine(..) => 6,
+          EditorCommand::DrawRect(..) => 7,
+          _ => 8,
+        });
+        write_coords(bytes, &coords[0]);
+        write_coords(bytes, &coords[1]);
+        bytes.extend(color);
+      },
+      EditorCommand::GaussianBlur(radius) => bytes.extend([9, *radius]),
+      EditorCommand::BoxBlur(radius) => bytes.extend([10, *radius]),
+      EditorCommand::MedianFilter(radius) => bytes.extend([11, *radius]),
+      EditorCommand::ChangeTableColor(index, color) => {
+        bytes.extend([12, *index]);
+        bytes.extend(color);
+      },
+      EditorCommand::SetResolution(x, y) => {
+        bytes.push(13);
+        bytes.extend(x.to_le_bytes());
+        bytes.extend(y.to_le_bytes());
+      },
+    }
+  }
+
+  pub(crate) fn read_bytes(reader: &mut ByteReader) -> Option<Self> {
+    let command = match reader.take(1)?[0] {
+      0 => {
+        let mut pixels: Vec<[u16; 2]> = Vec::new();
+        for _ in 0..reader.take_u32()? {
+          pixels.push(read_coords(reader)?);
+        }
+        EditorCommand::ChangePixels(pixels, reader.take_array()?)
+      },
+      1 => EditorCommand::ChangePixel(read_coords(reader)?, reader.take_array()?),
+      2 => EditorCommand::FillBucket(read_coords(reader)?, reader.take_array()?),
+      3 => EditorCommand::Invert,
+      4 => EditorCommand::Greyscale,
+      5 => EditorCommand::Rotate,
+      6 => EditorCommand::DrawLine([read_coords(reader)?, read_coords(reader)?], reader.take_array()?),
+      7 => EditorCommand::DrawRect([read_coords(reader)?, read_coords(reader)?], reader.take_array()?),
+      8 => EditorCommand::DrawEllipse([read_coords(reader)?, read_coords(reader)?], reader.take_array()?),
+      9 => EditorCommand::GaussianBlur(reader.take(1)?[0]),
+      10 => EditorCommand::BoxBlur(reader.take(1)?[0]),
+      11 => EditorCommand::MedianFilter(reader.take(1)?[0]),
+      12 => EditorCommand::ChangeTableColor(reader.take(1)?[0], reader.take_array()?),
+      13 => EditorCommand::SetResolution(reader.take_u32()?, reader.take_u32()?),
+      _ => return None,
+    };
+    Some(command)
+  }
+}
+
+//4-connected flood fill of the pixels with the same index as the starting one
+fn fill_indexes(bmp: &mut BMP, layout: &IndexedLayout, start: [u16; 2], index: u8) -> Result<(), ErrorKind> {
+  let old_index = layout.index_at(bmp, start[0], start[1])?;
+  if old_index == index {
+    return Ok(());
+  }
+  let mut stack: Vec<[u16; 2]> = vec![start];
+  while let Some([x, y]) = stack.pop() {
+    if layout.index_at(bmp, x, y)? != old_index {
+      continue;
+    }
+    layout.set_index(bmp, x, y, index)?;
+    if x > 0 {
+      stack.push([x-1, y]);
+    }
+    if u32::from(x)+1 < layout.width {
+      stack.push([x+1, y]);
+    }
+    if y > 0 {
+      stack.push([x, y-1]);
+    }
+    if u32::from(y)+1 < layout.height {
+      stack.push([x, y+1]);
+    }
+  }
+  Ok(())
+}
+
+fn write_coords(bytes: &mut Vec<u8>, coords: &[u16; 2]) {
+  bytes.extend(coords[0].to_le_bytes());
+  bytes.extend(coords[1].to_le_bytes());
+}
+
+fn read_coords(reader: &mut ByteReader) -> Option<[u16; 2]> {
+  Some([reader.take_u16()?, reader.take_u16()?])
+}
+
+pub fn color_text(color: [u8; 4]) -> String {
+  format!("({}, {}, {}, {})", color[0], color[1], color[2], color[3])
+}
+
+//the image being edited and its undo history
+pub struct Document {
+  bmp: BMP,
+  history: History,
+}
+
+impl Document {
+  pub fn new(bmp: BMP, history_budget: usize) -> Self {
+    Self { bmp, history: History::new(history_budget) }
+  }
+
+  //eg when restoring a saved session
+  pub fn with_history(bmp: BMP, history: History) -> Self {
+    Self { bmp, history }
+  }
+
+  pub fn bmp(&self) -> &BMP {
+    &self.bmp
+  }
+
+  pub fn history(&self) -> &History {
+    &self.history
+  }
+
+  pub fn history_info(&self) -> HistoryInfo {
+    self.history.info()
+  }
+
+  //edit script of everything in the undo history
+  pub fn history_script(&self) -> String {
+    to_script(&self.history.commands())
+  }
+
+  pub fn set_history_budget(&mut self, budget: usize) {
+    self.history.set_budget(budget);
+  }
+
+  pub fn color_of_pixel(&self, coord: [u16; 2]) -> Result<[u8; 4], ErrorKind> {
+    PixelReader::new(&self.bmp)?.color(coord[0], coord[1])
+  }
+
+  //none if the image isn't indexed
+  pub fn color_table(&self) -> Option<Vec<[u8; 4]>> {
+    IndexedLayout::of(&self.bmp).ok().flatten().map(|layout| layout.colors(&self.bmp))
+  }
+
+  //average color of the size x size square around the pixel (only the part inside the image)
+  pub fn average_color(&self, center: [u16; 2], size: u8) -> Result<[u8; 4], ErrorKind> {
+    let dib_header = self.bmp.get_dib_header()?;
+    let reader = PixelReader::new(&self.bmp)?;
+    let half = i32::from(size.max(1)/2);
+    let mut totals: [u32; 4] = [0; 4];
+    let mut count: u32 = 0;
+    for y in (i32::from(center[1])-half)..=(i32::from(center[1])+half) {
+      for x in (i32::from(center[0])-half)..=(i32::from(center[0])+half) {
+        if x < 0 || y < 0 || x >= dib_header.width as i32 || y >= dib_header.height.abs() {
+          continue;
+        }
+        let color = reader.color(x as u16, y as u16)?;
+        for i in 0..4 {
+          totals[i] += u32::from(color[i]);
+        }
+        count += 1;
+      }
+    }
+    if count == 0 {
+      return self.color_of_pixel(center);
+    }
+    Ok(totals.map(|total| (f64::from(total)/f64::from(count)).round() as u8))
+  }
+
+  //run the command and add it to the history. if it fails, the image is left unchanged
+  pub fn apply(&mut self, command: EditorCommand) -> Result<(), ErrorKind> {
+    let mut new_bmp = self.bmp.clone();
+    command.run(&mut new_bmp)?;
+    self.history.push(HistoryEntry::new(command.name(), vec![command], &self.bmp, &new_bmp));
+    self.bmp = new_bmp;
+    Ok(())
+  }
+
+  //run several commands as one edit, so they are undone together (eg playing a macro)
+  pub fn apply_all(&mut self, name: String, commands: &[EditorCommand]) -> Result<(), ErrorKind> {
+    let mut new_bmp = self.bmp.clone();
+    for command in commands {
+      command.run(&mut new_bmp)?;
+    }
+    self.history.push(HistoryEntry::new(name, commands.to_vec(), &self.bmp, &new_bmp));
+    self.bmp = new_bmp;
+    Ok(())
+  }
+
+  //run an edit script as one edit. if a command can't be done on this image (eg it is outside of it),
+  //the error has its line number and the image is left unchanged. returns the commands that were run
+  pub fn apply_script(&mut self, script: &str) -> Result<Vec<EditorCommand>, Vec<ScriptError>> {
+    let lines = parse_script_lines(script)?;
+    let mut new_bmp = self.bmp.clone();
+    for (line, command) in lines.iter() {
+      command.run(&mut new_bmp).map_err(|e| vec![ScriptError { line: *line, message: format!("{} failed: {}", command.name(), e) }])?;
+    }
+    let commands: Vec<EditorCommand> = lines.into_iter().map(|(_, command)| command).collect();
+    if !commands.is_empty() {
+      self.history.push(HistoryEntry::new(format!("Script ({} operations)", commands.len()), commands.clone(), &self.bmp, &new_bmp));
+      self.bmp = new_bmp;
+    }
+    Ok(commands)
+  }
+
+  //these return false if there was nothing to undo/redo/jump to
+
+  pub fn undo(&mut self) -> bool {
+    self.replace_bmp(|history, bmp| history.undo(bmp))
+  }
+
+  pub fn redo(&mut self) -> bool {
+    self.replace_bmp(|history, bmp| history.redo(bmp))
+  }
+
+  pub fn jump_to(&mut self, undo_steps: usize) -> bool {
+    self.replace_bmp(|history, bmp| history.jump_to(undo_steps, bmp))
+  }
+
+  fn replace_bmp(&mut self, change: impl FnOnce(&mut History, &BMP) -> Option<BMP>) -> bool {
+    if let Some(new_bmp) = change(&mut self.history, &self.bmp) {
+      self.bmp = new_bmp;
+      true
+    } else {
+      false
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::export::{ExportFormat, export_bmp};
+  use crate::header::resolution;
+  use crate::history::DEFAULT_HISTORY_BUDGET;
+  use crate::indexed::new_indexed;
+
+  const RED: [u8; 4] = [255, 0, 0, 255];
+  const WHITE: [u8; 4] = [255, 255, 255, 255];
+
+  fn new_document(width: u32, height: i32) -> Document {
+    Document::new(BMP::new(height, width, None), DEFAULT_HISTORY_BUDGET)
+  }
+
+  fn color_at(document: &Document, x: u16, y: u16) -> [u8; 4] {
+    document.color_of_pixel([x, y]).unwrap()
+  }
+
+  #[test]
+  fn change_pixel_only_changes_that_pixel() {
+    let mut document = new_document(4, 4);
+    document.apply(EditorCommand::ChangePixel([1, 2], RED)).unwrap();
+    assert_eq!(color_at(&document, 1, 2), RED);
+    assert_eq!(color_at(&document, 2, 1), WHITE);
+  }
+
+  #[test]
+  fn change_pixels_changes_all_of_them() {
+    let mut document = new_document(4, 4);
+    document.apply(EditorCommand::ChangePixels(vec![[0, 0], [3, 3], [2, 1]], RED)).unwrap();
+    assert_eq!(color_at(&document, 0, 0), RED);
+    assert_eq!(color_at(&document, 3, 3), RED);
+    assert_eq!(color_at(&document, 2, 1), RED);
+    assert_eq!(color_at(&document, 1, 1), WHITE);
+  }
+
+  #[test]
+  fn bucket_fill_stops_at_borders() {
+    let mut document = new_document(5, 5);
+    //vertical wall at x = 2
+    document.apply(EditorCommand::DrawLine([[2, 0], [2, 4]], [0, 0, 0, 255])).unwrap();
+    document.apply(EditorCommand::FillBucket([0, 0], RED)).unwrap();
+    assert_eq!(color_at(&document, 1, 4), RED);
+    assert_eq!(color_at(&document, 2, 2), [0, 0, 0, 255]);
+    assert_eq!(color_at(&document, 3, 0), WHITE);
+  }
+
+  #[test]
+  fn average_color_of_area() {
+    let mut document = new_document(4, 4);
+    document.apply(EditorCommand::ChangePixel([1, 1], [0, 0, 0, 255])).unwrap();
+    assert_eq!(document.average_color([1, 1], 1).unwrap(), [0, 0, 0, 255]);
+    //8 white pixels and 1 black
+    assert_eq!(document.average_color([1, 1], 3).unwrap(), [227, 227, 227, 255]);
+    //corner, only 4 pixels are in the image
+    assert_eq!(document.average_color([0, 0], 3).unwrap(), [191, 191, 191, 255]);
+  }
+
+  #[test]
+  fn indexed_images_stay_indexed() {
+    let colors = [[0, 0, 0, 255], WHITE, RED];
+    let bmp = new_indexed(4, 3, 4, &colors, &[1; 12]).unwrap();
+    let mut document = Document::new(bmp, DEFAULT_HISTORY_BUDGET);
+    //closest color in the table is used
+    document.apply(EditorCommand::ChangePixel([1, 1], [250, 5, 5, 255])).unwrap();
+    document.apply(EditorCommand::DrawRect([[2, 0], [3, 2]], [0, 0, 0, 255])).unwrap();
+    assert_eq!(color_at(&document, 1, 1), RED);
+    assert_eq!(color_at(&document, 3, 2), [0, 0, 0, 255]);
+    document.apply(EditorCommand::ChangeTableColor(2, [0, 0, 255, 255])).unwrap();
+    assert_eq!(color_at(&document, 1, 1), [0, 0, 255, 255]);
+    document.apply(EditorCommand::FillBucket([0, 0], [0, 0, 250, 255])).unwrap();
+    assert_eq!(color_at(&document, 0, 2), [0, 0, 255, 255]);
+    assert!(document.apply(EditorCommand::GaussianBlur(1)).is_err());
+    assert_eq!(document.bmp().get_dib_header().unwrap().bitcount, 4);
+    assert!(document.undo());
+    assert!(document.undo());
+    assert_eq!(color_at(&document, 1, 1), RED);
+    assert_eq!(color_at(&document, 0, 0), WHITE);
+  }
+
+  #[test]
+  fn shallow_diagonal_line() {
+    let mut document = new_document(20, 10);
+    document.apply(EditorCommand::DrawLine([[0, 9], [19, 0]], RED)).unwrap();
+    assert_eq!(color_at(&document, 0, 9), RED);
+    assert_eq!(color_at(&document, 19, 0), RED);
+    assert_eq!(color_at(&document, 0, 0), WHITE);
+  }
+
+  #[test]
+  fn invert_and_greyscale() {
+    let mut document = new_document(2, 2);
+    document.apply(EditorCommand::ChangePixel([0, 0], [10, 20, 30, 255])).unwrap();
+    document.apply(EditorCommand::Invert).unwrap();
+    assert_eq!(color_at(&document, 0, 0), [245, 235, 225, 255]);
+    assert_eq!(color_at(&document, 1, 1), [0, 0, 0, 255]);
+    document.apply(EditorCommand::Greyscale).unwrap();
+    let grey = color_at(&document, 0, 0);
+    assert!(grey[0] == grey[1] && grey[1] == grey[2]);
+  }
+
+  #[test]
+  fn blur_of_solid_image_is_unchanged() {
+    let mut document = new_document(6, 6);
+    let before = document.bmp().clone();
+    document.apply(EditorCommand::GaussianBlur(2)).unwrap();
+    document.apply(EditorCommand::BoxBlur(2)).unwrap();
+    document.apply(EditorCommand::MedianFilter(1)).unwrap();
+    assert!(document.bmp() == &before);
+  }
+
+  #[test]
+  fn coordinates_outside_the_image_are_errors() {
+    let mut document = new_document(6, 3);
+    let before = document.bmp().clone();
+    for command in [
+      EditorCommand::ChangePixel([6, 0], RED),
+      EditorCommand::ChangePixels(vec![[0, 0], [100, 100]], RED),
+      EditorCommand::FillBucket([0, 3], RED),
+      EditorCommand::DrawLine([[0, 0], [100, 100]], RED),
+      EditorCommand::DrawRect([[10, 0], [1, 1]], RED),
+      EditorCommand::DrawEllipse([[2, 1], [3, 1]], RED),
+      EditorCommand::DrawEllipse([[2, 1], [1, 2]], RED),
+    ] {
+      assert!(document.apply(command).is_err());
+    }
+    assert!(document.bmp() == &before);
+    assert!(document.apply(EditorCommand::DrawEllipse([[2, 1], [2, 1]], RED)).is_ok());
+    //indexed images too
+    let mut indexed = Document::new(new_indexed(4, 3, 4, &[WHITE, RED], &[0; 12]).unwrap(), DEFAULT_HISTORY_BUDGET);
+    assert!(indexed.apply(EditorCommand::DrawLine([[0, 0], [4, 2]], RED)).is_err());
+  }
+
+  #[test]
+  fn rects_with_any_corners() {
+    let masked = export_bmp(&BMP::new(8, 8, None), ExportFormat::Rgb565, false).unwrap();
+    let indexed = new_indexed(8, 8, 8, &[WHITE, RED], &[0; 64]).unwrap();
+    for bmp in [BMP::new(8, 8, None), masked, indexed] {
+      let mut document = Document::new(bmp, DEFAULT_HISTORY_BUDGET);
+      //reversed, the same corner, and next to each other
+      document.apply(EditorCommand::DrawRect([[6, 6], [4, 5]], RED)).unwrap();
+      document.apply(EditorCommand::DrawRect([[1, 1], [1, 1]], RED)).unwrap();
+      document.apply(EditorCommand::DrawRect([[2, 3], [3, 4]], RED)).unwrap();
+      for pixel in [[4, 5], [5, 6], [6, 5], [1, 1], [2, 3], [3, 4]] {
+        assert_eq!(color_at(&document, pixel[0], pixel[1]), RED);
+      }
+      for pixel in [[7, 7], [3, 5], [0, 1], [1, 2], [4, 3]] {
+        assert_eq!(color_at(&document, pixel[0], pixel[1]), WHITE);
+      }
+    }
+  }
+
+  #[test]
+  fn script_errors_have_the_failing_line() {
+    let mut document = new_document(6, 3);
+    let errors = document.apply_script("invert\n\nline 0,0 500,500 (255,0,0,255)").unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].line, 3);
+    assert!(errors[0].message.contains("(500, 500) is outside the 6x3 image"));
+    assert_eq!(document.history_info().undo_names.len(), 0);
+    assert_eq!(document.apply_script("invert\npixel 5,2 (255,0,0,255)").unwrap().len(), 2);
+    assert_eq!(color_at(&document, 5, 2), RED);
+  }
+
+  #[test]
+  fn resolution_only_changes_the_header() {
+    let bmp = new_indexed(2, 2, 8, &[WHITE, RED], &[1; 4]).unwrap();
+    let mut document = Document::new(bmp.clone(), DEFAULT_HISTORY_BUDGET);
+    document.apply(EditorCommand::SetResolution(3780, 2835)).unwrap();
+    assert_eq!(resolution(document.bmp()), Some([3780, 2835]));
+    assert_eq!(document.bmp().contents[46..], bmp.contents[46..]);
+    assert!(document.undo());
+    assert!(document.bmp() == &bmp);
+  }
+
+  #[test]
+  fn shapes_draw_their_endpoints() {
+    let mut document = new_document(10, 10);
+    document.apply(EditorCommand::DrawLine([[0, 0], [9, 9]], RED)).unwrap();
+    assert_eq!(color_at(&document, 0, 0), RED);
+    assert_eq!(color_at(&document, 9, 9), RED);
+    document.apply(EditorCommand::DrawRect([[2, 5], [4, 7]], RED)).unwrap();
+    assert_eq!(color_at(&document, 3, 6), RED);
+    assert_eq!(color_at(&document, 5, 6), WHITE);
+    document.apply(EditorCommand::DrawEllipse([[5, 2], [1, 1]], RED)).unwrap();
+    assert_eq!(color_at(&document, 5, 2), RED);
+  }
+
+  #[test]
+  fn undo_and_redo() {
+    let mut document = new_document(3, 3);
+    let original = document.bmp().clone();
+    assert!(!document.undo());
+    document.apply(EditorCommand::ChangePixel([1, 1], RED)).unwrap();
+    let changed = document.bmp().clone();
+    assert!(document.undo());
+    assert!(document.bmp() == &original);
+    assert!(document.redo());
+    assert!(document.bmp() == &changed);
+    assert!(!document.redo());
+  }
+
+  #[test]
+  fn new_edit_clears_redo() {
+    let mut document = new_document(3, 3);
+    document.apply(EditorCommand::ChangePixel([0, 0], RED)).unwrap();
+    document.undo();
+    document.apply(EditorCommand::Invert).unwrap();
+    assert!(!document.redo());
+    assert_eq!(document.history_info().undo_names, vec!["Invert".to_string()]);
+  }
+
+  #[test]
+  fn undo_rotate_of_non_square_image() {
+    let mut document = new_document(4, 2);
+    document.apply(EditorCommand::ChangePixel([3, 0], RED)).unwrap();
+    let before = document.bmp().clone();
+    document.apply(EditorCommand::Rotate).unwrap();
+    assert!(document.undo());
+    assert!(document.bmp() == &before);
+  }
+
+  #[test]
+  fn jump_to_any_point_in_history() {
+    let mut document = new_document(3, 3);
+    let original = document.bmp().clone();
+    document.apply(EditorCommand::ChangePixel([0, 0], RED)).unwrap();
+    let first = document.bmp().clone();
+    document.apply(EditorCommand::ChangePixel([1, 1], RED)).unwrap();
+    document.apply(EditorCommand::Invert).unwrap();
+    let last = document.bmp().clone();
+    assert!(document.jump_to(0));
+    assert!(document.bmp() == &original);
+    assert!(document.jump_to(1));
+    assert!(document.bmp() == &first);
+    assert!(document.jump_to(3));
+    assert!(document.bmp() == &last);
+    assert!(!document.jump_to(3));
+  }
+
+  #[test]
+  fn history_budget_drops_oldest_entries() {
+    let mut document = new_document(8, 8);
+    document.apply(EditorCommand::ChangePixel([0, 0], RED)).unwrap();
+    document.apply(EditorCommand::ChangePixel([1, 0], RED)).unwrap();
+    //too small for two entries, but the latest is always kept
+    document.set_history_budget(1);
+    assert_eq!(document.history_info().undo_names.len(), 1);
+    assert!(document.undo());
+    assert!(!document.undo());
+  }
+
+  #[test]
+  fn apply_all_is_one_undo_step() {
+    let mut document = new_document(3, 3);
+    let original = document.bmp().clone();
+    document.apply_all("Macro".to_string(), &[EditorCommand::ChangePixel([0, 0], RED), EditorCommand::Invert]).unwrap();
+    assert_eq!(color_at(&document, 0, 0), [0, 255, 255, 255]);
+    assert_eq!(document.history_info().undo_names, vec!["Macro".to_string()]);
+    assert!(document.undo());
+    assert!(document.bmp() == &original);
+  }
+
+  #[test]
+  fn history_script_skips_undone_edits() {
+    let mut document = new_document(3, 3);
+    document.apply(EditorCommand::ChangePixel([0, 0], RED)).unwrap();
+    document.apply_all("Macro".to_string(), &[EditorCommand::Greyscale, EditorCommand::GaussianBlur(1)]).unwrap();
+    document.apply(EditorCommand::Invert).unwrap();
+    document.undo();
+    assert_eq!(document.history_script(), "pixel 0,0 (255, 0, 0, 255)\ngreyscale\ngaussian 1\n");
+  }
+
+  #[test]
+  fn command_bytes_roundtrip() {
+    let commands = vec![
+      EditorCommand::ChangePixels(vec![[1, 2], [300, 4]], RED),
+      EditorCommand::ChangePixel([5, 6], WHITE),
+      EditorCommand::FillBucket([7, 8], [1, 2, 3, 4]),
+      EditorCommand::Invert,
+      EditorCommand::Greyscale,
+      EditorCommand::Rotate,
+      EditorCommand::DrawLine([[0, 0], [10, 20]], RED),
+      EditorCommand::DrawRect([[1, 1], [2, 2]], RED),
+      EditorCommand::DrawEllipse([[5, 5], [3, 2]], RED),
+      EditorCommand::GaussianBlur(3),
+      EditorCommand::BoxBlur(2),
+      EditorCommand::MedianFilter(1),
+      EditorCommand::ChangeTableColor(3, [10, 20, 30, 255]),
+      EditorCommand::SetResolution(2835, 3780),
+    ];
+    let mut bytes: Vec<u8> = Vec::new();
+    for command in commands.iter() {
+      command.write_bytes(&mut bytes);
+    }
+    let mut reader = ByteReader::new(&bytes);
+    for command in commands.iter() {
+      assert_eq!(&EditorCommand::read_bytes(&mut reader).unwrap(), command);
+    }
+    assert!(EditorCommand::read_bytes(&mut reader).is_none());
+  }
+
+  #[test]
+  fn failed_command_leaves_image_unchanged() {
+    let mut bmp = BMP::new(2, 2, None);
+    //set the bit count to 1, pixels can only be changed in 24 or 32 bit images
+    bmp.contents[28] = 1;
+    let mut document = Document::new(bmp.clone(), DEFAULT_HISTORY_BUDGET);
+    assert!(document.apply(EditorCommand::ChangePixel([0, 0], RED)).is_err());
+    assert!(document.bmp() == &bmp);
+    assert_eq!(document.history_info().undo_names.len(), 0);
+  }
+}
@@ -0,0 +1,50 @@
+use yew::prelude::*;
+
+//shows why a file couldn't be opened or an edit (eg a macro) failed, instead of the app crashing on it
+
+#[derive(PartialEq, Properties)]
+pub struct ErrorPanelProps {
+  //none hides the panel
+  pub error: Option<String>,
+  pub close_callback: Callback<bool>,
+}
+
+pub enum ErrorPanelMessage {
+  Close,
+}
+
+pub struct ErrorPanel;
+
+impl Component for ErrorPanel {
+  type Message = ErrorPanelMessage;
+  type Properties = ErrorPanelProps;
+
+  fn create(_ctx: &Context<Self>) -> Self {
+    Self
+  }
+
+  fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+    match msg {
+      Self::Message::Close => {
+        ctx.props().close_callback.emit(true);
+        false
+      },
+    }
+  }
+
+  fn view(&self, ctx: &Context<Self>) -> Html {
+    let error = match &ctx.props().error {
+      Some(error) => error,
+      None => return html! {},
+    };
+
+    let close = ctx.link().callback(|_| Self::Message::Close);
+
+    html! {
+      <div id={"error-panel"}>
+        { error }
+        <button onclick={close}>{ "Dismiss" }</button>
+      </div>
+    }
+  }
+}
@@ -0,0 +1,290 @@
+use std::collections::HashMap;
+
+use bmp_rust::bmp::{BMP, ErrorKind};
+
+use crate::header::copy_resolution;
+use crate::indexed::{nearest_index, new_indexed};
+use crate::palette::{color_counts, median_cut};
+use crate::render::{bmp_from_rgba, image_area, rgba_area};
+
+//converting the image to another bit depth when saving. whatever the image was loaded or created as,
+//it can be written as 32 bit bgra, 24 bit bgr, 16 bit (565 or 555) or 8, 4 or 1 bit indexed
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum ExportFormat {
+  Bgra32,
+  Bgr24,
+  Rgb565,
+  Rgb555,
+  Indexed8,
+  Indexed4,
+  Indexed1,
+}
+
+impl ExportFormat {
+  pub const ALL: [ExportFormat; 7] = [ExportFormat::Bgra32, ExportFormat::Bgr24, ExportFormat::Rgb565, ExportFormat::Rgb555, ExportFormat::Indexed8, ExportFormat::Indexed4, ExportFormat::Indexed1];
+
+  pub fn name(&self) -> &'static str {
+    match self {
+      ExportFormat::Bgra32 => "32-bit BGRA",
+      ExportFormat::Bgr24 => "24-bit BGR",
+      ExportFormat::Rgb565 => "16-bit RGB565",
+      ExportFormat::Rgb555 => "16-bit RGB555",
+      ExportFormat::Indexed8 => "8-bit indexed (256 colors)",
+      ExportFormat::Indexed4 => "4-bit indexed (16 colors)",
+      ExportFormat::Indexed1 => "1-bit indexed (2 colors)",
+    }
+  }
+
+  pub fn bitcount(&self) -> u16 {
+    match self {
+      ExportFormat::Bgra32 => 32,
+      ExportFormat::Bgr24 => 24,
+      ExportFormat::Rgb565 | ExportFormat::Rgb555 => 16,
+      ExportFormat::Indexed8 => 8,
+      ExportFormat::Indexed4 => 4,
+      ExportFormat::Indexed1 => 1,
+    }
+  }
+
+  pub fn is_indexed(&self) -> bool {
+    self.bitcount() <= 8
+  }
+}
+
+//what will be lost by exporting in the format, so it can be shown before saving
+pub fn export_warnings(bmp: &BMP, format: ExportFormat) -> Result<Vec<String>, ErrorKind> {
+  let counts = color_counts(bmp)?;
+  let mut warnings: Vec<String> = Vec::new();
+  if format != ExportFormat::Bgra32 {
+    let transparent: u32 = counts.iter().filter(|(color, _)| color[3] != 255).map(|(_, count)| count).sum();
+    if transparent > 0 {
+      warnings.push(format!("{} pixel{} transparent, and will be made opaque", transparent, if transparent == 1 { " is" } else { "s are" }));
+    }
+  }
+  if format.is_indexed() {
+    //colors that only differ in alpha become the same
+    let mut rgb_colors: Vec<[u8; 3]> = counts.iter().map(|(color, _)| [color[0], color[1], color[2]]).collect();
+    rgb_colors.sort();
+    rgb_colors.dedup();
+    let max_colors = 1usize << format.bitcount();
+    if rgb_colors.len() > max_colors {
+      warnings.push(format!("The image has {} colors, which will be reduced to {}", rgb_colors.len(), max_colors));
+    }
+  } else if format.bitcount() == 16 && counts.iter().any(|(color, _)| from_16_bit(to_16_bit(*color, format), format) != [color[0], color[1], color[2], 255]) {
+    warnings.push(format!("Colors will be rounded to {} bits per channel", if format == ExportFormat::Rgb565 { "5 (6 for green)" } else { "5" }));
+  }
+  Ok(warnings)
+}
+
+//dithering (floyd-steinberg) only applies to the indexed formats
+pub fn export_bmp(bmp: &BMP, format: ExportFormat, dither: bool) -> Result<BMP, ErrorKind> {
+  let mut exported = convert(bmp, format, dither)?;
+  copy_resolution(bmp, &mut exported)?;
+  Ok(exported)
+}
+
+fn convert(bmp: &BMP, format: ExportFormat, dither: bool) -> Result<BMP, ErrorKind> {
+  let area = image_area(bmp)?.ok_or(ErrorKind::InvalidParameter("image has no pixels".to_string()))?;
+  let width = u32::from(area[1][0])+1;
+  let height = u32::from(area[1][1])+1;
+  let rgba = rgba_area(bmp, area)?;
+  let pixels: Vec<[u8; 4]> = rgba.chunks_exact(4).map(|chunk| [chunk[0], chunk[1], chunk[2], chunk[3]]).collect();
+  if format.is_indexed() {
+    let colors = quantize(bmp, 1 << format.bitcount())?;
+    let indexes = if dither {
+      dither_indexes(&pixels, width as usize, &colors)
+    } else {
+      let mut nearest: HashMap<[u8; 4], u8> = HashMap::new();
+      pixels.iter().map(|pixel| *nearest.entry(*pixel).or_insert_with(|| nearest_index(&colors, *pixel))).collect()
+    };
+    return new_indexed(width, height, format.bitcount(), &colors, &indexes);
+  }
+  if format == ExportFormat::Bgra32 {
+    return bmp_from_rgba(width, height, &rgba);
+  }
+  let bytes_per_pixel = usize::from(format.bitcount()/8);
+  let row_size = (width as usize*bytes_per_pixel).div_ceil(4)*4;
+  //bottom row first
+  let mut pixel_bytes: Vec<u8> = Vec::with_capacity(row_size*height as usize);
+  for row in pixels.chunks_exact(width as usize).rev() {
+    for pixel in row {
+      if format == ExportFormat::Bgr24 {
+        pixel_bytes.extend([pixel[2], pixel[1], pixel[0]]);
+      } else {
+        pixel_bytes.extend(to_16_bit(*pixel, format).to_le_bytes());
+      }
+    }
+    pixel_bytes.resize(pixel_bytes.len()+row_size-width as usize*bytes_per_pixel, 0);
+  }
+  let (compression, masks): (u32, Vec<u32>) = if format == ExportFormat::Rgb565 {
+    //BI_BITFIELDS
+    (3, vec![0xF800, 0x07E0, 0x001F])
+  } else {
+    //BI_RGB, 16 bit without masks is 555
+    (0, Vec::new())
+  };
+  let extra: Vec<u8> = masks.iter().flat_map(|mask| mask.to_le_bytes()).collect();
+  let mut contents = headers(width, height, format.bitcount(), compression, &extra, 0, pixel_bytes.len());
+  contents.extend(pixel_bytes);
+  let mut exported = BMP::new(1, 1, None);
+  exported.contents = contents;
+  Ok(exported)
+}
+
+//file header and a 40 byte BITMAPINFOHEADER for a bottom up image, followed by the extra bytes (masks or color table)
+pub(crate) fn headers(width: u32, height: u32, bitcount: u16, compression: u32, extra: &[u8], colors_used: u32, image_size: usize) -> Vec<u8> {
+  let pixels_offset = 14+40+extra.len();
+  let mut bytes: Vec<u8> = Vec::with_capacity(pixels_offset);
+  bytes.extend(b"BM");
+  bytes.extend(((pixels_offset+image_size) as u32).to_le_bytes());
+  bytes.extend([0; 4]);
+  bytes.extend((pixels_offset as u32).to_le_bytes());
+  //size, width, height, planes, bitcount, compression, image size, 96 dpi, colors used, colors important
+  bytes.extend(40u32.to_le_bytes());
+  bytes.extend(width.to_le_bytes());
+  bytes.extend((height as i32).to_le_bytes());
+  bytes.extend(1u16.to_le_bytes());
+  bytes.extend(bitcount.to_le_bytes());
+  bytes.extend(compression.to_le_bytes());
+  bytes.extend((image_size as u32).to_le_bytes());
+  bytes.extend(3780u32.to_le_bytes());
+  bytes.extend(3780u32.to_le_bytes());
+  bytes.extend(colors_used.to_le_bytes());
+  bytes.extend(0u32.to_le_bytes());
+  bytes.extend(extra);
+  bytes
+}
+
+//the image's colors if they fit, otherwise median cut. alpha is dropped, since color tables don't have it
+fn quantize(bmp: &BMP, max_colors: usize) -> Result<Vec<[u8; 4]>, ErrorKind> {
+  let mut opaque: HashMap<[u8; 4], u32> = HashMap::new();
+  for (color, count) in color_counts(bmp)? {
+    *opaque.entry([color[0], color[1], color[2], 255]).or_insert(0) += count;
+  }
+  let counts: Vec<([u8; 4], u32)> = opaque.into_iter().collect();
+  Ok(median_cut(&counts, max_colors).into_iter().map(|(color, _)| [color[0], color[1], color[2], 255]).collect())
+}
+
+//floyd-steinberg: the difference between a pixel and its table color is spread to the pixels right and below it
+fn dither_indexes(pixels: &[[u8; 4]], width: usize, colors: &[[u8; 4]]) -> Vec<u8> {
+  let mut values: Vec<[f64; 3]> = pixels.iter().map(|pixel| [f64::from(pixel[0]), f64::from(pixel[1]), f64::from(pixel[2])]).collect();
+  let mut indexes: Vec<u8> = Vec::with_capacity(pixels.len());
+  for i in 0..values.len() {
+    let value = values[i].map(|channel| channel.round().clamp(0.0, 255.0));
+    let index = nearest_index(colors, [value[0] as u8, value[1] as u8, value[2] as u8, 255]);
+    indexes.push(index);
+    let chosen = colors[usize::from(index)];
+    let error: [f64; 3] = [0, 1, 2].map(|c| values[i][c]-f64::from(chosen[c]));
+    let x = i % width;
+    let mut spread = |target: usize, amount: f64| {
+      if target < values.len() {
+        for c in 0..3 {
+          values[target][c] += error[c]*amount;
+        }
+      }
+    };
+    if x+1 < width {
+      spread(i+1, 7.0/16.0);
+      spread(i+width+1, 1.0/16.0);
+    }
+    if x > 0 {
+      spread(i+width-1, 3.0/16.0);
+    }
+    spread(i+width, 5.0/16.0);
+  }
+  indexes
+}
+
+fn to_16_bit(color: [u8; 4], format: ExportFormat) -> u16 {
+  let [r, g, b] = [color[0], color[1], color[2]].map(u16::from);
+  if format == ExportFormat::Rgb565 {
+    ((r >> 3) << 11) | ((g >> 2) << 5) | (b >> 3)
+  } else {
+    ((r >> 3) << 10) | ((g >> 3) << 5) | (b >> 3)
+  }
+}
+
+//scaled back up so 31 (or 63) is 255
+fn from_16_bit(value: u16, format: ExportFormat) -> [u8; 4] {
+  let scale = |channel: u16, max: u16| (f64::from(channel)*255.0/f64::from(max)).round() as u8;
+  if format == ExportFormat::Rgb565 {
+    [scale(value >> 11, 31), scale((value >> 5) & 63, 63), scale(value & 31, 31), 255]
+  } else {
+    [scale((value >> 10) & 31, 31), scale((value >> 5) & 31, 31), scale(value & 31, 31), 255]
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::header::{resolution, set_resolution};
+  use crate::indexed::IndexedLayout;
+
+  fn test_image() -> BMP {
+    let mut bmp = BMP::new(2, 3, Some([255, 255, 255, 255]));
+    bmp.change_color_of_pixel(0, 0, [255, 0, 0, 255]).unwrap();
+    bmp.change_color_of_pixel(2, 1, [0, 0, 255, 128]).unwrap();
+    bmp
+  }
+
+  #[test]
+  fn direct_formats() {
+    let bmp = test_image();
+    let exported = export_bmp(&bmp, ExportFormat::Bgr24, false).unwrap();
+    let dib_header = exported.get_dib_header().unwrap();
+    assert_eq!((dib_header.bitcount, dib_header.width, dib_header.height), (24, 3, 2));
+    //bottom row first, 9 bytes padded to 12
+    assert_eq!(exported.contents[54..], [
+      255, 255, 255, 255, 255, 255, 255, 0, 0, 0, 0, 0,
+      0, 0, 255, 255, 255, 255, 255, 255, 255, 0, 0, 0,
+    ]);
+    let exported = export_bmp(&bmp, ExportFormat::Bgra32, false).unwrap();
+    assert_eq!(exported.get_color_of_pixel(2, 1).unwrap(), [0, 0, 255, 128]);
+    let exported = export_bmp(&bmp, ExportFormat::Rgb565, false).unwrap();
+    //after the 3 masks, bottom row first: white, white, blue
+    assert_eq!(exported.contents[66..72], [0xFF, 0xFF, 0xFF, 0xFF, 0x1F, 0x00]);
+    assert_eq!(from_16_bit(to_16_bit([255, 128, 0, 255], ExportFormat::Rgb555), ExportFormat::Rgb555), [255, 132, 0, 255]);
+  }
+
+  #[test]
+  fn indexed_formats() {
+    let bmp = test_image();
+    let exported = export_bmp(&bmp, ExportFormat::Indexed4, false).unwrap();
+    let layout = IndexedLayout::of(&exported).unwrap().unwrap();
+    assert_eq!(layout.bitcount, 4);
+    assert_eq!(layout.table_len, 3);
+    let colors = layout.colors(&exported);
+    assert_eq!(colors[usize::from(layout.index_at(&exported, 0, 0).unwrap())], [255, 0, 0, 255]);
+    assert_eq!(colors[usize::from(layout.index_at(&exported, 2, 1).unwrap())], [0, 0, 255, 255]);
+    let exported = export_bmp(&bmp, ExportFormat::Indexed1, true).unwrap();
+    assert_eq!(IndexedLayout::of(&exported).unwrap().unwrap().table_len, 2);
+  }
+
+  #[test]
+  fn keeps_resolution() {
+    let mut bmp = test_image();
+    set_resolution(&mut bmp, [3780, 2835]).unwrap();
+    for format in [ExportFormat::Bgra32, ExportFormat::Bgr24, ExportFormat::Indexed8] {
+      assert_eq!(resolution(&export_bmp(&bmp, format, false).unwrap()), Some([3780, 2835]));
+    }
+  }
+
+  #[test]
+  fn dithering_mixes_colors() {
+    //grey between black and white comes out as about half of each
+    let pixels = vec![[128, 128, 128, 255]; 16];
+    let indexes = dither_indexes(&pixels, 4, &[[0, 0, 0, 255], [255, 255, 255, 255]]);
+    assert_eq!(indexes.iter().filter(|index| **index == 1).count(), 8);
+  }
+
+  #[test]
+  fn warnings() {
+    let bmp = test_image();
+    assert_eq!(export_warnings(&bmp, ExportFormat::Bgra32).unwrap(), Vec::<String>::new());
+    assert_eq!(export_warnings(&bmp, ExportFormat::Bgr24).unwrap(), vec!["1 pixel is transparent, and will be made opaque".to_string()]);
+    let warnings = export_warnings(&bmp, ExportFormat::Indexed1).unwrap();
+    assert_eq!(warnings[1], "The image has 3 colors, which will be reduced to 2");
+    assert_eq!(export_warnings(&BMP::new(1, 1, Some([255, 0, 0, 255])), ExportFormat::Rgb565).unwrap().len(), 0);
+  }
+}
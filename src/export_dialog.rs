@@ -0,0 +1,198 @@
+use yew::prelude::*;
+use web_sys::HtmlSelectElement;
+use bmp_rust::bmp::BMP;
+
+use bmp_editor::export::{ExportFormat, export_bmp, export_warnings};
+use bmp_editor::indexed::IndexedLayout;
+use bmp_editor::rle::encode_rle;
+
+use crate::download::prompt_download;
+
+//choose the bit depth to download the image as, with warnings about what the conversion loses
+
+#[derive(PartialEq, Properties)]
+pub struct ExportDialogProps {
+  pub show: bool,
+  pub current_bmp: Option<BMP>,
+  pub close_callback: Callback<bool>,
+}
+
+pub enum ExportDialogMessage {
+  Show,
+  Hide,
+  //none keeps the image as it is
+  SetFormat(Option<ExportFormat>),
+  SetDither(bool),
+  SetRle(bool),
+  Download,
+  Cancel,
+}
+
+pub struct ExportDialog {
+  display: String,
+  format: Option<ExportFormat>,
+  dither: bool,
+  rle: bool,
+  warnings: Vec<String>,
+}
+
+impl ExportDialog {
+  //only 8 and 4 bit indexed images can be run length encoded
+  fn can_rle(&self, ctx: &Context<Self>) -> bool {
+    match self.format {
+      Some(format) => format == ExportFormat::Indexed8 || format == ExportFormat::Indexed4,
+      None => ctx.props().current_bmp.as_ref().and_then(|bmp| IndexedLayout::of(bmp).ok().flatten()).map(|layout| layout.bitcount == 8 || layout.bitcount == 4).unwrap_or(false),
+    }
+  }
+
+  fn update_warnings(&mut self, ctx: &Context<Self>) {
+    self.warnings = match (ctx.props().current_bmp.as_ref(), self.format) {
+      (Some(bmp), Some(format)) => export_warnings(bmp, format).unwrap_or_else(|e| vec![e.to_string()]),
+      _ => Vec::new(),
+    };
+  }
+}
+
+impl Component for ExportDialog {
+  type Message = ExportDialogMessage;
+  type Properties = ExportDialogProps;
+
+  fn create(_ctx: &Context<Self>) -> Self {
+    Self { display: "none".to_string(), format: None, dither: false, rle: false, warnings: Vec::new() }
+  }
+
+  fn changed(&mut self, ctx: &Context<Self>, old_props: &Self::Properties) -> bool {
+    //the image was edited while the dialog is open
+    if ctx.props().show && ctx.props().current_bmp != old_props.current_bmp {
+      self.update_warnings(ctx);
+    }
+    true
+  }
+
+  fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+    match msg {
+      Self::Message::Show => {
+        self.display = "block".to_string();
+        self.update_warnings(ctx);
+        true
+      },
+      Self::Message::Hide => {
+        self.display = "none".to_string();
+        true
+      },
+      Self::Message::SetFormat(format) => {
+        self.format = format;
+        self.update_warnings(ctx);
+        true
+      },
+      Self::Message::SetDither(dither) => {
+        self.dither = dither;
+        true
+      },
+      Self::Message::SetRle(rle) => {
+        self.rle = rle;
+        true
+      },
+      Self::Message::Download => {
+        let bmp = match ctx.props().current_bmp.as_ref() {
+          Some(bmp) => bmp,
+          None => return false,
+        };
+        let exported = match self.format {
+          Some(format) => export_bmp(bmp, format, self.dither),
+          None => Ok(bmp.clone()),
+        };
+        let exported = if self.rle && self.can_rle(ctx) {
+          exported.and_then(|exported| encode_rle(&exported))
+        } else {
+          exported
+        };
+        let contents = match exported {
+          Ok(exported) => exported.contents,
+          Err(e) => {
+            self.warnings = vec![format!("Could not convert the image: {}", e)];
+            return true;
+          },
+        };
+        prompt_download(&contents, "edited.bmp");
+        ctx.props().close_callback.emit(true);
+        false
+      },
+      Self::Message::Cancel => {
+        ctx.props().close_callback.emit(true);
+        false
+      },
+    }
+  }
+
+  fn view(&self, ctx: &Context<Self>) -> Html {
+    let link = ctx.link().clone();
+
+    if self.display == "none".to_string() && ctx.props().show {
+      link.send_message(Self::Message::Show);
+    } else if self.display == "block".to_string() && !ctx.props().show {
+      link.send_message(Self::Message::Hide);
+    }
+
+    let current_bitcount = ctx.props().current_bmp.as_ref().and_then(|bmp| bmp.get_dib_header().ok()).map(|dib_header| dib_header.bitcount).unwrap_or(0);
+
+    let format_options = ExportFormat::ALL.iter().enumerate().map(|(index, format)| {
+      html! {
+        <option value={index.to_string()} selected={self.format == Some(*format)}>{ format.name() }</option>
+      }
+    }).collect::<Html>();
+
+    let select_format = ctx.link().callback(|e: Event| {
+      let select: HtmlSelectElement = e.target_unchecked_into();
+      Self::Message::SetFormat(select.value().parse::<usize>().ok().and_then(|index| ExportFormat::ALL.get(index).copied()))
+    });
+
+    let toggle_dither = ctx.link().callback(|e: Event| {
+      let checkbox: web_sys::HtmlInputElement = e.target_unchecked_into();
+      Self::Message::SetDither(checkbox.checked())
+    });
+
+    let toggle_rle = ctx.link().callback(|e: Event| {
+      let checkbox: web_sys::HtmlInputElement = e.target_unchecked_into();
+      Self::Message::SetRle(checkbox.checked())
+    });
+
+    let download = ctx.link().callback(|_| Self::Message::Download);
+    let cancel = ctx.link().callback(|_| Self::Message::Cancel);
+
+    let warnings = self.warnings.iter().map(|warning| {
+      html! {
+        <li>{ warning }</li>
+      }
+    }).collect::<Html>();
+
+    html! {
+      <div id={"export-dialog"} style={"display: ".to_string()+&self.display}>
+        <label for="export-format">{ "Save as: " }</label>
+        <select id="export-format" onchange={select_format}>
+          <option value={"keep"} selected={self.format.is_none()}>{ format!("Keep as is ({}-bit)", current_bitcount) }</option>
+          { format_options }
+        </select>
+        if self.format.map(|format| format.is_indexed()).unwrap_or(false) {
+          <label>
+            <input type="checkbox" checked={self.dither} onchange={toggle_dither}/>
+            { "Dither" }
+          </label>
+        }
+        if self.can_rle(ctx) {
+          <label>
+            <input type="checkbox" checked={self.rle} onchange={toggle_rle}/>
+            { "RLE compress" }
+          </label>
+        }
+        if self.warnings.len() > 0 {
+          <ul class="export-warnings">
+            { warnings }
+          </ul>
+        }
+        <button onclick={download}>{ "Download" }</button>
+        <button onclick={cancel}>{ "Cancel" }</button>
+      </div>
+    }
+  }
+}
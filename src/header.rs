@@ -0,0 +1,140 @@
+use bmp_rust::bmp::{BMP, ErrorKind};
+
+use crate::bitfields::MaskedLayout;
+
+//the fields of the file header and dib header, for showing what is in the file,
+//and changing the ones that don't affect the pixels (the resolution)
+
+//where the resolution is in the info header and the ones after it (the core header doesn't have one)
+const RESOLUTION_OFFSET: usize = 38;
+const METERS_PER_INCH: f64 = 0.0254;
+
+fn header_name(dib_size: u32) -> &'static str {
+  match dib_size {
+    12 => "BITMAPCOREHEADER",
+    40 => "BITMAPINFOHEADER",
+    108 => "BITMAPV4HEADER",
+    124 => "BITMAPV5HEADER",
+    _ => "unknown",
+  }
+}
+
+//name and value of every field, in the order they are in the file
+pub fn header_fields(bmp: &BMP) -> Result<Vec<(&'static str, String)>, ErrorKind> {
+  let header = bmp.get_header();
+  let dib_header = bmp.get_dib_header()?;
+  let mut fields: Vec<(&'static str, String)> = vec![
+    ("Signature", header.bfType),
+    ("File size", if header.bfSize as usize == bmp.contents.len() {
+      format!("{} bytes", header.bfSize)
+    } else {
+      format!("{} bytes (the file is actually {})", header.bfSize, bmp.contents.len())
+    }),
+    ("Pixel data offset", header.bfOffBits.to_string()),
+    ("DIB header", format!("{} bytes ({})", dib_header.size, header_name(dib_header.size))),
+    ("Width", dib_header.width.to_string()),
+    //negative heights are stored from the top row down
+    ("Height", format!("{} ({})", dib_header.height, if dib_header.height < 0 { "top-down" } else { "bottom-up" })),
+    ("Planes", dib_header.planes.to_string()),
+    ("Bits per pixel", dib_header.bitcount.to_string()),
+  ];
+  if dib_header.size == 12 {
+    return Ok(fields);
+  }
+  let compression = dib_header.compression.unwrap_or_default();
+  let has_masks = compression == "BI_BITFIELDS" || compression == "BI_ALPHABITFIELDS";
+  fields.push(("Compression", compression));
+  fields.push(("Image size", format!("{} bytes", dib_header.sizeimage.unwrap_or(0))));
+  if let Some([x, y]) = resolution(bmp) {
+    fields.push(("Horizontal resolution", resolution_text(x)));
+    fields.push(("Vertical resolution", resolution_text(y)));
+  }
+  //0 means the whole table for indexed images
+  let colors_used = dib_header.ClrUsed.unwrap_or(0);
+  fields.push(("Colors used", if colors_used == 0 && dib_header.bitcount <= 8 {
+    format!("0 (all {})", 1u32 << dib_header.bitcount)
+  } else {
+    colors_used.to_string()
+  }));
+  let important_colors = dib_header.ClrImportant.unwrap_or(0);
+  fields.push(("Important colors", if important_colors == 0 { "0 (all)".to_string() } else { important_colors.to_string() }));
+  if let Some(layout) = MaskedLayout::of(bmp)?.filter(|_| has_masks) {
+    let masks = layout.masks;
+    fields.push(("Channel masks", format!("red {:08X}, green {:08X}, blue {:08X}, alpha {:08X}", masks.red, masks.green, masks.blue, masks.alpha)));
+  }
+  if dib_header.size >= 108 {
+    fields.push(("Color space", dib_header.CSType.unwrap_or_default()));
+  }
+  if dib_header.size == 124 {
+    fields.push(("Rendering intent", dib_header.Intent.unwrap_or_default()));
+  }
+  Ok(fields)
+}
+
+fn resolution_text(pixels_per_meter: u32) -> String {
+  format!("{} pixels/meter ({} DPI)", pixels_per_meter, ppm_to_dpi(pixels_per_meter).round())
+}
+
+//horizontal and vertical pixels per meter, none for core headers
+pub fn resolution(bmp: &BMP) -> Option<[u32; 2]> {
+  let dib_size = u32::from_le_bytes(bmp.contents.get(14..18)?.try_into().unwrap());
+  if dib_size < 40 {
+    return None;
+  }
+  let bytes = bmp.contents.get(RESOLUTION_OFFSET..RESOLUTION_OFFSET+8)?;
+  Some([u32::from_le_bytes(bytes[..4].try_into().unwrap()), u32::from_le_bytes(bytes[4..].try_into().unwrap())])
+}
+
+pub fn set_resolution(bmp: &mut BMP, pixels_per_meter: [u32; 2]) -> Result<(), ErrorKind> {
+  if resolution(bmp).is_none() {
+    return Err(ErrorKind::InvalidParameter("core (12 byte) headers don't have a resolution".to_string()));
+  }
+  bmp.contents[RESOLUTION_OFFSET..RESOLUTION_OFFSET+4].copy_from_slice(&pixels_per_meter[0].to_le_bytes());
+  bmp.contents[RESOLUTION_OFFSET+4..RESOLUTION_OFFSET+8].copy_from_slice(&pixels_per_meter[1].to_le_bytes());
+  Ok(())
+}
+
+//images made from another one (when exporting, or decoding rle) get its resolution
+pub fn copy_resolution(from: &BMP, to: &mut BMP) -> Result<(), ErrorKind> {
+  match resolution(from) {
+    Some(pixels_per_meter) => set_resolution(to, pixels_per_meter),
+    None => Ok(()),
+  }
+}
+
+pub fn ppm_to_dpi(pixels_per_meter: u32) -> f64 {
+  f64::from(pixels_per_meter)*METERS_PER_INCH
+}
+
+pub fn dpi_to_ppm(dpi: f64) -> u32 {
+  (dpi/METERS_PER_INCH).round() as u32
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::indexed::new_indexed;
+
+  #[test]
+  fn lists_fields() {
+    let mut bmp = BMP::new(3, 5, None);
+    let fields = header_fields(&bmp).unwrap();
+    assert_eq!(fields[0], ("Signature", "BM".to_string()));
+    assert!(fields.contains(&("Width", "5".to_string())));
+    assert!(fields.contains(&("Height", "3 (bottom-up)".to_string())));
+    assert!(fields.contains(&("DIB header", "124 bytes (BITMAPV5HEADER)".to_string())));
+    bmp.contents[22..26].copy_from_slice(&(-3i32).to_le_bytes());
+    assert!(header_fields(&bmp).unwrap().contains(&("Height", "-3 (top-down)".to_string())));
+    let indexed = new_indexed(2, 2, 4, &[[0, 0, 0, 255]], &[0; 4]).unwrap();
+    assert!(header_fields(&indexed).unwrap().contains(&("Bits per pixel", "4".to_string())));
+  }
+
+  #[test]
+  fn changes_resolution() {
+    let mut bmp = BMP::new(2, 2, None);
+    set_resolution(&mut bmp, [dpi_to_ppm(300.0), 2835]).unwrap();
+    assert_eq!(resolution(&bmp), Some([11811, 2835]));
+    assert_eq!(bmp.get_dib_header().unwrap().YPelsPerMeter, Some(2835));
+    assert_eq!(ppm_to_dpi(2835).round(), 72.0);
+  }
+}
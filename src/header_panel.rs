@@ -0,0 +1,113 @@
+use yew::prelude::*;
+use web_sys::{HtmlInputElement, HtmlSelectElement};
+
+use bmp_editor::header::{dpi_to_ppm, ppm_to_dpi};
+use bmp_editor::parse::input_to_u32;
+
+//every field of the file and dib headers, and inputs to change the resolution,
+//which is kept in the file when it is downloaded
+
+#[derive(PartialEq, Properties)]
+pub struct HeaderPanelProps {
+  //none hides the panel
+  pub header_fields: Option<Vec<(&'static str, String)>>,
+  //pixels per meter, none if the header doesn't have a resolution
+  pub resolution: Option<[u32; 2]>,
+  pub resolution_callback: Callback<[u32; 2]>,
+}
+
+pub enum HeaderPanelMessage {
+  SetResolution,
+}
+
+pub struct HeaderPanel {
+  error: Option<String>,
+  horizontal_ref: NodeRef,
+  vertical_ref: NodeRef,
+  unit_ref: NodeRef,
+}
+
+impl Component for HeaderPanel {
+  type Message = HeaderPanelMessage;
+  type Properties = HeaderPanelProps;
+
+  fn create(_ctx: &Context<Self>) -> Self {
+    Self { error: None, horizontal_ref: NodeRef::default(), vertical_ref: NodeRef::default(), unit_ref: NodeRef::default() }
+  }
+
+  fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+    match msg {
+      Self::Message::SetResolution => {
+        let horizontal: HtmlInputElement = self.horizontal_ref.cast().unwrap();
+        let vertical: HtmlInputElement = self.vertical_ref.cast().unwrap();
+        let unit: HtmlSelectElement = self.unit_ref.cast().unwrap();
+        let in_dpi = unit.value() == "dpi";
+        let to_ppm = |input: &HtmlInputElement| -> Result<u32, String> {
+          if in_dpi {
+            match input.value().trim().parse::<f64>() {
+              Ok(dpi) if dpi >= 0.0 && dpi_to_ppm(dpi) < u32::MAX => Ok(dpi_to_ppm(dpi)),
+              _ => Err(format!("\"{}\" is not a valid DPI", input.value().trim())),
+            }
+          } else {
+            input_to_u32(&input.value())
+          }
+        };
+        match (to_ppm(&horizontal), to_ppm(&vertical)) {
+          (Ok(x), Ok(y)) => {
+            self.error = None;
+            ctx.props().resolution_callback.emit([x, y]);
+          },
+          (Err(e), _) | (_, Err(e)) => {
+            self.error = Some(e);
+          },
+        }
+        true
+      },
+    }
+  }
+
+  fn view(&self, ctx: &Context<Self>) -> Html {
+    let header_fields = match &ctx.props().header_fields {
+      Some(header_fields) => header_fields,
+      None => return html! {},
+    };
+
+    let rows = header_fields.iter().map(|(name, value)| {
+      html! {
+        <tr>
+          <td>{ name }</td>
+          <td>{ value }</td>
+        </tr>
+      }
+    }).collect::<Html>();
+
+    let set_resolution = ctx.link().callback(|_| Self::Message::SetResolution);
+
+    html! {
+      <div id={"header-panel"}>
+        <h3>{ "Header" }</h3>
+        <table>
+          { rows }
+        </table>
+        if let Some(resolution) = ctx.props().resolution {
+          <h4>{ "Resolution" }</h4>
+          <label for="horizontal-resolution">{ "Horizontal: " }</label>
+          <input id="horizontal-resolution" ref={&self.horizontal_ref} type="number" min="0" value={resolution[0].to_string()}/>
+          <br/>
+          <label for="vertical-resolution">{ "Vertical: " }</label>
+          <input id="vertical-resolution" ref={&self.vertical_ref} type="number" min="0" value={resolution[1].to_string()}/>
+          <br/>
+          <select ref={&self.unit_ref}>
+            <option value="ppm">{ "Pixels/meter" }</option>
+            <option value="dpi">{ "DPI" }</option>
+          </select>
+          <button onclick={set_resolution}>{ "Set Resolution" }</button>
+          <p class="palette-tip">{ format!("Currently {} x {} DPI.", ppm_to_dpi(resolution[0]).round(), ppm_to_dpi(resolution[1]).round()) }</p>
+        }
+        if let Some(error) = &self.error {
+          <p class="palette-error">{ error }</p>
+        }
+      </div>
+    }
+  }
+}
@@ -0,0 +1,370 @@
+use bmp_rust::bmp::BMP;
+use std::mem::size_of;
+
+use crate::bytes::ByteReader;
+use crate::editor::EditorCommand;
+
+//undo/redo history entries
+//most operations only change pixel colors, so only the changed bytes need to be stored (whatever the pixel format is),
+//but if the file size changes (resize, crop, etc), a byte diff can't restore the image, so the whole previous image is kept instead
+
+//differing bytes in a row starting at `offset`, as they are in the image the entry restores and in the other one
+struct ByteRun {
+  offset: u32,
+  restored: Vec<u8>,
+  replaced: Vec<u8>,
+}
+
+//unchanged bytes between two changed ones are kept in the same run if the gap is this small,
+//since every run costs more than a few bytes
+const MAX_RUN_GAP: usize = 16;
+
+enum HistoryChange {
+  Diff(Vec<ByteRun>),
+  Snapshot(BMP),
+}
+
+//the name is the operation that made the change, eg "Gaussian blur r=3", and is shown in the history panel
+//the commands are what made the change, so the history can be exported as an edit script
+pub struct HistoryEntry {
+  pub name: String,
+  pub commands: Vec<EditorCommand>,
+  change: HistoryChange,
+}
+
+impl HistoryEntry {
+  //create the entry that turns new_bmp back into old_bmp
+  pub fn new(name: String, commands: Vec<EditorCommand>, old_bmp: &BMP, new_bmp: &BMP) -> Self {
+    let change = if old_bmp.contents.len() == new_bmp.contents.len() {
+      HistoryChange::Diff(byte_diff(&old_bmp.contents, &new_bmp.contents))
+    } else {
+      HistoryChange::Snapshot(old_bmp.clone())
+    };
+    Self { name, commands, change }
+  }
+
+  //apply the entry to the bmp, returns the changed bmp and the entry that reverses the change (for redo, or undoing the redo)
+  pub fn apply(self, bmp: &BMP) -> (BMP, HistoryEntry) {
+    match self.change {
+      HistoryChange::Diff(runs) => {
+        let mut new_bmp = bmp.clone();
+        for run in runs.iter() {
+          let start = run.offset as usize;
+          new_bmp.contents[start..start+run.restored.len()].copy_from_slice(&run.restored);
+        }
+        //the reverse restores the bytes that were just replaced
+        let reversed = runs.into_iter().map(|run| ByteRun { offset: run.offset, restored: run.replaced, replaced: run.restored }).collect();
+        (new_bmp, HistoryEntry { name: self.name, commands: self.commands, change: HistoryChange::Diff(reversed) })
+      },
+      HistoryChange::Snapshot(old_bmp) => {
+        (old_bmp, HistoryEntry { name: self.name, commands: self.commands, change: HistoryChange::Snapshot(bmp.clone()) })
+      },
+    }
+  }
+
+  //rough amount of memory the entry takes up
+  pub fn size_bytes(&self) -> usize {
+    let change_size = match &self.change {
+      HistoryChange::Diff(runs) => runs.iter().map(|run| size_of::<ByteRun>()+run.restored.len()*2).sum(),
+      HistoryChange::Snapshot(bmp) => size_of::<BMP>()+bmp.contents.len(),
+    };
+    let commands_size: usize = self.commands.iter().map(|command| {
+      size_of::<EditorCommand>()+match command {
+        EditorCommand::ChangePixels(pixels, _) => pixels.len()*size_of::<[u16; 2]>(),
+        _ => 0,
+      }
+    }).sum();
+    size_of::<HistoryEntry>()+self.name.len()+commands_size+change_size
+  }
+
+  //name length, name, number of commands, commands, then 0 and the pixel diffs for a diff, or 1 and the bmp contents for a snapshot
+  fn write_bytes(&self, bytes: &mut Vec<u8>) {
+    bytes.extend((self.name.len() as u32).to_le_bytes());
+    bytes.extend(self.name.as_bytes());
+    bytes.extend((self.commands.len() as u32).to_le_bytes());
+    for command in self.commands.iter() {
+      command.write_bytes(bytes);
+    }
+    match &self.change {
+      HistoryChange::Diff(runs) => {
+        bytes.push(2);
+        bytes.extend((runs.len() as u32).to_le_bytes());
+        for run in runs.iter() {
+          bytes.extend(run.offset.to_le_bytes());
+          bytes.extend((run.restored.len() as u32).to_le_bytes());
+          bytes.extend(&run.restored);
+          bytes.extend(&run.replaced);
+        }
+      },
+      HistoryChange::Snapshot(bmp) => {
+        bytes.push(1);
+        bytes.extend((bmp.contents.len() as u32).to_le_bytes());
+        bytes.extend(&bmp.contents);
+      },
+    }
+  }
+
+  fn read_bytes(reader: &mut ByteReader) -> Option<Self> {
+    let name_len = reader.take_u32()? as usize;
+    let name = String::from_utf8(reader.take(name_len)?.to_vec()).ok()?;
+    let mut commands: Vec<EditorCommand> = Vec::new();
+    for _ in 0..reader.take_u32()? {
+      commands.push(EditorCommand::read_bytes(reader)?);
+    }
+    let change = match reader.take(1)?[0] {
+      1 => {
+        let contents_len = reader.take_u32()? as usize;
+        let mut bmp = BMP::new(1, 1, None);
+        bmp.contents = reader.take(contents_len)?.to_vec();
+        HistoryChange::Snapshot(bmp)
+      },
+      //0 was the pixel diffs of older versions, those histories are dropped
+      2 => {
+        let mut runs: Vec<ByteRun> = Vec::new();
+        for _ in 0..reader.take_u32()? {
+          let offset = reader.take_u32()?;
+          let len = reader.take_u32()? as usize;
+          let restored = reader.take(len)?.to_vec();
+          let replaced = reader.take(len)?.to_vec();
+          runs.push(ByteRun { offset, restored, replaced });
+        }
+        HistoryChange::Diff(runs)
+      },
+      _ => return None,
+    };
+    Some(Self { name, commands, change })
+  }
+}
+
+//default of 32 megabytes
+pub const DEFAULT_HISTORY_BUDGET: usize = 32*1024*1024;
+
+#[derive(PartialEq, Clone)]
+pub struct HistoryInfo {
+  //oldest first
+  pub undo_names: Vec<String>,
+  //in the order they would be redone
+  pub redo_names: Vec<String>,
+  pub used_bytes: usize,
+  pub budget: usize,
+}
+
+//undo and redo stacks, limited by how much memory the entries take up instead of how many entries there are,
+//so lots of small edits can be undone, but a few full image filters won't use up all the memory
+pub struct History {
+  undo_entries: Vec<HistoryEntry>,
+  redo_entries: Vec<HistoryEntry>,
+  budget: usize,
+}
+
+impl History {
+  pub fn new(budget: usize) -> Self {
+    Self { undo_entries: Vec::new(), redo_entries: Vec::new(), budget }
+  }
+
+  //any new edit makes the redo history invalid
+  pub fn push(&mut self, entry: HistoryEntry) {
+    self.undo_entries.push(entry);
+    self.redo_entries.clear();
+    self.trim();
+  }
+
+  //returns the bmp with the last edit undone, or none if there is nothing to undo
+  pub fn undo(&mut self, bmp: &BMP) -> Option<BMP> {
+    let new_bmp = self.undo_step(bmp);
+    self.trim();
+    new_bmp
+  }
+
+  pub fn redo(&mut self, bmp: &BMP) -> Option<BMP> {
+    let new_bmp = self.redo_step(bmp);
+    self.trim();
+    new_bmp
+  }
+
+  //undo or redo until there are `undo_steps` entries left to undo. 0 is the oldest state still in the history
+  pub fn jump_to(&mut self, undo_steps: usize, bmp: &BMP) -> Option<BMP> {
+    if undo_steps == self.undo_entries.len() || undo_steps > self.undo_entries.len()+self.redo_entries.len() {
+      return None;
+    }
+    let mut new_bmp = bmp.clone();
+    //don't trim in between steps, or the entries would shift while jumping
+    while self.undo_entries.len() > undo_steps {
+      new_bmp = self.undo_step(&new_bmp).unwrap();
+    }
+    while self.undo_entries.len() < undo_steps {
+      new_bmp = self.redo_step(&new_bmp).unwrap();
+    }
+    self.trim();
+    Some(new_bmp)
+  }
+
+  fn undo_step(&mut self, bmp: &BMP) -> Option<BMP> {
+    let entry = self.undo_entries.pop()?;
+    let (new_bmp, redo_entry) = entry.apply(bmp);
+    //keep the reverse of the entry so the undo can be redone
+    self.redo_entries.push(redo_entry);
+    Some(new_bmp)
+  }
+
+  fn redo_step(&mut self, bmp: &BMP) -> Option<BMP> {
+    let entry = self.redo_entries.pop()?;
+    let (new_bmp, undo_entry) = entry.apply(bmp);
+    self.undo_entries.push(undo_entry);
+    Some(new_bmp)
+  }
+
+  pub fn clear(&mut self) {
+    self.undo_entries.clear();
+    self.redo_entries.clear();
+  }
+
+  pub fn set_budget(&mut self, budget: usize) {
+    self.budget = budget;
+    self.trim();
+  }
+
+  //the commands that got from the oldest state still in the history to the current state
+  pub fn commands(&self) -> Vec<EditorCommand> {
+    self.undo_entries.iter().flat_map(|entry| entry.commands.iter().cloned()).collect()
+  }
+
+  pub fn used_bytes(&self) -> usize {
+    self.undo_entries.iter().chain(self.redo_entries.iter()).map(|entry| entry.size_bytes()).sum()
+  }
+
+  pub fn info(&self) -> HistoryInfo {
+    HistoryInfo {
+      undo_names: self.undo_entries.iter().map(|entry| entry.name.clone()).collect(),
+      redo_names: self.redo_entries.iter().rev().map(|entry| entry.name.clone()).collect(),
+      used_bytes: self.used_bytes(),
+      budget: self.budget,
+    }
+  }
+
+  //serialize the whole history, so it can be saved (eg to indexeddb) and restored later
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut bytes: Vec<u8> = Vec::new();
+    bytes.extend((self.budget as u64).to_le_bytes());
+    for entries in [&self.undo_entries, &self.redo_entries] {
+      bytes.extend((entries.len() as u32).to_le_bytes());
+      for entry in entries.iter() {
+        entry.write_bytes(&mut bytes);
+      }
+    }
+    bytes
+  }
+
+  //returns none if the bytes are not a valid serialized history
+  pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+    let mut reader = ByteReader::new(bytes);
+    let budget = u64::from_le_bytes(reader.take_array()?) as usize;
+    let mut undo_entries: Vec<HistoryEntry> = Vec::new();
+    for _ in 0..reader.take_u32()? {
+      undo_entries.push(HistoryEntry::read_bytes(&mut reader)?);
+    }
+    let mut redo_entries: Vec<HistoryEntry> = Vec::new();
+    for _ in 0..reader.take_u32()? {
+      redo_entries.push(HistoryEntry::read_bytes(&mut reader)?);
+    }
+    Some(Self { undo_entries, redo_entries, budget })
+  }
+
+  //drop the oldest undo entries until under budget, then the furthest redo entries
+  //at least one entry is always kept, even if it is over budget by itself, so the last action can always be undone
+  fn trim(&mut self) {
+    let mut used_bytes = self.used_bytes();
+    while used_bytes > self.budget && self.undo_entries.len()+self.redo_entries.len() > 1 {
+      let removed = if self.undo_entries.len() > 1 {
+        self.undo_entries.remove(0)
+      } else {
+        self.redo_entries.remove(0)
+      };
+      used_bytes -= removed.size_bytes();
+    }
+  }
+}
+
+//runs of the bytes that differ between the two (same length) files
+fn byte_diff(restored: &[u8], replaced: &[u8]) -> Vec<ByteRun> {
+  let mut runs: Vec<ByteRun> = Vec::new();
+  //start and end (exclusive) of the run being found
+  let mut current: Option<(usize, usize)> = None;
+  let mut push_run = |start: usize, end: usize| runs.push(ByteRun { offset: start as u32, restored: restored[start..end].to_vec(), replaced: replaced[start..end].to_vec() });
+  for i in 0..restored.len() {
+    if restored[i] == replaced[i] {
+      continue;
+    }
+    current = match current {
+      Some((start, end)) if i-end <= MAX_RUN_GAP => Some((start, i+1)),
+      Some((start, end)) => {
+        push_run(start, end);
+        Some((i, i+1))
+      },
+      None => Some((i, i+1)),
+    };
+  }
+  if let Some((start, end)) = current {
+    push_run(start, end);
+  }
+  runs
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::indexed::{IndexedLayout, new_indexed};
+
+  #[test]
+  fn bytes_roundtrip() {
+    let old_bmp = BMP::new(3, 3, None);
+    let mut new_bmp = old_bmp.clone();
+    new_bmp.change_color_of_pixel(1, 1, [255, 0, 0, 255]).unwrap();
+    let rotated_bmp = BMP::new(2, 4, None);
+    let mut history = History::new(DEFAULT_HISTORY_BUDGET);
+    history.push(HistoryEntry::new("Pixel".to_string(), Vec::new(), &old_bmp, &new_bmp));
+    history.push(HistoryEntry::new("Resize".to_string(), Vec::new(), &new_bmp, &rotated_bmp));
+    let undone_bmp = history.undo(&rotated_bmp).unwrap();
+
+    let mut restored = History::from_bytes(&history.to_bytes()).unwrap();
+    assert!(restored.info() == history.info());
+    assert!(restored.redo(&undone_bmp).unwrap() == rotated_bmp);
+    assert!(restored.jump_to(0, &rotated_bmp).unwrap() == old_bmp);
+  }
+
+  #[test]
+  fn indexed_edits_are_diffs() {
+    let old_bmp = new_indexed(4, 2, 8, &[[0, 0, 0, 255], [255, 255, 255, 255]], &[0; 8]).unwrap();
+    let mut new_bmp = old_bmp.clone();
+    IndexedLayout::of(&old_bmp).unwrap().unwrap().set_index(&mut new_bmp, 2, 1, 1).unwrap();
+    let entry = HistoryEntry::new("Pixel".to_string(), Vec::new(), &old_bmp, &new_bmp);
+    assert!(matches!(&entry.change, HistoryChange::Diff(runs) if runs.len() == 1 && runs[0].restored.len() == 1));
+    let mut history = History::new(DEFAULT_HISTORY_BUDGET);
+    history.push(entry);
+    assert!(history.undo(&new_bmp).unwrap() == old_bmp);
+    assert!(history.redo(&old_bmp).unwrap() == new_bmp);
+  }
+
+  #[test]
+  fn undo_resize() {
+    let mut old_bmp = BMP::new(3, 3, None);
+    old_bmp.change_color_of_pixel(2, 2, [255, 0, 0, 255]).unwrap();
+    let new_bmp = BMP::new(2, 2, None);
+    let entry = HistoryEntry::new("Resize".to_string(), Vec::new(), &old_bmp, &new_bmp);
+    assert!(matches!(entry.change, HistoryChange::Snapshot(_)));
+    let mut history = History::new(DEFAULT_HISTORY_BUDGET);
+    history.push(entry);
+    assert!(history.undo(&new_bmp).unwrap() == old_bmp);
+    assert!(history.redo(&old_bmp).unwrap() == new_bmp);
+  }
+
+  #[test]
+  fn truncated_bytes_are_invalid() {
+    let old_bmp = BMP::new(2, 2, None);
+    let mut history = History::new(DEFAULT_HISTORY_BUDGET);
+    history.push(HistoryEntry::new("Invert".to_string(), vec![EditorCommand::Invert], &old_bmp, &BMP::new(2, 2, Some([0, 0, 0, 255]))));
+    let bytes = history.to_bytes();
+    assert!(History::from_bytes(&bytes[..bytes.len()-1]).is_none());
+    assert!(History::from_bytes(&[]).is_none());
+  }
+}
@@ -0,0 +1,98 @@
+use yew::prelude::*;
+
+use bmp_editor::history::HistoryInfo;
+
+//list of the operations in the undo history, click one to undo or redo until that point
+
+#[derive(PartialEq, Properties)]
+pub struct HistoryPanelProps {
+  pub history_info: HistoryInfo,
+  pub show: bool,
+  pub history_jump_callback: Callback<usize>,
+}
+
+pub enum HistoryPanelMessage {
+  Show,
+  Hide,
+  Jump(usize),
+}
+
+pub struct HistoryPanel {
+  display: String,
+}
+
+impl Component for HistoryPanel {
+  type Message = HistoryPanelMessage;
+  type Properties = HistoryPanelProps;
+
+  fn create(_ctx: &Context<Self>) -> Self {
+    Self { display: "none".to_string() }
+  }
+
+  fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+    match msg {
+      Self::Message::Show => {
+        self.display = "block".to_string();
+        true
+      },
+      Self::Message::Hide => {
+        self.display = "none".to_string();
+        true
+      },
+      Self::Message::Jump(undo_steps) => {
+        ctx.props().history_jump_callback.emit(undo_steps);
+        false
+      },
+    }
+  }
+
+  fn view(&self, ctx: &Context<Self>) -> Html {
+    let link = ctx.link().clone();
+
+    if self.display == "none".to_string() && ctx.props().show {
+      link.send_message(Self::Message::Show);
+    } else if self.display == "block".to_string() && !ctx.props().show {
+      link.send_message(Self::Message::Hide);
+    }
+
+    let history_info = &ctx.props().history_info;
+    let undo_len = history_info.undo_names.len();
+
+    //the state after an entry is the state with (index of entry + 1) entries left to undo
+    //the first item is the state before any of the entries
+    let start_item = {
+      let jump = ctx.link().callback(|_| Self::Message::Jump(0));
+      let class = if undo_len == 0 { "history-item history-current" } else { "history-item" };
+      html! {
+        <li class={class} onclick={jump}>{ "(start)" }</li>
+      }
+    };
+
+    let undo_items = history_info.undo_names.iter().enumerate().map(|(index, name)| {
+      let jump = ctx.link().callback(move |_| Self::Message::Jump(index+1));
+      let class = if index+1 == undo_len { "history-item history-current" } else { "history-item" };
+      html! {
+        <li class={class} onclick={jump}>{ name }</li>
+      }
+    }).collect::<Html>();
+
+    //undone entries that can still be redone
+    let redo_items = history_info.redo_names.iter().enumerate().map(|(index, name)| {
+      let jump = ctx.link().callback(move |_| Self::Message::Jump(undo_len+index+1));
+      html! {
+        <li class={"history-item history-undone"} onclick={jump}>{ name }</li>
+      }
+    }).collect::<Html>();
+
+    html! {
+      <div id={"history-panel"} style={"display: ".to_string()+&self.display}>
+        <h3>{ "History" }</h3>
+        <ul>
+          { start_item }
+          { undo_items }
+          { redo_items }
+        </ul>
+      </div>
+    }
+  }
+}
@@ -1,7 +1,6 @@
 use yew::prelude::*;
-use wasm_bindgen::{JsValue, JsCast};
-use web_sys::{Blob, Url, HtmlLinkElement, HtmlSelectElement};
-use js_sys::{Uint8Array, Array};
+use wasm_bindgen::JsCast;
+use web_sys::{HtmlSelectElement, HtmlInputElement};
 use bmp_rust::bmp::BMP;
 use gloo_utils::document;
 use gloo_events::EventListener;
@@ -9,9 +8,13 @@ use gloo_console::log;
 use std::collections::HashMap;
 
 use crate::tools::ToolsTypes;
+use crate::export_dialog::ExportDialog;
+use crate::download::prompt_download;
+use bmp_editor::history::HistoryInfo;
+use bmp_editor::png::bmp_to_png;
 
 //keyboard shortcuts: ctrl+[ for tool left cycle, ctrl+] for tool right cycle, and then ctrl+shift+key for specific tools
-//ctrl+z to redo, possibly ctrl+y to undo redo
+//ctrl+z to undo, ctrl+y or ctrl+shift+z to redo, ctrl+m to play the selected macro
 
 #[derive(PartialEq, Properties)]
 pub struct ImageActionsProps {
@@ -19,6 +22,18 @@ pub struct ImageActionsProps {
   pub current_bmp: Option<BMP>,
   pub tool_change_callback: Callback<ToolsTypes>,
   pub undo_callback: Callback<bool>,
+  pub redo_callback: Callback<bool>,
+  pub history_info: HistoryInfo,
+  pub history_budget_callback: Callback<usize>,
+  pub macro_names: Vec<String>,
+  pub selected_macro: Option<usize>,
+  pub recording_macro: bool,
+  pub record_macro_callback: Callback<bool>,
+  //name to save the recorded macro as, empty to discard it
+  pub save_macro_callback: Callback<String>,
+  pub select_macro_callback: Callback<usize>,
+  pub play_macro_callback: Callback<bool>,
+  pub delete_macro_callback: Callback<bool>,
   pub selected_tool: ToolsTypes,
   pub keybinds: HashMap<String, KeybindActions>,
 }
@@ -27,13 +42,24 @@ pub enum ImageActionsMessage {
   Show,
   Hide,
   Undo(bool),
+  Redo(bool),
+  SetHistoryBudget(usize),
+  RecordMacro,
+  StopRecording,
+  SelectMacro(usize),
+  PlayMacro(bool),
+  DeleteMacro,
   ToolChange(ToolsTypes),
   SetKeybindsListener(Option<EventListener>),
+  ShowExport(bool),
+  DownloadPng,
 }
 
 #[derive(Clone, PartialEq)]
 pub enum KeybindActions {
   Undo,
+  Redo,
+  PlayMacro,
   PreviousTool,
   NextTool,
   ToolChange(ToolsTypes),
@@ -42,6 +68,7 @@ pub enum KeybindActions {
 pub struct ImageActions {
   display: String,
   keybinds_listener: Option<EventListener>,
+  show_export: bool,
 }
 
 impl Component for ImageActions {
@@ -49,7 +76,7 @@ impl Component for ImageActions {
   type Properties = ImageActionsProps;
 
   fn create(_ctx: &Context<Self>) -> Self {
-    Self { display: "none".to_string(), keybinds_listener: None }
+    Self { display: "none".to_string(), keybinds_listener: None, show_export: false }
   }
 
   fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
@@ -67,7 +94,48 @@ impl Component for ImageActions {
         //also just here because callbacks require parameters, that is probably bad practice sorry
         ctx.props().undo_callback.emit(button_clicked);
         true
-      }
+      },
+      Self::Message::Redo(button_clicked) => {
+        //same as undo, true if button clicked
+        ctx.props().redo_callback.emit(button_clicked);
+        true
+      },
+      Self::Message::SetHistoryBudget(budget) => {
+        ctx.props().history_budget_callback.emit(budget);
+        false
+      },
+      Self::Message::RecordMacro => {
+        ctx.props().record_macro_callback.emit(true);
+        false
+      },
+      Self::Message::StopRecording => {
+        //cancelling the prompt keeps recording, an empty name discards the recording
+        let window = web_sys::window().unwrap();
+        if let Ok(Some(name)) = window.prompt_with_message_and_default("Macro name? (leave empty to discard)", "") {
+          ctx.props().save_macro_callback.emit(name.trim().to_string());
+        }
+        false
+      },
+      Self::Message::SelectMacro(index) => {
+        ctx.props().select_macro_callback.emit(index);
+        false
+      },
+      Self::Message::PlayMacro(button_clicked) => {
+        //same as undo, true if button clicked
+        ctx.props().play_macro_callback.emit(button_clicked);
+        false
+      },
+      Self::Message::DeleteMacro => {
+        let props = ctx.props();
+        if props.selected_macro.is_some() {
+          let name = &props.macro_names[props.selected_macro.unwrap()];
+          let window = web_sys::window().unwrap();
+          if window.confirm_with_message(&format!("Delete macro \"{}\"?", name)).unwrap_or(false) {
+            props.delete_macro_callback.emit(true);
+          }
+        }
+        false
+      },
       Self::Message::ToolChange(tool_type) => {
         ctx.props().tool_change_callback.emit(tool_type);
         false
@@ -75,7 +143,20 @@ impl Component for ImageActions {
       Self::Message::SetKeybindsListener(keybinds_listener) => {
         self.keybinds_listener = keybinds_listener;
         false
-      }
+      },
+      Self::Message::ShowExport(show_export) => {
+        self.show_export = show_export;
+        true
+      },
+      Self::Message::DownloadPng => {
+        if let Some(bmp) = ctx.props().current_bmp.as_ref() {
+          match bmp_to_png(bmp) {
+            Ok(png) => prompt_download(&png, "edited.png"),
+            Err(e) => log!(format!("Could not make png: {}", e)),
+          }
+        }
+        false
+      },
     }
   }
 
@@ -115,13 +196,16 @@ impl Component for ImageActions {
         }
         let keyboard_event: KeyboardEvent = e.dyn_into::<web_sys::KeyboardEvent>().unwrap();
         let mut pressed_key: String = keyboard_event.key();
-        if keyboard_event.ctrl_key() {
+        if keyboard_event.ctrl_key() && keyboard_event.shift_key() {
+          //shift makes the key uppercase, so lowercase it again
+          pressed_key = "ctrl+shift+".to_owned()+&pressed_key.to_lowercase();
+        } else if keyboard_event.ctrl_key() {
           pressed_key = "ctrl+".to_owned()+&pressed_key;
         }
         let bind = keybinds.get(&pressed_key);
         //log!(pressed_key);
         if bind.is_some() {
-          let tools_vec: Vec<ToolsTypes> = vec![ToolsTypes::NoneSelected, ToolsTypes::ClickFill, ToolsTypes::BucketFill, ToolsTypes::Invert, ToolsTypes::Line, ToolsTypes::Rect, ToolsTypes::Ellipse, ToolsTypes::Greyscale, ToolsTypes::Gaussian, ToolsTypes::Box, ToolsTypes::Median, ToolsTypes::Rotate];
+          let tools_vec: Vec<ToolsTypes> = vec![ToolsTypes::NoneSelected, ToolsTypes::ClickFill, ToolsTypes::Pencil, ToolsTypes::BucketFill, ToolsTypes::Eyedropper, ToolsTypes::Invert, ToolsTypes::Line, ToolsTypes::Rect, ToolsTypes::Ellipse, ToolsTypes::Greyscale, ToolsTypes::Gaussian, ToolsTypes::Box, ToolsTypes::Median, ToolsTypes::Rotate];
           let filters_select: HtmlSelectElement = filters_ref2.cast().unwrap();
           let shapes_select: HtmlSelectElement = shapes_ref2.cast().unwrap();
           let fills_select: HtmlSelectElement = fills_ref2.cast().unwrap();
@@ -129,6 +213,12 @@ impl Component for ImageActions {
             KeybindActions::Undo => {
               return Some(Self::Message::Undo(false));
             },
+            KeybindActions::Redo => {
+              return Some(Self::Message::Redo(false));
+            },
+            KeybindActions::PlayMacro => {
+              return Some(Self::Message::PlayMacro(false));
+            },
             KeybindActions::PreviousTool => {
               let tool_index = tools_vec.iter().position(|&item| item == selected_tool).unwrap();
               let new_tool_index;
@@ -167,46 +257,7 @@ impl Component for ImageActions {
 
     let select_callback = Callback::from(move |e: Event| {
       let select: HtmlSelectElement = e.target_unchecked_into();
-      let select_value = select.value();
-      let tool_type: ToolsTypes; 
-      match select_value.as_str() {
-        "click-fill" => {
-          tool_type = ToolsTypes::ClickFill;
-        },
-        "bucket-fill" => {
-          tool_type = ToolsTypes::BucketFill;
-        },
-        "invert" => {
-          tool_type = ToolsTypes::Invert;
-        },
-        "line" => {
-          tool_type = ToolsTypes::Line;
-        },
-        "rect" => {
-          tool_type = ToolsTypes::Rect;
-        },
-        "ellipse" => {
-          tool_type = ToolsTypes::Ellipse;
-        },
-        "greyscale" => {
-          tool_type = ToolsTypes::Greyscale;
-        },
-        "gaussian" => {
-          tool_type = ToolsTypes::Gaussian;
-        },
-        "box" => {
-          tool_type = ToolsTypes::Box;
-        },
-        "median" => {
-          tool_type = ToolsTypes::Median;
-        },
-        "rotate" => {
-          tool_type = ToolsTypes::Rotate;
-        },
-        _ => {
-          tool_type = ToolsTypes::NoneSelected;
-        }
-      }
+      let tool_type = ToolsTypes::from_name(&select.value());
       link2.send_message(Self::Message::ToolChange(tool_type));
     });
 
@@ -252,48 +303,80 @@ impl Component for ImageActions {
       })
     };
 
-    let contents = ctx.clone().props().clone().current_bmp.as_ref().unwrap_or(&BMP::new(1, 1, None)).contents.to_owned();
+    //pick the format in the export dialog first
+    let download = ctx.link().callback(|_| Self::Message::ShowExport(true));
 
-    let download_ref = NodeRef::default();
+    let close_export = ctx.link().callback(|_: bool| Self::Message::ShowExport(false));
 
-    let download = {
-      let download_ref2 = download_ref.clone();
-      let window = web_sys::window().unwrap();
-      Callback::from(move |_| {
-        //create blob
-        let bmp_array: Array = Array::new();
-        for i in 0..contents.len() {
-          bmp_array.push(&JsValue::from(contents[i]));
-        }
-        let bmp_uint8array = Uint8Array::new(&JsValue::from(bmp_array));
-        let bmp_array2 = Array::new();
-        bmp_array2.push(&bmp_uint8array.buffer());
-        let bmp_blob = Blob::new_with_u8_array_sequence(&bmp_array2).unwrap();
-        //get object url
-        let obj_url = Url::create_object_url_with_blob(&bmp_blob).unwrap();
-        //download
-        let download_link: HtmlLinkElement = download_ref2.clone().cast().unwrap();
-        download_link.set_href(&obj_url);
-        //prompt to set file name
-        let file_name = window.prompt_with_message_and_default("File name?", "edited.bmp").unwrap_or(Some("edited.bmp".to_string())).unwrap();
-        if file_name != "" {
-          download_link.set_attribute("download", &file_name).unwrap();
-          download_link.click();
-        }
-      })
-    };
+    let download_png = ctx.link().callback(|_| Self::Message::DownloadPng);
 
     let undo = ctx.link().callback(|_| {
       Self::Message::Undo(true)
     });
 
+    let redo = ctx.link().callback(|_| {
+      Self::Message::Redo(true)
+    });
+
+    let history_budget = ctx.link().batch_callback(|e: Event| {
+      //in megabytes
+      let budget_input: HtmlInputElement = e.target_unchecked_into();
+      let budget_mb: Option<f64> = budget_input.value().parse().ok();
+      if budget_mb.is_some() && budget_mb.unwrap() >= 0.0 {
+        Some(Self::Message::SetHistoryBudget((budget_mb.unwrap()*1024.0*1024.0) as usize))
+      } else {
+        None
+      }
+    });
+
+    let record_macro = if ctx.props().recording_macro {
+      let stop_recording = ctx.link().callback(|_| Self::Message::StopRecording);
+      html! {
+        <button onclick={stop_recording} class={"image-actions recording"}>{ "Stop Recording" }</button>
+      }
+    } else {
+      let record = ctx.link().callback(|_| Self::Message::RecordMacro);
+      html! {
+        <button onclick={record} class={"image-actions"}>{ "Record Macro" }</button>
+      }
+    };
+
+    let select_macro = ctx.link().batch_callback(|e: Event| {
+      let select: HtmlSelectElement = e.target_unchecked_into();
+      select.value().parse::<usize>().ok().map(Self::Message::SelectMacro)
+    });
+
+    let macro_options = if ctx.props().macro_names.len() == 0 {
+      html! {
+        <option value={"none"} selected={true} disabled={true}>{ "-- No Macros --" }</option>
+      }
+    } else {
+      ctx.props().macro_names.iter().enumerate().map(|(index, name)| {
+        html! {
+          <option value={index.to_string()} selected={ctx.props().selected_macro == Some(index)}>{ name }</option>
+        }
+      }).collect::<Html>()
+    };
+
+    let play_macro = ctx.link().callback(|_| {
+      Self::Message::PlayMacro(true)
+    });
+
+    let delete_macro = ctx.link().callback(|_| {
+      Self::Message::DeleteMacro
+    });
+
+    let history_info = &ctx.props().history_info;
+    let history_text = format!("Undo steps: {} ({:.1}/{:.1} MB)", history_info.undo_names.len(), bytes_to_mb(history_info.used_bytes), bytes_to_mb(history_info.budget));
+
     html! {
       <div id={"image-actions-container"} style={"display: ".to_string()+&self.display}>
-        <a ref={download_ref}></a>
         <select ref={fills_ref} class={"image-actions"} onchange={fills}>
           <option value={"none-selected"} selected={true}>{ "-- Fills --" }</option>
           <option value={"click-fill"}>{ "Click Fill" }</option>
+          <option value={"pencil"}>{ "Pencil" }</option>
           <option value={"bucket-fill"}>{ "Bucket Fill" }</option>
+          <option value={"eyedropper"}>{ "Eyedropper" }</option>
           <option value={"invert"}>{ "Invert" }</option>
         </select>
         <select ref={shapes_ref} class={"image-actions"} onchange={shapes}>
@@ -311,9 +394,26 @@ impl Component for ImageActions {
           <option value={"rotate"}>{ "Rotate" }</option>
         </select>
         <button onclick={undo} class={"image-actions"}>{ "Undo" }</button>
+        <button onclick={redo} class={"image-actions"}>{ "Redo" }</button>
         <button onclick={download} class={"image-actions"}>{ "Download" }</button>
+        <button onclick={download_png} class={"image-actions"}>{ "Download PNG" }</button>
+        <ExportDialog show={self.show_export} current_bmp={ctx.props().current_bmp.clone()} close_callback={close_export} />
+        <span class={"history-info"}>{ history_text }</span>
+        <label for="history-budget">{ "History limit (MB): " }</label>
+        <input id="history-budget" type="number" name="history-budget" min="0" step="1" value={bytes_to_mb(history_info.budget).to_string()} onchange={history_budget}/>
+        <br/>
+        { record_macro }
+        <select class={"image-actions"} onchange={select_macro}>
+          { macro_options }
+        </select>
+        <button onclick={play_macro} class={"image-actions"}>{ "Play Macro" }</button>
+        <button onclick={delete_macro} class={"image-actions"}>{ "Delete Macro" }</button>
         <br/>
       </div>
     }
   }
 }
+
+fn bytes_to_mb(bytes: usize) -> f64 {
+  bytes as f64/1024.0/1024.0
+}
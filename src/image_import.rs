@@ -0,0 +1,32 @@
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen::Clamped;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{Blob, CanvasRenderingContext2d, HtmlCanvasElement, ImageBitmap};
+use gloo_utils::document;
+use bmp_rust::bmp::BMP;
+
+use bmp_editor::render::bmp_from_rgba;
+
+//other image formats (png, jpeg, gif, or anything else the browser can decode) are drawn onto a canvas
+//and the pixels read back, to make a new 32 bit bmp. gifs only get their first frame
+
+//types the file input offers, bmps are still loaded as they are
+pub const IMPORT_TYPES: &str = "image/bmp,image/png,image/jpeg,image/gif";
+
+pub fn is_bmp(mime_type: &str, file_name: &str) -> bool {
+  mime_type == "image/bmp" || mime_type == "image/x-ms-bmp" || file_name.to_lowercase().ends_with(".bmp")
+}
+
+pub async fn decode_image(blob: &Blob) -> Result<BMP, JsValue> {
+  let window = web_sys::window().unwrap();
+  let bitmap: ImageBitmap = JsFuture::from(window.create_image_bitmap_with_blob(blob)?).await?.dyn_into()?;
+  let (width, height) = (bitmap.width(), bitmap.height());
+  let canvas: HtmlCanvasElement = document().create_element("canvas")?.unchecked_into();
+  canvas.set_width(width);
+  canvas.set_height(height);
+  let context: CanvasRenderingContext2d = canvas.get_context("2d")?.ok_or("no 2d context")?.dyn_into()?;
+  context.draw_image_with_image_bitmap(&bitmap, 0.0, 0.0)?;
+  bitmap.close();
+  let Clamped(rgba) = context.get_image_data(0.0, 0.0, f64::from(width), f64::from(height))?.data();
+  bmp_from_rgba(width, height, &rgba).map_err(|e| JsValue::from(e.to_string()))
+}
@@ -0,0 +1,207 @@
+use bmp_rust::bmp::{BMP, DIBHEADER, ErrorKind};
+
+use crate::export::headers;
+
+//1, 2, 4 and 8 bit images, where every pixel is an index into the color table that comes after the headers.
+//bmp_rust can only change pixels of 24 and 32 bit images (and misreads rows of pixels smaller than a byte),
+//so indexed images are read and edited on the bytes here instead. that way they stay indexed when saved
+
+//where everything is in an indexed image
+#[derive(PartialEq, Clone, Debug)]
+pub struct IndexedLayout {
+  pub bitcount: u16,
+  pub width: u32,
+  pub height: u32,
+  top_down: bool,
+  pixels_offset: usize,
+  row_size: usize,
+  table_offset: usize,
+  entry_size: usize,
+  pub table_len: usize,
+}
+
+impl IndexedLayout {
+  //none if the image isn't an uncompressed indexed image
+  pub fn of(bmp: &BMP) -> Result<Option<Self>, ErrorKind> {
+    let dib_header = bmp.get_dib_header()?;
+    if ![1, 2, 4, 8].contains(&dib_header.bitcount) {
+      return Ok(None);
+    }
+    if dib_header.compression.as_ref().map(|compression| compression != "BI_RGB").unwrap_or(false) {
+      return Ok(None);
+    }
+    let pixels_offset = bmp.get_offset() as usize;
+    let (table_offset, entry_size, table_len) = table_position(bmp, &dib_header);
+    let row_size = row_size(dib_header.bitcount, dib_header.width);
+    let height = dib_header.height.unsigned_abs();
+    if table_len == 0 || bmp.contents.len() < pixels_offset+row_size*height as usize {
+      return Err(ErrorKind::InvalidParameter("indexed image is missing its color table or pixels".to_string()));
+    }
+    Ok(Some(Self {
+      bitcount: dib_header.bitcount,
+      width: dib_header.width,
+      height,
+      top_down: dib_header.height < 0,
+      pixels_offset,
+      row_size,
+      table_offset,
+      entry_size,
+      table_len,
+    }))
+  }
+
+  pub fn colors(&self, bmp: &BMP) -> Vec<[u8; 4]> {
+    read_table(bmp, self.table_offset, self.entry_size, self.table_len)
+  }
+
+  //every pixel using the index changes with it. alpha is ignored
+  pub fn set_color(&self, bmp: &mut BMP, index: u8, color: [u8; 4]) -> Result<(), ErrorKind> {
+    if usize::from(index) >= self.table_len {
+      return Err(ErrorKind::InvalidParameter(format!("color table only has {} colors", self.table_len)));
+    }
+    let start = self.table_offset+usize::from(index)*self.entry_size;
+    bmp.contents[start..start+3].copy_from_slice(&[color[2], color[1], color[0]]);
+    Ok(())
+  }
+
+  //byte the pixel is in, and how far the pixel's bits are shifted in that byte (pixels further left are in the higher bits)
+  fn pixel_position(&self, x: u16, y: u16) -> Result<(usize, u32), ErrorKind> {
+    if u32::from(x) >= self.width || u32::from(y) >= self.height {
+      return Err(ErrorKind::InvalidParameter(format!("pixel ({}, {}) is outside the image", x, y)));
+    }
+    //y is from the top, but most images store the bottom row first
+    let row = if self.top_down { u32::from(y) } else { self.height-u32::from(y)-1 };
+    let bit = usize::from(x)*usize::from(self.bitcount);
+    let shift = 8-u32::from(self.bitcount)-(bit % 8) as u32;
+    Ok((self.pixels_offset+row as usize*self.row_size+bit/8, shift))
+  }
+
+  pub fn index_at(&self, bmp: &BMP, x: u16, y: u16) -> Result<u8, ErrorKind> {
+    let (byte, shift) = self.pixel_position(x, y)?;
+    let mask = ((1u16 << self.bitcount)-1) as u8;
+    Ok((bmp.contents[byte] >> shift) & mask)
+  }
+
+  pub fn set_index(&self, bmp: &mut BMP, x: u16, y: u16, index: u8) -> Result<(), ErrorKind> {
+    if usize::from(index) >= self.table_len {
+      return Err(ErrorKind::InvalidParameter(format!("color table only has {} colors", self.table_len)));
+    }
+    let (byte, shift) = self.pixel_position(x, y)?;
+    let mask = ((1u16 << self.bitcount)-1) as u8;
+    bmp.contents[byte] = (bmp.contents[byte] & !(mask << shift)) | (index << shift);
+    Ok(())
+  }
+}
+
+//where the color table starts, how many bytes each color is, and how many colors there are
+fn table_position(bmp: &BMP, dib_header: &DIBHEADER) -> (usize, usize, usize) {
+  let table_offset = 14+dib_header.size as usize;
+  //3 bytes (bgr) for the old 12 byte header, 4 (bgr and an unused byte) otherwise
+  let entry_size = if dib_header.size == 12 { 3 } else { 4 };
+  //ClrUsed of 0 means the whole 2^bitcount colors
+  let colors_used = match dib_header.ClrUsed {
+    Some(colors_used) if colors_used > 0 => colors_used as usize,
+    _ => 1 << dib_header.bitcount.min(8),
+  };
+  let table_len = colors_used.min((bmp.get_offset() as usize).saturating_sub(table_offset)/entry_size);
+  (table_offset, entry_size, table_len)
+}
+
+//the color table as rgba. the unused fourth byte is not alpha, so every color is opaque
+fn read_table(bmp: &BMP, table_offset: usize, entry_size: usize, table_len: usize) -> Vec<[u8; 4]> {
+  (0..table_len).map(|index| {
+    let start = table_offset+index*entry_size;
+    let bgr = &bmp.contents[start..start+3];
+    [bgr[2], bgr[1], bgr[0], 255]
+  }).collect()
+}
+
+//color table of any 1 to 8 bit image, including compressed ones
+pub fn color_table(bmp: &BMP) -> Result<Vec<[u8; 4]>, ErrorKind> {
+  let dib_header = bmp.get_dib_header()?;
+  if dib_header.bitcount > 8 {
+    return Err(ErrorKind::DoesNotExist);
+  }
+  let (table_offset, entry_size, table_len) = table_position(bmp, &dib_header);
+  Ok(read_table(bmp, table_offset, entry_size, table_len))
+}
+
+//rows are padded to a multiple of 4 bytes
+fn row_size(bitcount: u16, width: u32) -> usize {
+  (bitcount as usize*width as usize).div_ceil(32)*4
+}
+
+pub fn is_indexed(bmp: &BMP) -> bool {
+  matches!(IndexedLayout::of(bmp), Ok(Some(_)))
+}
+
+//index of the color in the table, or the closest one (by rgb distance) if it isn't there
+pub fn nearest_index(colors: &[[u8; 4]], color: [u8; 4]) -> u8 {
+  colors.iter().enumerate().min_by_key(|(_, table_color)| {
+    (0..3).map(|i| (i32::from(table_color[i])-i32::from(color[i])).pow(2)).sum::<i32>()
+  }).map(|(index, _)| index as u8).unwrap_or(0)
+}
+
+//make a bottom up indexed image with a 40 byte header. indexes go row by row from the top left
+pub fn new_indexed(width: u32, height: u32, bitcount: u16, colors: &[[u8; 4]], indexes: &[u8]) -> Result<BMP, ErrorKind> {
+  if ![1, 2, 4, 8].contains(&bitcount) || colors.is_empty() || colors.len() > 1 << bitcount {
+    return Err(ErrorKind::InvalidParameter(format!("{} colors can't be stored in a {} bit image", colors.len(), bitcount)));
+  }
+  if indexes.len() != (width*height) as usize {
+    return Err(ErrorKind::InvalidParameter(format!("expected {} pixels, found {}", width*height, indexes.len())));
+  }
+  let row_size = row_size(bitcount, width);
+  let table: Vec<u8> = colors.iter().flat_map(|color| [color[2], color[1], color[0], 0]).collect();
+  let mut contents = headers(width, height, bitcount, 0, &table, colors.len() as u32, row_size*height as usize);
+  contents.resize(contents.len()+row_size*height as usize, 0);
+  let mut bmp = BMP::new(1, 1, None);
+  bmp.contents = contents;
+  let layout = IndexedLayout::of(&bmp)?.unwrap();
+  for y in 0..height {
+    for x in 0..width {
+      layout.set_index(&mut bmp, x as u16, y as u16, indexes[(y*width+x) as usize])?;
+    }
+  }
+  Ok(bmp)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const COLORS: [[u8; 4]; 4] = [[0, 0, 0, 255], [255, 255, 255, 255], [255, 0, 0, 255], [0, 0, 255, 255]];
+
+  #[test]
+  fn packed_pixels() {
+    //5 wide, so rows don't end on a byte
+    let indexes: Vec<u8> = (0..15).map(|i| (i % 4) as u8).collect();
+    for bitcount in [2, 4, 8] {
+      let bmp = new_indexed(5, 3, bitcount, &COLORS, &indexes).unwrap();
+      let layout = IndexedLayout::of(&bmp).unwrap().unwrap();
+      assert_eq!(layout.colors(&bmp), COLORS.to_vec());
+      for y in 0..3 {
+        for x in 0..5 {
+          assert_eq!(layout.index_at(&bmp, x, y).unwrap(), indexes[(y*5+x) as usize]);
+        }
+      }
+    }
+    let bmp = new_indexed(9, 1, 1, &COLORS[..2], &[1, 0, 0, 0, 0, 0, 0, 1, 1]).unwrap();
+    //bottom up, one row of 2 bytes padded to 4
+    assert_eq!(bmp.contents[bmp.get_offset() as usize..], [0b10000001, 0b10000000, 0, 0]);
+    assert!(!is_indexed(&BMP::new(2, 2, None)));
+  }
+
+  #[test]
+  fn edit_index_and_table() {
+    let mut bmp = new_indexed(3, 2, 4, &COLORS, &[0, 1, 2, 3, 2, 1]).unwrap();
+    let layout = IndexedLayout::of(&bmp).unwrap().unwrap();
+    layout.set_index(&mut bmp, 2, 1, 3).unwrap();
+    assert_eq!(layout.index_at(&bmp, 2, 1).unwrap(), 3);
+    assert_eq!(layout.index_at(&bmp, 1, 1).unwrap(), 2);
+    layout.set_color(&mut bmp, 2, [0, 255, 0, 255]).unwrap();
+    assert_eq!(layout.colors(&bmp)[2], [0, 255, 0, 255]);
+    assert!(layout.set_index(&mut bmp, 3, 0, 1).is_err());
+    assert!(layout.set_color(&mut bmp, 4, [0, 0, 0, 255]).is_err());
+    assert_eq!(nearest_index(&COLORS, [250, 10, 10, 255]), 2);
+  }
+}
@@ -0,0 +1,21 @@
+//the editing logic that doesn't need yew or the browser, so it can be tested natively and used outside the web app
+
+pub mod bitfields;
+mod bytes;
+pub mod color;
+pub mod header;
+pub mod history;
+pub mod indexed;
+pub mod editor;
+pub mod export;
+pub mod macros;
+pub mod palette;
+pub mod parse;
+pub mod png;
+pub mod render;
+pub mod rle;
+pub mod script;
+pub mod shapes;
+pub mod stroke;
+pub mod validate;
+pub mod viewport;
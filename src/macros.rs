@@ -0,0 +1,52 @@
+use crate::bytes::ByteReader;
+use crate::editor::EditorCommand;
+
+//a recorded sequence of edits that can be played back on any image
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct Macro {
+  pub name: String,
+  pub commands: Vec<EditorCommand>,
+}
+
+impl Macro {
+  //name length, name, number of commands, then the commands
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut bytes: Vec<u8> = Vec::new();
+    bytes.extend((self.name.len() as u32).to_le_bytes());
+    bytes.extend(self.name.as_bytes());
+    bytes.extend((self.commands.len() as u32).to_le_bytes());
+    for command in self.commands.iter() {
+      command.write_bytes(&mut bytes);
+    }
+    bytes
+  }
+
+  //returns none if the bytes are not a valid serialized macro
+  pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+    let mut reader = ByteReader::new(bytes);
+    let name_len = reader.take_u32()? as usize;
+    let name = String::from_utf8(reader.take(name_len)?.to_vec()).ok()?;
+    let mut commands: Vec<EditorCommand> = Vec::new();
+    for _ in 0..reader.take_u32()? {
+      commands.push(EditorCommand::read_bytes(&mut reader)?);
+    }
+    Some(Self { name, commands })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn bytes_roundtrip() {
+    let sprite_macro = Macro {
+      name: "Sprite cleanup".to_string(),
+      commands: vec![EditorCommand::Greyscale, EditorCommand::MedianFilter(1), EditorCommand::Invert],
+    };
+    let bytes = sprite_macro.to_bytes();
+    assert_eq!(Macro::from_bytes(&bytes).unwrap(), sprite_macro);
+    assert!(Macro::from_bytes(&bytes[..bytes.len()-1]).is_none());
+  }
+}
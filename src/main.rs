@@ -1,33 +1,69 @@
 use yew::prelude::*;
-use bmp_rust::bmp::{BMP, ImageDiff};
+use bmp_rust::bmp::BMP;
 use gloo_console::log;
+use gloo::timers::callback::Interval;
+use wasm_bindgen_futures::spawn_local;
 use std::collections::HashMap;
 
+use bmp_editor::editor::{Document, EditorCommand};
+use bmp_editor::header::{header_fields, resolution};
+use bmp_editor::history::{History, DEFAULT_HISTORY_BUDGET};
+use bmp_editor::macros::Macro;
+use bmp_editor::palette::{Palette, add_recent};
+use bmp_editor::shapes::DragShape;
+use bmp_editor::stroke::pixels_bounds;
+use bmp_editor::validate::validate_bmp;
+
 mod start;
 use start::Start;
 mod create_load;
 use create_load::Create;
 use create_load::Load;
 mod pixels;
-use pixels::{Pixels, PixelRedrawRange};
+use pixels::{Pixels, PixelRedrawRange, DragTool};
 mod pixel_actions;
 use pixel_actions::{PixelActions, PixelInfo};
 mod image_actions;
 use image_actions::{ImageActions, KeybindActions};
 mod tools;
 use tools::{Tools, ToolsTypes};
+mod color_picker;
+mod history_panel;
+use history_panel::HistoryPanel;
+mod script_panel;
+use script_panel::ScriptPanel;
+mod palette_panel;
+use palette_panel::PalettePanel;
+mod color_table_panel;
+use color_table_panel::ColorTablePanel;
+mod header_panel;
+use header_panel::HeaderPanel;
+mod export_dialog;
+mod error_panel;
+use error_panel::ErrorPanel;
+mod image_import;
+mod download;
+use download::prompt_download;
+mod storage;
+use storage::{Session, save_session, load_session, save_macro, delete_macro, load_macros, save_palette, load_palette};
 
 #[derive(PartialEq, Properties, Default)]
 pub struct Props;
 
+//in milliseconds
+const AUTOSAVE_INTERVAL: u32 = 5000;
+
 //App
 
 pub enum AppMessage {
   Create,
   Load,
   NewBMP(BMP),
+  //shown in the error panel, none closes it
+  ShowError(Option<String>),
   PixelClicked(u16, u16),
   ChangePixels(Vec<[u16; 2]>, [u8; 4]),
+  PencilStroke(Vec<[u16; 2]>),
   ChangeSelectedPixel([u8; 4]),
   ToolSelected(ToolsTypes),
   FillBucket([u8; 4]),
@@ -36,12 +72,36 @@ pub enum AppMessage {
   DrawLine([[u16; 2]; 2]),
   DrawRect([[u16; 2]; 2]),
   DrawEllipse([[u16; 2]; 2]),
+  DragShape([[u16; 2]; 2]),
   Blur(u8),
+  EyedropperSize(u8),
   Undo,
+  Redo,
+  SetHistoryBudget(usize),
+  JumpHistory(usize),
+  Autosave,
+  SessionLoaded(Option<Session>),
+  RestoreSession,
+  MacrosLoaded(Vec<Macro>),
+  StartRecording,
+  StopRecording(String),
+  SelectMacro(usize),
+  PlayMacro,
+  DeleteMacro,
+  RunScript(String),
+  ExportScript,
+  PaletteLoaded(Option<(Palette, Vec<[u8; 4]>)>),
+  AddToPalette,
+  RemoveFromPalette(usize),
+  ClearPalette,
+  ImportPalette(Palette),
+  ExtractPalette(usize),
+  ChangeTableColor(u8, [u8; 4]),
+  SetResolution([u32; 2]),
 }
 
 pub struct App {
-  current_bmp: Option<BMP>,
+  document: Option<Document>,
   selected_tool: ToolsTypes,
   tool_color: [u8; 4],
   show_create: bool,
@@ -51,21 +111,72 @@ pub struct App {
   should_redraw: bool,
   only_redraw_coords: PixelRedrawRange,
   pixel_info: Option<PixelInfo>,
-  last_diff: Vec<ImageDiff>,
+  //kept outside the document so it carries over to new images
+  history_budget: usize,
   keybinds: HashMap<String, KeybindActions>,
+  //last session found in indexeddb on startup, until it is restored or something else is opened
+  saved_session: Option<Session>,
+  autosave_pending: bool,
+  _autosave_interval: Interval,
+  macros: Vec<Macro>,
+  selected_macro: Option<usize>,
+  //edits done since recording started, none if not recording
+  recording_macro: Option<Vec<EditorCommand>>,
+  script_errors: Vec<String>,
+  //why the last file couldn't be opened
+  error_message: Option<String>,
+  //last shapes drawn by dragging, shown in the tool inputs
+  drag_endpoints: Option<[[u16; 2]; 2]>,
+  drag_ellipse_args: Option<[[u16; 2]; 2]>,
+  //width and height of the square the eyedropper averages
+  eyedropper_size: u8,
+  palette: Palette,
+  //colors recently drawn with, newest first
+  recent_colors: Vec<[u8; 4]>,
 }
 
 impl Component for App {
   type Message = AppMessage;
   type Properties = Props;
 
-  fn create(_ctx: &Context<Self>) -> Self {
+  fn create(ctx: &Context<Self>) -> Self {
+    //check for a previous session to offer restoring
+    ctx.link().send_future(async {
+      let session = load_session().await;
+      if session.is_err() {
+        log!("Failed to load last session", session.as_ref().err().unwrap());
+      }
+      Self::Message::SessionLoaded(session.unwrap_or(None))
+    });
+    ctx.link().send_future(async {
+      let macros = load_macros().await;
+      if macros.is_err() {
+        log!("Failed to load macros", macros.as_ref().err().unwrap());
+      }
+      Self::Message::MacrosLoaded(macros.unwrap_or_default())
+    });
+    ctx.link().send_future(async {
+      let palette = load_palette().await;
+      if palette.is_err() {
+        log!("Failed to load palette", palette.as_ref().err().unwrap());
+      }
+      Self::Message::PaletteLoaded(palette.unwrap_or_default())
+    });
+    let autosave_interval = {
+      let link = ctx.link().clone();
+      Interval::new(AUTOSAVE_INTERVAL, move || link.send_message(Self::Message::Autosave))
+    };
     let keybinds: HashMap<String, KeybindActions> = HashMap::from([
       ("ctrl+z".to_string(), KeybindActions::Undo),
+      ("ctrl+y".to_string(), KeybindActions::Redo),
+      ("ctrl+shift+z".to_string(), KeybindActions::Redo),
+      ("ctrl+m".to_string(), KeybindActions::PlayMacro),
       ("[".to_string(), KeybindActions::PreviousTool),
       ("]".to_string(), KeybindActions::NextTool),
       ("c".to_string(), KeybindActions::ToolChange(ToolsTypes::ClickFill)),
+      ("p".to_string(), KeybindActions::ToolChange(ToolsTypes::Pencil)),
       ("b".to_string(), KeybindActions::ToolChange(ToolsTypes::BucketFill)),
+      ("d".to_string(), KeybindActions::ToolChange(ToolsTypes::Eyedropper)),
       ("i".to_string(), KeybindActions::ToolChange(ToolsTypes::Invert)),
       ("l".to_string(), KeybindActions::ToolChange(ToolsTypes::Line)),
       ("r".to_string(), KeybindActions::ToolChange(ToolsTypes::Rect)),
@@ -76,14 +187,13 @@ impl Component for App {
       ("m".to_string(), KeybindActions::ToolChange(ToolsTypes::Median)),
       ("t".to_string(), KeybindActions::ToolChange(ToolsTypes::Rotate)),
     ]);
-    Self { current_bmp: None, selected_tool: ToolsTypes::NoneSelected, tool_color: [255, 255, 255, 255], show_create: false, show_load: false, show_pixel_info: false, show_image_actions: false, should_redraw: true, only_redraw_coords: PixelRedrawRange::Empty, pixel_info: None, last_diff: Vec::new(), keybinds }
+    Self { document: None, selected_tool: ToolsTypes::NoneSelected, tool_color: [255, 255, 255, 255], show_create: false, show_load: false, show_pixel_info: false, show_image_actions: false, should_redraw: true, only_redraw_coords: PixelRedrawRange::Empty, pixel_info: None, history_budget: DEFAULT_HISTORY_BUDGET, keybinds, saved_session: None, autosave_pending: false, _autosave_interval: autosave_interval, macros: Vec::new(), selected_macro: None, recording_macro: None, script_errors: Vec::new(), error_message: None, drag_endpoints: None, drag_ellipse_args: None, eyedropper_size: 1, palette: Palette::new("Palette"), recent_colors: Vec::new() }
   }
 
   fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
     self.only_redraw_coords = PixelRedrawRange::Empty;
-    //don't store more than last 10 last diffs
-    if self.last_diff.len() == 10 {
-      self.last_diff.remove(0);
+    if !matches!(msg, Self::Message::Autosave | Self::Message::SessionLoaded(_) | Self::Message::MacrosLoaded(_) | Self::Message::PaletteLoaded(_)) {
+      self.autosave_pending = true;
     }
     let link = ctx.link().clone();
     match msg {
@@ -101,12 +211,20 @@ impl Component for App {
         self.show_create = false;
         self.show_load = false;
         self.show_image_actions = true;
-        self.current_bmp = Some(bmp_inside);
+        self.error_message = None;
+        //history of the previous image does not apply to the new one
+        self.document = Some(Document::new(bmp_inside, self.history_budget));
+        self.saved_session = None;
+        true
+      },
+      Self::Message::ShowError(error) => {
+        self.error_message = error;
+        self.should_redraw = false;
         true
       },
       Self::Message::PixelClicked(x, y) => {
         self.show_pixel_info = true;
-        let pixel_color = self.current_bmp.as_ref().unwrap().clone().get_color_of_pixel(x as usize, y as usize).unwrap();
+        let pixel_color = self.document.as_ref().unwrap().color_of_pixel([x, y]).unwrap();
         self.pixel_info = Some(PixelInfo {
           color: pixel_color,
           coords: [x, y],
@@ -118,6 +236,12 @@ impl Component for App {
           ToolsTypes::BucketFill => {
             link.send_message(Self::Message::FillBucket(self.tool_color));
           },
+          ToolsTypes::Eyedropper => {
+            match self.document.as_ref().unwrap().average_color([x, y], self.eyedropper_size) {
+              Ok(color) => self.tool_color = color,
+              Err(e) => log!(format!("Could not sample color: {}", e)),
+            }
+          },
           _ => {
             //do nothing
           },
@@ -128,34 +252,21 @@ impl Component for App {
       },
       Self::Message::ChangePixels(pixels, color) => {
         //iterate through pixels and change them
-        let mut current_bmp = self.current_bmp.as_ref().unwrap().clone();
-        current_bmp.change_color_of_pixels(pixels, color).unwrap();
-        self.last_diff.push(BMP::diff(&self.current_bmp.as_ref().unwrap(), &current_bmp).unwrap());
-        self.current_bmp = Some(current_bmp);
-        self.should_redraw = true;
-        true
+        self.run_command(EditorCommand::ChangePixels(pixels, color))
+      },
+      Self::Message::PencilStroke(pixels) => {
+        self.run_command(EditorCommand::ChangePixels(pixels, self.tool_color))
       },
       //vec![self.pixel_info.unwrap().coords], 
       Self::Message::ChangeSelectedPixel(color) => {
         //get selected pixel and change the color of it
         let coord = self.pixel_info.as_ref().unwrap().coords;
-        let mut current_bmp = self.current_bmp.as_ref().unwrap().clone();
-        current_bmp.change_color_of_pixel(coord[0], coord[1], color).unwrap();
-        self.last_diff.push(BMP::diff(&self.current_bmp.as_ref().unwrap(), &current_bmp).unwrap());
-        self.current_bmp = Some(current_bmp);
-        self.should_redraw = true;
-        self.only_redraw_coords = PixelRedrawRange::Point(coord);
-        true
+        self.run_command(EditorCommand::ChangePixel(coord, color))
       },
       Self::Message::FillBucket(color) => {
         //get selected pixel and fill paint bucket
         let coord = self.pixel_info.as_ref().unwrap().coords;
-        let mut current_bmp = self.current_bmp.as_ref().unwrap().clone();
-        current_bmp.fill_bucket(color, coord[0] as usize, coord[1] as usize).unwrap();
-        self.last_diff.push(BMP::diff(&self.current_bmp.as_ref().unwrap(), &current_bmp).unwrap());
-        self.current_bmp = Some(current_bmp);
-        self.should_redraw = true;
-        true
+        self.run_command(EditorCommand::FillBucket(coord, color))
       },
       Self::Message::ToolSelected(tool) => {
         self.selected_tool = tool;
@@ -165,86 +276,58 @@ impl Component for App {
       Self::Message::ChangeToolColor(color) => {
         self.tool_color = color;
         self.should_redraw = false;
-        false
+        //the canvas previews (pencil, shapes) use the tool color too
+        true
       },
       Self::Message::Filter(filter_type) => {
         if filter_type == "invert" {
-          let mut current_bmp = self.current_bmp.as_ref().unwrap().clone();
-          current_bmp.invert(None).unwrap();
-          self.last_diff.push(BMP::diff(&self.current_bmp.as_ref().unwrap(), &current_bmp).unwrap());
-          self.current_bmp = Some(current_bmp);
-          self.should_redraw = true;
-          true
+          self.run_command(EditorCommand::Invert)
         } else if filter_type == "greyscale" {
-          let mut current_bmp = self.current_bmp.as_ref().unwrap().clone();
-          current_bmp.greyscale().unwrap();
-          self.last_diff.push(BMP::diff(&self.current_bmp.as_ref().unwrap(), &current_bmp).unwrap());
-          self.current_bmp = Some(current_bmp);
-          self.should_redraw = true;
-          true
+          self.run_command(EditorCommand::Greyscale)
         } else if filter_type == "rotate" {
-          let mut current_bmp = self.current_bmp.as_ref().unwrap().clone();
-          let dib_header = current_bmp.get_dib_header().unwrap();
-          let center_pixel: [u16; 2] = [(f64::from(dib_header.width)/2.0).round() as u16, (f64::from(dib_header.height.abs())/2.0).round() as u16];
-          current_bmp.rotate(90.0, Some(center_pixel)).unwrap();
-          self.last_diff.push(BMP::diff(&self.current_bmp.as_ref().unwrap(), &current_bmp).unwrap());
-          self.current_bmp = Some(current_bmp);
-          self.should_redraw = true;
-          true
+          self.run_command(EditorCommand::Rotate)
         } else {
           false
         }
       },
       Self::Message::DrawLine(endpoints) => {
-        let mut current_bmp = self.current_bmp.as_ref().unwrap().clone();
-        current_bmp.draw_line(self.tool_color, endpoints[0], endpoints[1]).unwrap();
-        self.last_diff.push(BMP::diff(&self.current_bmp.as_ref().unwrap(), &current_bmp).unwrap());
-        self.current_bmp = Some(current_bmp);
-        self.should_redraw = true;
-        self.only_redraw_coords = PixelRedrawRange::Rect(endpoints);
-        true
+        self.run_command(EditorCommand::DrawLine(endpoints, self.tool_color))
       },
       Self::Message::DrawRect(endpoints) => {
-        let mut current_bmp = self.current_bmp.as_ref().unwrap().clone();
-        current_bmp.draw_rectangle(Some(self.tool_color), Some(self.tool_color), endpoints[0], endpoints[1]).unwrap();
-        self.last_diff.push(BMP::diff(&self.current_bmp.as_ref().unwrap(), &current_bmp).unwrap());
-        self.current_bmp = Some(current_bmp);
-        self.should_redraw = true;
-        self.only_redraw_coords = PixelRedrawRange::Rect(endpoints);
-        true
+        self.run_command(EditorCommand::DrawRect(endpoints, self.tool_color))
       },
       Self::Message::DrawEllipse(ellipse_args) => {
-        let mut current_bmp = self.current_bmp.as_ref().unwrap().clone();
-        current_bmp.draw_ellipse(ellipse_args[0], ellipse_args[1][0], ellipse_args[1][1], self.tool_color, Some(self.tool_color), true).unwrap();
-        self.last_diff.push(BMP::diff(&self.current_bmp.as_ref().unwrap(), &current_bmp).unwrap());
-        self.current_bmp = Some(current_bmp);
-        self.should_redraw = true;
-        true
+        self.run_command(EditorCommand::DrawEllipse(ellipse_args, self.tool_color))
       },
-      Self::Message::Blur(blur_radius) => {
-        let mut current_bmp = self.current_bmp.as_ref().unwrap().clone();
+      Self::Message::DragShape(shape_args) => {
         match self.selected_tool {
-          ToolsTypes::Gaussian => {
-            current_bmp.gaussian_blur(blur_radius).unwrap();
-            self.last_diff.push(BMP::diff(&self.current_bmp.as_ref().unwrap(), &current_bmp).unwrap());
-            self.current_bmp = Some(current_bmp);
-            self.should_redraw = true;
-            true
+          ToolsTypes::Line => {
+            self.drag_endpoints = Some(shape_args);
+            link.send_message(Self::Message::DrawLine(shape_args));
           },
-          ToolsTypes::Box => {
-            current_bmp.box_blur(blur_radius).unwrap();
-            self.last_diff.push(BMP::diff(&self.current_bmp.as_ref().unwrap(), &current_bmp).unwrap());
-            self.current_bmp = Some(current_bmp);
-            self.should_redraw = true;
-            true
+          ToolsTypes::Rect => {
+            self.drag_endpoints = Some(shape_args);
+            link.send_message(Self::Message::DrawRect(shape_args));
           },
-          ToolsTypes::Median => {
-            current_bmp.median_filter(blur_radius).unwrap();
-            self.last_diff.push(BMP::diff(&self.current_bmp.as_ref().unwrap(), &current_bmp).unwrap());
-            self.current_bmp = Some(current_bmp);
-            self.should_redraw = true;
-            true
+          ToolsTypes::Ellipse => {
+            self.drag_ellipse_args = Some(shape_args);
+            link.send_message(Self::Message::DrawEllipse(shape_args));
           },
+          _ => {},
+        }
+        self.should_redraw = false;
+        true
+      },
+      Self::Message::EyedropperSize(size) => {
+        self.eyedropper_size = size;
+        self.should_redraw = false;
+        true
+      },
+      Self::Message::Blur(blur_radius) => {
+        match self.selected_tool {
+          ToolsTypes::Gaussian => self.run_command(EditorCommand::GaussianBlur(blur_radius)),
+          ToolsTypes::Box => self.run_command(EditorCommand::BoxBlur(blur_radius)),
+          ToolsTypes::Median => self.run_command(EditorCommand::MedianFilter(blur_radius)),
           _ => {
             //do nothing
             self.should_redraw = false;
@@ -253,31 +336,243 @@ impl Component for App {
         }
       },
       Self::Message::Undo => {
-        if self.last_diff.len() > 0 {
-          let mut current_bmp = self.current_bmp.as_ref().unwrap().clone();
-          let last_diff: &ImageDiff = &self.last_diff.last().unwrap();
-          let dib_header = current_bmp.get_dib_header().unwrap();
-          let file_header = current_bmp.get_header();
-          //write diff to bmp, specifically the first color
-          if last_diff.is_same_size() {
-            //write the difference
-            for diff_num in 0..last_diff.diff.len() {
-              let diff = &last_diff[diff_num];
-              let diff_coord = diff.coord;
-              let diff_color = diff.color1.unwrap();
-              current_bmp.change_color_of_pixel_efficient(diff_coord[0], diff_coord[1], diff_color, &dib_header, &file_header).unwrap();
+        //also works if the image changed size, since those entries are full snapshots.
+        //the shortcuts work on the start screen too, where there is no document yet
+        self.should_redraw = self.document.as_mut().map(|document| document.undo()).unwrap_or(false);
+        self.should_redraw
+      },
+      Self::Message::Redo => {
+        self.should_redraw = self.document.as_mut().map(|document| document.redo()).unwrap_or(false);
+        self.should_redraw
+      },
+      Self::Message::SetHistoryBudget(budget) => {
+        self.history_budget = budget;
+        if let Some(document) = self.document.as_mut() {
+          document.set_history_budget(budget);
+        }
+        self.should_redraw = false;
+        true
+      },
+      Self::Message::JumpHistory(undo_steps) => {
+        self.should_redraw = self.document.as_mut().map(|document| document.jump_to(undo_steps)).unwrap_or(false);
+        self.should_redraw
+      },
+      Self::Message::Autosave => {
+        //only save if something changed since the last save
+        if self.autosave_pending && self.document.is_some() {
+          self.autosave_pending = false;
+          let document = self.document.as_ref().unwrap();
+          let session = Session {
+            bmp_contents: document.bmp().contents.clone(),
+            tool_color: self.tool_color,
+            selected_tool: self.selected_tool,
+            history: document.history().to_bytes(),
+          };
+          spawn_local(async move {
+            if let Err(e) = save_session(session).await {
+              log!("Failed to autosave", e);
             }
-            self.last_diff.pop();
-            self.current_bmp = Some(current_bmp);
-            self.should_redraw = true;
-          } else {
-            //todo: make it work for images of different sizes (extend the image or shrink it and write the diffs)
+          });
+        }
+        false
+      },
+      Self::Message::SessionLoaded(session) => {
+        //don't offer to restore if something was already opened while loading
+        if self.document.is_none() {
+          self.saved_session = session;
+        }
+        true
+      },
+      Self::Message::RestoreSession => {
+        if self.saved_session.is_some() {
+          let session = self.saved_session.take().unwrap();
+          if let Err(e) = validate_bmp(&session.bmp_contents) {
+            self.error_message = Some(format!("The saved session's image is broken: {}", e));
+            //the start buttons are hidden by now, so offer to load a file instead
+            self.show_load = true;
+            return true;
           }
+          let mut restored_bmp = BMP::new(1, 1, None);
+          restored_bmp.contents = session.bmp_contents;
+          let history = History::from_bytes(&session.history).unwrap_or(History::new(self.history_budget));
+          self.history_budget = history.info().budget;
+          self.document = Some(Document::with_history(restored_bmp, history));
+          self.tool_color = session.tool_color;
+          self.selected_tool = session.selected_tool;
+          self.show_create = false;
+          self.show_load = false;
+          self.show_image_actions = true;
+          self.show_pixel_info = false;
+          self.pixel_info = None;
+          self.should_redraw = true;
           true
         } else {
           false
         }
-      }
+      },
+      Self::Message::MacrosLoaded(macros) => {
+        self.selected_macro = if macros.len() > 0 { Some(0) } else { None };
+        self.macros = macros;
+        self.should_redraw = false;
+        true
+      },
+      Self::Message::PaletteLoaded(saved_palette) => {
+        if let Some((palette, recent_colors)) = saved_palette {
+          self.palette = palette;
+          self.recent_colors = recent_colors;
+        }
+        self.should_redraw = false;
+        true
+      },
+      Self::Message::AddToPalette => {
+        self.should_redraw = false;
+        if self.palette.add(self.tool_color) {
+          self.save_palette();
+          true
+        } else {
+          false
+        }
+      },
+      Self::Message::RemoveFromPalette(index) => {
+        self.should_redraw = false;
+        if index < self.palette.colors.len() {
+          self.palette.colors.remove(index);
+          self.save_palette();
+        }
+        true
+      },
+      Self::Message::ClearPalette => {
+        self.palette.colors.clear();
+        self.save_palette();
+        self.should_redraw = false;
+        true
+      },
+      Self::Message::ImportPalette(palette) => {
+        self.palette = palette;
+        self.save_palette();
+        self.should_redraw = false;
+        true
+      },
+      Self::Message::ExtractPalette(max_colors) => {
+        self.should_redraw = false;
+        if let Some(document) = self.document.as_ref() {
+          match Palette::from_image(document.bmp(), "Image", max_colors) {
+            Ok(palette) => {
+              self.palette = palette;
+              self.save_palette();
+            },
+            Err(e) => log!(format!("Could not get colors from image: {}", e)),
+          }
+        }
+        true
+      },
+      Self::Message::ChangeTableColor(index, color) => {
+        self.run_command(EditorCommand::ChangeTableColor(index, color))
+      },
+      Self::Message::SetResolution(pixels_per_meter) => {
+        self.run_command(EditorCommand::SetResolution(pixels_per_meter[0], pixels_per_meter[1]))
+      },
+      Self::Message::StartRecording => {
+        self.recording_macro = Some(Vec::new());
+        self.should_redraw = false;
+        true
+      },
+      Self::Message::StopRecording(name) => {
+        let commands = self.recording_macro.take().unwrap_or_default();
+        self.should_redraw = false;
+        //empty name means the recording was cancelled
+        if name == "" || commands.len() == 0 {
+          return true;
+        }
+        let new_macro = Macro { name, commands };
+        //replace the macro with the same name, if there is one
+        let index = match self.macros.iter().position(|existing| existing.name == new_macro.name) {
+          Some(index) => {
+            self.macros[index] = new_macro.clone();
+            index
+          },
+          None => {
+            self.macros.push(new_macro.clone());
+            self.macros.len()-1
+          },
+        };
+        self.selected_macro = Some(index);
+        spawn_local(async move {
+          if let Err(e) = save_macro(new_macro).await {
+            log!("Failed to save macro", e);
+          }
+        });
+        true
+      },
+      Self::Message::SelectMacro(index) => {
+        self.selected_macro = Some(index);
+        self.should_redraw = false;
+        false
+      },
+      Self::Message::PlayMacro => {
+        if self.document.is_none() || self.selected_macro.is_none() {
+          return false;
+        }
+        let editor_macro = &self.macros[self.selected_macro.unwrap()];
+        //the whole macro is one undo step
+        match self.document.as_mut().unwrap().apply_all(format!("Macro {}", editor_macro.name), &editor_macro.commands) {
+          Ok(()) => {
+            if let Some(recording_macro) = self.recording_macro.as_mut() {
+              recording_macro.extend(editor_macro.commands.iter().cloned());
+            }
+            self.should_redraw = true;
+            true
+          },
+          Err(e) => {
+            //eg a macro recorded on a bigger image
+            self.error_message = Some(format!("Macro \"{}\" failed: {}", editor_macro.name, e));
+            self.should_redraw = false;
+            true
+          },
+        }
+      },
+      Self::Message::DeleteMacro => {
+        if self.selected_macro.is_none() {
+          return false;
+        }
+        let removed = self.macros.remove(self.selected_macro.unwrap());
+        self.selected_macro = if self.macros.len() > 0 { Some(0) } else { None };
+        spawn_local(async move {
+          if let Err(e) = delete_macro(removed.name).await {
+            log!("Failed to delete macro", e);
+          }
+        });
+        self.should_redraw = false;
+        true
+      },
+      Self::Message::RunScript(script) => {
+        self.should_redraw = false;
+        if self.document.is_none() {
+          self.script_errors = vec!["No image is open".to_string()];
+          return true;
+        }
+        //the whole script is one undo step, like a macro
+        match self.document.as_mut().unwrap().apply_script(&script) {
+          Ok(commands) => {
+            self.script_errors = Vec::new();
+            if let Some(recording_macro) = self.recording_macro.as_mut() {
+              recording_macro.extend(commands);
+            }
+            self.should_redraw = true;
+          },
+          Err(errors) => {
+            self.script_errors = errors.iter().map(|error| error.to_string()).collect();
+          },
+        }
+        true
+      },
+      Self::Message::ExportScript => {
+        if let Some(document) = self.document.as_ref() {
+          prompt_download(document.history_script().as_bytes(), "edits.txt");
+        }
+        self.should_redraw = false;
+        false
+      },
     }
   }
 
@@ -292,14 +587,42 @@ impl Component for App {
       }
     });
 
+    let restore_callback = ctx.link().callback(|_: bool| {
+      Self::Message::RestoreSession
+    });
+
     let send_bmp_callback = ctx.link().callback(|new_bmp: BMP| {
       Self::Message::NewBMP(new_bmp)
     });
 
+    let load_error_callback = ctx.link().callback(|error: String| {
+      Self::Message::ShowError(Some(format!("Could not open the file: {}", error)))
+    });
+
+    let close_error_callback = ctx.link().callback(|_: bool| {
+      Self::Message::ShowError(None)
+    });
+
     let send_pixel_click = ctx.link().callback(|coords: [u16; 2]| {
       Self::Message::PixelClicked(coords[0], coords[1])
     });
 
+    let send_stroke = ctx.link().callback(|pixels: Vec<[u16; 2]>| {
+      Self::Message::PencilStroke(pixels)
+    });
+
+    let send_shape = ctx.link().callback(|shape_args: [[u16; 2]; 2]| {
+      Self::Message::DragShape(shape_args)
+    });
+
+    let drag_tool = match self.selected_tool {
+      ToolsTypes::Pencil => DragTool::Pencil,
+      ToolsTypes::Line => DragTool::Shape(DragShape::Line),
+      ToolsTypes::Rect => DragTool::Shape(DragShape::Rect),
+      ToolsTypes::Ellipse => DragTool::Shape(DragShape::Ellipse),
+      _ => DragTool::None,
+    };
+
     //pixel change
     let change_pixel_callback = ctx.link().callback(|new_color: [u8; 4]| {
       Self::Message::ChangeSelectedPixel(new_color)
@@ -314,10 +637,85 @@ impl Component for App {
       Self::Message::Undo
     });
 
+    let redo_callback = ctx.link().callback(|_: bool| {
+      Self::Message::Redo
+    });
+
+    let history_budget_callback = ctx.link().callback(|budget: usize| {
+      Self::Message::SetHistoryBudget(budget)
+    });
+
+    let history_jump_callback = ctx.link().callback(|undo_steps: usize| {
+      Self::Message::JumpHistory(undo_steps)
+    });
+
+    let record_macro_callback = ctx.link().callback(|_: bool| {
+      Self::Message::StartRecording
+    });
+
+    let save_macro_callback = ctx.link().callback(|name: String| {
+      Self::Message::StopRecording(name)
+    });
+
+    let select_macro_callback = ctx.link().callback(|index: usize| {
+      Self::Message::SelectMacro(index)
+    });
+
+    let play_macro_callback = ctx.link().callback(|_: bool| {
+      Self::Message::PlayMacro
+    });
+
+    let delete_macro_callback = ctx.link().callback(|_: bool| {
+      Self::Message::DeleteMacro
+    });
+
+    let run_script_callback = ctx.link().callback(|script: String| {
+      Self::Message::RunScript(script)
+    });
+
+    let export_script_callback = ctx.link().callback(|_: bool| {
+      Self::Message::ExportScript
+    });
+
     let change_tool_color_callback = ctx.link().callback(|color: [u8; 4]| {
       Self::Message::ChangeToolColor(color)
     });
 
+    let select_color_callback = change_tool_color_callback.clone();
+
+    let add_color_callback = ctx.link().callback(|_: bool| {
+      Self::Message::AddToPalette
+    });
+
+    let remove_color_callback = ctx.link().callback(|index: usize| {
+      Self::Message::RemoveFromPalette(index)
+    });
+
+    let clear_palette_callback = ctx.link().callback(|_: bool| {
+      Self::Message::ClearPalette
+    });
+
+    let import_palette_callback = ctx.link().callback(|palette: Palette| {
+      Self::Message::ImportPalette(palette)
+    });
+
+    let extract_palette_callback = ctx.link().callback(|max_colors: usize| {
+      Self::Message::ExtractPalette(max_colors)
+    });
+
+    let color_table = self.document.as_ref().and_then(|document| document.color_table());
+
+    let change_entry_callback = ctx.link().callback(|(index, color): (u8, [u8; 4])| {
+      Self::Message::ChangeTableColor(index, color)
+    });
+
+    let header_info = self.document.as_ref().and_then(|document| header_fields(document.bmp()).ok());
+    let current_resolution = self.document.as_ref().and_then(|document| resolution(document.bmp()));
+
+    let resolution_callback = ctx.link().callback(|pixels_per_meter: [u32; 2]| {
+      Self::Message::SetResolution(pixels_per_meter)
+    });
+
     let filter_callback = ctx.link().callback(|filter_type: String| {
       Self::Message::Filter(filter_type)
     });
@@ -338,17 +736,28 @@ impl Component for App {
       Self::Message::Blur(blur_radius)
     });
 
-    let current_bmp = &self.to_owned().current_bmp;
+    let eyedropper_size_callback = ctx.link().callback(|size: u8| {
+      Self::Message::EyedropperSize(size)
+    });
+
+    let current_bmp = self.document.as_ref().map(|document| document.bmp().clone());
+    let history_info = self.document.as_ref().map(|document| document.history_info()).unwrap_or(History::new(self.history_budget).info());
   
     html! {
       <div id="main">
-        <Start {create_load_callback} />
+        <Start {create_load_callback} show_restore={self.saved_session.is_some()} {restore_callback} />
         <Create send_bmp_callback={send_bmp_callback.clone()} show={self.show_create} />
-        <Load send_bmp_callback={send_bmp_callback} show={self.show_load} />
-        <ImageActions selected_tool={self.selected_tool} current_bmp={current_bmp.clone()} show={self.show_image_actions} {tool_change_callback} {undo_callback} keybinds={self.keybinds.clone()} />
-        <Tools selected_tool={self.selected_tool} {change_tool_color_callback} {filter_callback} {line_callback} {rect_callback} {ellipse_callback} {blur_callback} tool_color={self.tool_color} show={self.show_image_actions} keybinds={self.keybinds.clone()} />
-        <Pixels {send_pixel_click} current_bmp={current_bmp.clone()} should_redraw={self.should_redraw} only_redraw_coords={self.only_redraw_coords} />
+        <Load send_bmp_callback={send_bmp_callback} show={self.show_load} error_callback={load_error_callback} />
+        <ErrorPanel error={self.error_message.clone()} close_callback={close_error_callback} />
+        <ImageActions selected_tool={self.selected_tool} current_bmp={current_bmp.clone()} show={self.show_image_actions} {tool_change_callback} {undo_callback} {redo_callback} history_info={history_info.clone()} {history_budget_callback} macro_names={self.macros.iter().map(|editor_macro| editor_macro.name.clone()).collect::<Vec<String>>()} selected_macro={self.selected_macro} recording_macro={self.recording_macro.is_some()} {record_macro_callback} {save_macro_callback} {select_macro_callback} {play_macro_callback} {delete_macro_callback} keybinds={self.keybinds.clone()} />
+        <Tools selected_tool={self.selected_tool} {change_tool_color_callback} {filter_callback} {line_callback} {rect_callback} {ellipse_callback} {blur_callback} {eyedropper_size_callback} eyedropper_size={self.eyedropper_size} tool_color={self.tool_color} drag_endpoints={self.drag_endpoints} drag_ellipse_args={self.drag_ellipse_args} show={self.show_image_actions} keybinds={self.keybinds.clone()} />
+        <PalettePanel show={self.show_image_actions} palette={self.palette.clone()} recent_colors={self.recent_colors.clone()} tool_color={self.tool_color} select_color_callback={select_color_callback.clone()} {add_color_callback} {remove_color_callback} {clear_palette_callback} {import_palette_callback} {extract_palette_callback} has_image={self.document.is_some()} />
+        <ColorTablePanel {color_table} tool_color={self.tool_color} {select_color_callback} {change_entry_callback} />
+        <HeaderPanel header_fields={header_info} resolution={current_resolution} {resolution_callback} />
+        <Pixels {send_pixel_click} {current_bmp} should_redraw={self.should_redraw} only_redraw_coords={self.only_redraw_coords} {drag_tool} tool_color={self.tool_color} {send_stroke} {send_shape} />
         <PixelActions pixel_info={self.pixel_info.clone()} show={self.show_pixel_info} {change_pixel_callback} />
+        <HistoryPanel {history_info} show={self.show_image_actions} {history_jump_callback} />
+        <ScriptPanel show={self.show_image_actions} script_errors={self.script_errors.clone()} {run_script_callback} {export_script_callback} />
         <div id={"bottom-links"}>
           <a href="https://github.com/jetstream0/BMP-Editor" target="_blank">{ "Editor Github" }</a>
           <span class="link-divider">{ "-" }</span>
@@ -361,6 +770,64 @@ impl Component for App {
   }
 }
 
+impl App {
+  //do an edit on the document, and redraw the part of the image it changed
+  fn run_command(&mut self, command: EditorCommand) -> bool {
+    let redraw_range = redraw_range(&command);
+    match self.document.as_mut().unwrap().apply(command.clone()) {
+      Ok(()) => {
+        //drawing again with the same color doesn't change the recent colors, so nothing needs saving
+        if let Some(color) = command.color() {
+          if add_recent(&mut self.recent_colors, color) {
+            self.save_palette();
+          }
+        }
+        if let Some(recording_macro) = self.recording_macro.as_mut() {
+          recording_macro.push(command);
+        }
+        self.should_redraw = true;
+        self.only_redraw_coords = redraw_range;
+        true
+      },
+      Err(e) => {
+        log!(format!("Edit failed: {}", e));
+        self.should_redraw = false;
+        false
+      },
+    }
+  }
+
+  fn save_palette(&self) {
+    let palette = self.palette.clone();
+    let recent_colors = self.recent_colors.clone();
+    spawn_local(async move {
+      if let Err(e) = save_palette(palette, recent_colors).await {
+        log!("Failed to save palette", e);
+      }
+    });
+  }
+}
+
+fn redraw_range(command: &EditorCommand) -> PixelRedrawRange {
+  match command {
+    EditorCommand::ChangePixel(coord, _) => PixelRedrawRange::Point(*coord),
+    EditorCommand::ChangePixels(pixels, _) => {
+      match pixels_bounds(pixels) {
+        Some(bounds) => PixelRedrawRange::Rect(bounds),
+        None => PixelRedrawRange::Empty,
+      }
+    },
+    EditorCommand::DrawLine(endpoints, _) | EditorCommand::DrawRect(endpoints, _) => {
+      //endpoints can be in any order, but the redraw rect goes from the top left to the bottom right
+      PixelRedrawRange::Rect([
+        [endpoints[0][0].min(endpoints[1][0]), endpoints[0][1].min(endpoints[1][1])],
+        [endpoints[0][0].max(endpoints[1][0]), endpoints[0][1].max(endpoints[1][1])],
+      ])
+    },
+    _ => PixelRedrawRange::Empty,
+  }
+}
+
 fn main() {
   yew::Renderer::<App>::new().render();
 }
@@ -0,0 +1,322 @@
+use std::collections::HashMap;
+
+use bmp_rust::bmp::{BMP, ErrorKind};
+
+use crate::color::parse_hex;
+use crate::render::{image_area, PixelReader};
+
+//saved colors for the tool color, and reading and writing them as gimp (.gpl), jasc (.pal) or hex list (.hex) palette files
+//palettes can also be made from the colors in an image
+
+//how many recently used colors are kept
+pub const RECENT_COLORS: usize = 16;
+
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct Palette {
+  pub name: String,
+  pub colors: Vec<[u8; 4]>,
+}
+
+impl Palette {
+  pub fn new(name: &str) -> Self {
+    Self { name: name.to_string(), colors: Vec::new() }
+  }
+
+  //returns false if the color is already in the palette
+  pub fn add(&mut self, color: [u8; 4]) -> bool {
+    if self.colors.contains(&color) {
+      return false;
+    }
+    self.colors.push(color);
+    true
+  }
+
+  //the image's distinct colors, most used first. if there are more than max_colors, they are reduced with median cut
+  pub fn from_image(bmp: &BMP, name: &str, max_colors: usize) -> Result<Self, ErrorKind> {
+    let counts = color_counts(bmp)?;
+    let colors = if counts.len() <= max_colors {
+      counts.into_iter().map(|(color, _)| color).collect()
+    } else {
+      median_cut(&counts, max_colors).into_iter().map(|(color, _)| color).collect()
+    };
+    Ok(Self { name: name.to_string(), colors })
+  }
+
+  //figure out the format from the file extension, or the first line if the extension is unknown
+  pub fn from_file(file_name: &str, text: &str) -> Result<Self, String> {
+    let name = file_name.rsplit_once('.').map(|(name, _)| name).unwrap_or(file_name);
+    let extension = file_name.rsplit_once('.').map(|(_, extension)| extension.to_lowercase()).unwrap_or_default();
+    let first_line = text.lines().next().unwrap_or("").trim();
+    if extension == "gpl" || first_line == "GIMP Palette" {
+      Self::from_gpl(text, name)
+    } else if extension == "pal" || first_line == "JASC-PAL" {
+      Self::from_pal(text, name)
+    } else {
+      Self::from_hex_list(text, name)
+    }
+  }
+
+  //the name in the file is used if there is one
+  pub fn from_gpl(text: &str, default_name: &str) -> Result<Self, String> {
+    let mut lines = text.lines().enumerate();
+    match lines.next() {
+      Some((_, line)) if line.trim() == "GIMP Palette" => {},
+      _ => return Err("Not a GIMP palette: first line should be \"GIMP Palette\"".to_string()),
+    }
+    let mut palette = Self::new(default_name);
+    for (line_index, line) in lines {
+      let line = line.trim();
+      if line.is_empty() || line.starts_with('#') || line.starts_with("Columns:") {
+        continue;
+      }
+      if let Some(name) = line.strip_prefix("Name:") {
+        palette.name = name.trim().to_string();
+        continue;
+      }
+      //"r g b name", the name is optional
+      let values: Vec<&str> = line.split_whitespace().take(3).collect();
+      palette.colors.push(parse_rgb(&values).map_err(|e| format!("Line {}: {}", line_index+1, e))?);
+    }
+    Ok(palette)
+  }
+
+  pub fn to_gpl(&self) -> String {
+    let mut text = format!("GIMP Palette\nName: {}\nColumns: 8\n#\n", self.name);
+    for color in &self.colors {
+      text += &format!("{:>3} {:>3} {:>3}\t{}\n", color[0], color[1], color[2], hex_text(*color));
+    }
+    text
+  }
+
+  pub fn from_pal(text: &str, name: &str) -> Result<Self, String> {
+    let lines: Vec<&str> = text.lines().map(|line| line.trim()).collect();
+    if lines.len() < 3 || lines[0] != "JASC-PAL" {
+      return Err("Not a JASC palette: first line should be \"JASC-PAL\"".to_string());
+    }
+    let count = lines[2].parse::<usize>().map_err(|_| format!("Line 3: \"{}\" is not the number of colors", lines[2]))?;
+    let mut palette = Self::new(name);
+    for (line_index, line) in lines.iter().enumerate().skip(3) {
+      if line.is_empty() {
+        continue;
+      }
+      let values: Vec<&str> = line.split_whitespace().collect();
+      palette.colors.push(parse_rgb(&values).map_err(|e| format!("Line {}: {}", line_index+1, e))?);
+    }
+    if palette.colors.len() != count {
+      return Err(format!("Expected {} colors, found {}", count, palette.colors.len()));
+    }
+    Ok(palette)
+  }
+
+  pub fn to_pal(&self) -> String {
+    let mut text = format!("JASC-PAL\n0100\n{}\n", self.colors.len());
+    for color in &self.colors {
+      text += &format!("{} {} {}\n", color[0], color[1], color[2]);
+    }
+    text
+  }
+
+  //one hex color per line, like lospec's .hex files
+  pub fn from_hex_list(text: &str, name: &str) -> Result<Self, String> {
+    let mut palette = Self::new(name);
+    for (line_index, line) in text.lines().enumerate() {
+      let line = line.trim();
+      if line.is_empty() || line.starts_with(';') || line.starts_with("//") {
+        continue;
+      }
+      palette.colors.push(parse_hex(line).map_err(|e| format!("Line {}: {}", line_index+1, e))?);
+    }
+    Ok(palette)
+  }
+
+  pub fn to_hex_list(&self) -> String {
+    self.colors.iter().map(|color| hex_text(*color).trim_start_matches('#').to_string()+"\n").collect()
+  }
+
+  //4 bytes per color, for storing
+  pub fn colors_to_bytes(colors: &[[u8; 4]]) -> Vec<u8> {
+    colors.iter().flatten().copied().collect()
+  }
+
+  pub fn colors_from_bytes(bytes: &[u8]) -> Vec<[u8; 4]> {
+    bytes.chunks_exact(4).map(|chunk| [chunk[0], chunk[1], chunk[2], chunk[3]]).collect()
+  }
+}
+
+//the most recent color goes first, and a color is only in the list once.
+//returns false if it was already first, so the list didn't change and doesn't need saving
+pub fn add_recent(recent: &mut Vec<[u8; 4]>, color: [u8; 4]) -> bool {
+  if recent.first() == Some(&color) {
+    return false;
+  }
+  recent.retain(|recent_color| *recent_color != color);
+  recent.insert(0, color);
+  recent.truncate(RECENT_COLORS);
+  true
+}
+
+//every distinct color in the image and how many pixels have it, most used first
+pub fn color_counts(bmp: &BMP) -> Result<Vec<([u8; 4], u32)>, ErrorKind> {
+  let mut counts: HashMap<[u8; 4], u32> = HashMap::new();
+  if let Some(area) = image_area(bmp)? {
+    let reader = PixelReader::new(bmp)?;
+    for y in 0..=area[1][1] {
+      for x in 0..=area[1][0] {
+        *counts.entry(reader.color(x, y)?).or_insert(0) += 1;
+      }
+    }
+  }
+  Ok(sorted_by_count(counts.into_iter().collect()))
+}
+
+//most used first, same counts are ordered by color so the result doesn't depend on hashmap order
+fn sorted_by_count(mut counts: Vec<([u8; 4], u32)>) -> Vec<([u8; 4], u32)> {
+  counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+  counts
+}
+
+//reduce colors (with how many pixels have them) to at most max_colors. the colors are split into boxes,
+//always cutting the box with the widest channel at the pixel-weighted median of that channel,
+//then every box becomes the weighted average of its colors. returns the new colors with their pixel counts, most used first
+pub fn median_cut(counts: &[([u8; 4], u32)], max_colors: usize) -> Vec<([u8; 4], u32)> {
+  if counts.len() <= max_colors {
+    return sorted_by_count(counts.to_vec());
+  }
+  let mut boxes: Vec<Vec<([u8; 4], u32)>> = vec![counts.to_vec()];
+  while boxes.len() < max_colors {
+    //the widest box that can still be split
+    let widest = boxes.iter().enumerate().filter(|(_, color_box)| color_box.len() > 1).map(|(index, color_box)| {
+      let (channel, range) = widest_channel(color_box);
+      (index, channel, range)
+    }).max_by_key(|(_, _, range)| *range);
+    let (index, channel, _) = match widest {
+      Some(widest) => widest,
+      None => break,
+    };
+    let mut color_box = boxes.swap_remove(index);
+    color_box.sort_by_key(|(color, _)| color[channel]);
+    let total: u32 = color_box.iter().map(|(_, count)| count).sum();
+    let mut so_far: u32 = 0;
+    //first color past the median, but leave at least one color on each side
+    let mut split = color_box.len()-1;
+    for (i, (_, count)) in color_box.iter().enumerate() {
+      so_far += count;
+      if so_far*2 >= total {
+        split = (i+1).min(color_box.len()-1);
+        break;
+      }
+    }
+    let second_half = color_box.split_off(split);
+    boxes.push(color_box);
+    boxes.push(second_half);
+  }
+  sorted_by_count(boxes.iter().map(|color_box| average_of(color_box)).collect())
+}
+
+//the channel (r, g, b or a) with the biggest difference between min and max, and that difference
+fn widest_channel(colors: &[([u8; 4], u32)]) -> (usize, u8) {
+  (0..4).map(|channel| {
+    let min = colors.iter().map(|(color, _)| color[channel]).min().unwrap_or(0);
+    let max = colors.iter().map(|(color, _)| color[channel]).max().unwrap_or(0);
+    (channel, max-min)
+  }).max_by_key(|(channel, range)| (*range, 3-*channel)).unwrap()
+}
+
+fn average_of(colors: &[([u8; 4], u32)]) -> ([u8; 4], u32) {
+  let total: u32 = colors.iter().map(|(_, count)| count).sum();
+  let mut sums: [u64; 4] = [0; 4];
+  for (color, count) in colors {
+    for i in 0..4 {
+      sums[i] += u64::from(color[i])*u64::from(*count);
+    }
+  }
+  (sums.map(|sum| (sum as f64/f64::from(total.max(1))).round() as u8), total)
+}
+
+//#rrggbb, or #rrggbbaa if it is transparent. the palette formats other than hex lists can't keep alpha
+fn hex_text(color: [u8; 4]) -> String {
+  if color[3] == 255 {
+    format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2])
+  } else {
+    format!("#{:02x}{:02x}{:02x}{:02x}", color[0], color[1], color[2], color[3])
+  }
+}
+
+fn parse_rgb(values: &[&str]) -> Result<[u8; 4], String> {
+  if values.len() != 3 {
+    return Err(format!("Expected 3 numbers (r g b), found {}", values.len()));
+  }
+  let mut color: [u8; 4] = [0, 0, 0, 255];
+  for i in 0..3 {
+    color[i] = values[i].parse::<u8>().map_err(|_| format!("\"{}\" is not a number from 0 to 255", values[i]))?;
+  }
+  Ok(color)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn test_palette() -> Palette {
+    Palette { name: "Test".to_string(), colors: vec![[255, 0, 0, 255], [0, 128, 255, 255], [10, 20, 30, 255]] }
+  }
+
+  #[test]
+  fn gpl_roundtrip() {
+    let palette = test_palette();
+    assert_eq!(Palette::from_gpl(&palette.to_gpl(), "other").unwrap(), palette);
+    let gimp = "GIMP Palette\nName: Two\nColumns: 2\n# comment\n  0   0   0\tBlack\n255 255 255\n";
+    assert_eq!(Palette::from_gpl(gimp, "x").unwrap(), Palette { name: "Two".to_string(), colors: vec![[0, 0, 0, 255], [255, 255, 255, 255]] });
+    assert!(Palette::from_gpl("GIMP Palette\n1 2\n", "x").unwrap_err().starts_with("Line 2"));
+  }
+
+  #[test]
+  fn pal_roundtrip() {
+    let palette = test_palette();
+    assert_eq!(Palette::from_pal(&palette.to_pal(), "Test").unwrap(), palette);
+    assert!(Palette::from_pal("JASC-PAL\n0100\n2\n1 2 3\n", "x").is_err());
+  }
+
+  #[test]
+  fn hex_list_and_detection() {
+    let palette = Palette::from_file("pico.hex", "ff0000\n0080ff\n\n0a141e\n").unwrap();
+    assert_eq!(palette, Palette { name: "pico".to_string(), ..test_palette() });
+    assert_eq!(palette.to_hex_list(), "ff0000\n0080ff\n0a141e\n");
+    assert_eq!(Palette::from_file("unknown.txt", &test_palette().to_pal()).unwrap().colors, test_palette().colors);
+    assert!(Palette::from_file("bad.hex", "ff0000\nnope\n").unwrap_err().starts_with("Line 2"));
+  }
+
+  #[test]
+  fn extract_from_image() {
+    let mut bmp = BMP::new(2, 3, Some([255, 255, 255, 255]));
+    bmp.change_color_of_pixel(0, 0, [255, 0, 0, 255]).unwrap();
+    bmp.change_color_of_pixel(1, 0, [255, 0, 0, 255]).unwrap();
+    bmp.change_color_of_pixel(2, 1, [0, 0, 250, 255]).unwrap();
+    assert_eq!(color_counts(&bmp).unwrap(), vec![([255, 255, 255, 255], 3), ([255, 0, 0, 255], 2), ([0, 0, 250, 255], 1)]);
+    assert_eq!(Palette::from_image(&bmp, "Image", 16).unwrap().colors, vec![[255, 255, 255, 255], [255, 0, 0, 255], [0, 0, 250, 255]]);
+    //cut along red at the median, so the blue pixel is averaged in with the white ones
+    assert_eq!(Palette::from_image(&bmp, "Image", 2).unwrap().colors, vec![[191, 191, 254, 255], [255, 0, 0, 255]]);
+  }
+
+  #[test]
+  fn median_cut_keeps_separate_colors() {
+    let counts = vec![([0, 0, 0, 255], 10), ([2, 2, 2, 255], 10), ([250, 0, 0, 255], 5), ([252, 0, 0, 255], 5)];
+    let reduced = median_cut(&counts, 2);
+    assert_eq!(reduced, vec![([1, 1, 1, 255], 20), ([251, 0, 0, 255], 10)]);
+    assert_eq!(median_cut(&counts, 1), vec![([84, 1, 1, 255], 30)]);
+  }
+
+  #[test]
+  fn recent_colors() {
+    let mut recent: Vec<[u8; 4]> = Vec::new();
+    for i in 0..20 {
+      add_recent(&mut recent, [i, 0, 0, 255]);
+    }
+    assert!(add_recent(&mut recent, [10, 0, 0, 255]));
+    assert!(!add_recent(&mut recent, [10, 0, 0, 255]));
+    assert_eq!(recent.len(), RECENT_COLORS);
+    assert_eq!(recent[0], [10, 0, 0, 255]);
+    assert_eq!(recent[1], [19, 0, 0, 255]);
+    assert_eq!(recent.iter().filter(|color| **color == [10, 0, 0, 255]).count(), 1);
+  }
+}
@@ -0,0 +1,216 @@
+use yew::prelude::*;
+use web_sys::{HtmlInputElement, HtmlSelectElement};
+use gloo::file::File;
+use gloo::file::callbacks::FileReader;
+
+use bmp_editor::palette::Palette;
+
+use crate::download::download_bytes;
+
+//swatches of saved and recently used colors, click one to make it the tool color
+//palettes can be imported and exported as gimp (.gpl), jasc (.pal) or hex list (.hex) files, or made from the image's colors
+
+//default for how many colors to take from the image
+const DEFAULT_EXTRACT_COLORS: usize = 32;
+
+#[derive(PartialEq, Properties)]
+pub struct PalettePanelProps {
+  pub show: bool,
+  pub palette: Palette,
+  pub recent_colors: Vec<[u8; 4]>,
+  pub tool_color: [u8; 4],
+  pub select_color_callback: Callback<[u8; 4]>,
+  pub add_color_callback: Callback<bool>,
+  pub remove_color_callback: Callback<usize>,
+  pub clear_palette_callback: Callback<bool>,
+  pub import_palette_callback: Callback<Palette>,
+  pub extract_palette_callback: Callback<usize>,
+  pub has_image: bool,
+}
+
+pub enum PalettePanelMessage {
+  Show,
+  Hide,
+  SwatchClicked(usize, bool),
+  Import(File),
+  Imported(String, String),
+  Export,
+  Extract,
+}
+
+pub struct PalettePanel {
+  display: String,
+  reader: Option<FileReader>,
+  import_error: Option<String>,
+  export_format_ref: NodeRef,
+  extract_colors_ref: NodeRef,
+}
+
+fn swatch_style(color: [u8; 4]) -> String {
+  format!("background-color: rgba({}, {}, {}, {});", color[0], color[1], color[2], f64::from(color[3])/255.0)
+}
+
+fn swatch_title(color: [u8; 4]) -> String {
+  format!("({}, {}, {}, {})", color[0], color[1], color[2], color[3])
+}
+
+impl Component for PalettePanel {
+  type Message = PalettePanelMessage;
+  type Properties = PalettePanelProps;
+
+  fn create(_ctx: &Context<Self>) -> Self {
+    Self { display: "none".to_string(), reader: None, import_error: None, export_format_ref: NodeRef::default(), extract_colors_ref: NodeRef::default() }
+  }
+
+  fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+    match msg {
+      Self::Message::Show => {
+        self.display = "block".to_string();
+        true
+      },
+      Self::Message::Hide => {
+        self.display = "none".to_string();
+        true
+      },
+      Self::Message::SwatchClicked(index, remove) => {
+        //shift+click removes the swatch instead
+        if remove {
+          ctx.props().remove_color_callback.emit(index);
+        } else if let Some(color) = ctx.props().palette.colors.get(index) {
+          ctx.props().select_color_callback.emit(*color);
+        }
+        false
+      },
+      Self::Message::Import(file) => {
+        let link = ctx.link().clone();
+        let file_name = file.name();
+        self.reader = Some(gloo::file::callbacks::read_as_text(&file, move |res| {
+          if let Ok(text) = res {
+            link.send_message(Self::Message::Imported(file_name, text));
+          }
+        }));
+        false
+      },
+      Self::Message::Imported(file_name, text) => {
+        self.reader = None;
+        match Palette::from_file(&file_name, &text) {
+          Ok(palette) => {
+            self.import_error = None;
+            ctx.props().import_palette_callback.emit(palette);
+          },
+          Err(e) => {
+            self.import_error = Some(format!("Could not import {}: {}", file_name, e));
+          },
+        }
+        true
+      },
+      Self::Message::Export => {
+        let export_format: HtmlSelectElement = self.export_format_ref.cast().unwrap();
+        let palette = &ctx.props().palette;
+        let text = match export_format.value().as_str() {
+          "pal" => palette.to_pal(),
+          "hex" => palette.to_hex_list(),
+          _ => palette.to_gpl(),
+        };
+        download_bytes(text.as_bytes(), &format!("{}.{}", palette.name, export_format.value()));
+        false
+      },
+      Self::Message::Extract => {
+        let extract_colors: HtmlInputElement = self.extract_colors_ref.cast().unwrap();
+        match extract_colors.value().parse::<usize>() {
+          Ok(max_colors) if max_colors > 0 => {
+            self.import_error = None;
+            ctx.props().extract_palette_callback.emit(max_colors);
+          },
+          _ => {
+            self.import_error = Some(format!("\"{}\" is not a valid number of colors", extract_colors.value()));
+          },
+        }
+        true
+      },
+    }
+  }
+
+  fn view(&self, ctx: &Context<Self>) -> Html {
+    let link = ctx.link().clone();
+
+    if self.display == "none".to_string() && ctx.props().show {
+      link.send_message(Self::Message::Show);
+    } else if self.display == "block".to_string() && !ctx.props().show {
+      link.send_message(Self::Message::Hide);
+    }
+
+    let tool_color = ctx.props().tool_color;
+
+    let swatches = ctx.props().palette.colors.iter().enumerate().map(|(index, color)| {
+      let onclick = ctx.link().callback(move |e: MouseEvent| Self::Message::SwatchClicked(index, e.shift_key()));
+      let class = if *color == tool_color { "swatch selected-swatch" } else { "swatch" };
+      html! {
+        <button {class} style={swatch_style(*color)} title={swatch_title(*color)} {onclick}></button>
+      }
+    }).collect::<Html>();
+
+    let recent_swatches = ctx.props().recent_colors.iter().map(|color| {
+      let color = *color;
+      let select_color_callback = ctx.props().select_color_callback.clone();
+      let onclick = Callback::from(move |_: MouseEvent| select_color_callback.emit(color));
+      html! {
+        <button class="swatch" style={swatch_style(color)} title={swatch_title(color)} {onclick}></button>
+      }
+    }).collect::<Html>();
+
+    let add_color_callback = ctx.props().add_color_callback.clone();
+    let add = Callback::from(move |_: MouseEvent| add_color_callback.emit(true));
+
+    let clear_palette_callback = ctx.props().clear_palette_callback.clone();
+    let clear = Callback::from(move |_: MouseEvent| clear_palette_callback.emit(true));
+
+    let export = ctx.link().callback(|_| Self::Message::Export);
+
+    let extract = ctx.link().callback(|_| Self::Message::Extract);
+
+    let import = ctx.link().batch_callback(|e: Event| {
+      let file_input: HtmlInputElement = e.target_unchecked_into();
+      let file = file_input.files().unwrap().item(0).map(File::from);
+      //so the same file can be imported again
+      file_input.set_value("");
+      file.map(Self::Message::Import)
+    });
+
+    html! {
+      <div id={"palette-panel"} style={"display: ".to_string()+&self.display}>
+        <h3>{ format!("Palette: {}", ctx.props().palette.name) }</h3>
+        <div class="swatches">
+          { swatches }
+        </div>
+        <button onclick={add}>{ "Add Tool Color" }</button>
+        <button onclick={clear}>{ "Clear" }</button>
+        <p class="palette-tip">{ "Click a swatch to use it, shift+click to remove it." }</p>
+        if ctx.props().recent_colors.len() > 0 {
+          <h4>{ "Recent" }</h4>
+          <div class="swatches">
+            { recent_swatches }
+          </div>
+        }
+        <label for="palette-upload">{ "Import: " }</label>
+        <input id="palette-upload" type="file" name="palette-upload" accept=".gpl,.pal,.hex,.txt" multiple={false} onchange={import}/>
+        <br/>
+        if ctx.props().has_image {
+          <label for="extract-colors">{ "Up to " }</label>
+          <input id="extract-colors" ref={&self.extract_colors_ref} type="number" min="1" max="256" value={DEFAULT_EXTRACT_COLORS.to_string()}/>
+          <button onclick={extract}>{ "Colors From Image" }</button>
+          <br/>
+        }
+        <select ref={&self.export_format_ref}>
+          <option value="gpl">{ "GIMP (.gpl)" }</option>
+          <option value="pal">{ "JASC (.pal)" }</option>
+          <option value="hex">{ "Hex list (.hex)" }</option>
+        </select>
+        <button onclick={export}>{ "Export" }</button>
+        if let Some(import_error) = &self.import_error {
+          <p class="palette-error">{ import_error }</p>
+        }
+      </div>
+    }
+  }
+}
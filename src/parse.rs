@@ -0,0 +1,70 @@
+use std::str::FromStr;
+
+//parsing the coordinates and colors typed into inputs (and edit scripts), eg "(0, 20)" or "(255, 0, 0, 255)"
+//the brackets and spaces are optional
+
+pub fn parse_vec_input(input: &str) -> String {
+  input.replace("(", "").replace(")", "").replace(" ", "")
+}
+
+fn parse_numbers<T: FromStr>(input: &str, max: &str) -> Result<Vec<T>, String> {
+  let parsed = parse_vec_input(input);
+  if parsed.is_empty() {
+    return Err("nothing entered".to_string());
+  }
+  parsed.split(",").map(|value| {
+    value.parse::<T>().map_err(|_| format!("\"{}\" is not a number from 0 to {}", value, max))
+  }).collect()
+}
+
+//alpha can be left out, and defaults to 255
+pub fn input_to_color(input: &str) -> Result<[u8; 4], String> {
+  let color_vec = parse_numbers::<u8>(input, "255").map_err(|e| format!("Invalid color \"{}\": {}", input, e))?;
+  match color_vec.len() {
+    3 => Ok([color_vec[0], color_vec[1], color_vec[2], 255]),
+    4 => Ok([color_vec[0], color_vec[1], color_vec[2], color_vec[3]]),
+    _ => Err(format!("Invalid color \"{}\": should be (r, g, b, a)", input)),
+  }
+}
+
+pub fn input_to_coords(input: &str) -> Result<[u16; 2], String> {
+  let coords_vec = parse_numbers::<u16>(input, "65535").map_err(|e| format!("Invalid coordinates \"{}\": {}", input, e))?;
+  if coords_vec.len() != 2 {
+    return Err(format!("Invalid coordinates \"{}\": should be (x, y)", input));
+  }
+  Ok([coords_vec[0], coords_vec[1]])
+}
+
+pub fn input_to_u16(input: &str) -> Result<u16, String> {
+  input.trim().parse::<u16>().map_err(|_| format!("\"{}\" is not a number from 0 to 65535", input.trim()))
+}
+
+pub fn input_to_u32(input: &str) -> Result<u32, String> {
+  input.trim().parse::<u32>().map_err(|_| format!("\"{}\" is not a number from 0 to 4294967295", input.trim()))
+}
+
+pub fn input_to_u8(input: &str) -> Result<u8, String> {
+  input.trim().parse::<u8>().map_err(|_| format!("\"{}\" is not a number from 0 to 255", input.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn colors() {
+    assert_eq!(input_to_color("(255, 0, 10, 128)").unwrap(), [255, 0, 10, 128]);
+    assert_eq!(input_to_color("1,2,3").unwrap(), [1, 2, 3, 255]);
+    assert!(input_to_color("(256, 0, 0, 0)").is_err());
+    assert!(input_to_color("(1, 2)").is_err());
+    assert!(input_to_color("").is_err());
+  }
+
+  #[test]
+  fn coords() {
+    assert_eq!(input_to_coords("(10, 20)").unwrap(), [10, 20]);
+    assert_eq!(input_to_coords("0,0").unwrap(), [0, 0]);
+    assert!(input_to_coords("(1, 2, 3)").is_err());
+    assert!(input_to_coords("(-1, 2)").is_err());
+  }
+}
@@ -1,9 +1,10 @@
 use yew::prelude::*;
-use web_sys::HtmlInputElement;
 //use gloo_console::log;
 
+use crate::color_picker::ColorPicker;
+
 //info and actions of a specific pixel
-//coords, color, in future: option to change color
+//coords, color, and changing the color
 
 #[derive(PartialEq, Clone)]
 pub struct PixelInfo {
@@ -21,11 +22,14 @@ pub struct PixelActionsProps {
 pub enum PixelActionsMessage {
   Show,
   Hide,
-  ChangePixel([u8; 4]),
+  PickColor([u8; 4]),
+  ChangePixel,
 }
 
 pub struct PixelActions {
   display: String,
+  //color chosen in the picker, the pixel is only changed when the button is clicked
+  picked_color: Option<[u8; 4]>,
 }
 
 impl Component for PixelActions {
@@ -33,7 +37,15 @@ impl Component for PixelActions {
   type Properties = PixelActionsProps;
 
   fn create(_ctx: &Context<Self>) -> Self {
-    Self { display: "none".to_string() }
+    Self { display: "none".to_string(), picked_color: None }
+  }
+
+  fn changed(&mut self, ctx: &Context<Self>, old_props: &Self::Properties) -> bool {
+    //another pixel was clicked
+    if ctx.props().pixel_info != old_props.pixel_info {
+      self.picked_color = None;
+    }
+    true
   }
 
   fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
@@ -46,8 +58,14 @@ impl Component for PixelActions {
         self.display = "none".to_string();
         true
       },
-      Self::Message::ChangePixel(color) => {
-        let _ = ctx.props().change_pixel_callback.emit(color);
+      Self::Message::PickColor(color) => {
+        self.picked_color = Some(color);
+        false
+      },
+      Self::Message::ChangePixel => {
+        if let Some(picked_color) = self.picked_color {
+          let _ = ctx.props().change_pixel_callback.emit(picked_color);
+        }
         false
       }
     }
@@ -62,35 +80,22 @@ impl Component for PixelActions {
       link.send_message(Self::Message::Hide);
     }
 
-    fn input_to_color(input: String) -> Vec<u8> {
-      return input.replace("(", "").replace(")", "").replace(" ", "").split(",").map(|value| value.parse::<u8>().unwrap()).collect();
-    }
-
     if ctx.props().pixel_info.is_some() {
-      let pc_input_ref = NodeRef::default();
       let pixel_info = ctx.props().pixel_info.as_ref().unwrap();
       let pixel_click_coords = pixel_info.coords.clone();
       let pixel_click_color = pixel_info.color.clone();
-      let color_text = format!("({}, {}, {}, {})", pixel_click_color[0], pixel_click_color[1], pixel_click_color[2], pixel_click_color[3]);
       let coords_text = format!("Coords: ({}, {})", pixel_click_coords[0], pixel_click_coords[1]);
   
-      let new_pixel = {
-        let pc_input_ref2 = pc_input_ref.clone();
-        ctx.link().callback(move |_| {
-          //get new pixel color
-          let pc_input: HtmlInputElement = pc_input_ref2.cast().unwrap();
-          let new_color_vec: Vec<u8> = input_to_color(pc_input.value());
-          let new_color: [u8; 4] = [new_color_vec[0], new_color_vec[1], new_color_vec[2], new_color_vec[3]];
-          Self::Message::ChangePixel(new_color)
-        })
-      };
-      
+      let pick_color = ctx.link().callback(Self::Message::PickColor);
+
+      let new_pixel = ctx.link().callback(|_| Self::Message::ChangePixel);
+
       html! {
         <div style={"display: ".to_string()+&self.display}>
           <span>{coords_text}</span>
           <br/>
-          <label for="pixel-color">{"Color: "}</label>
-          <input name="pixel-color" value={color_text} ref={pc_input_ref}/>
+          <span>{"Color: "}</span>
+          <ColorPicker color={self.picked_color.unwrap_or(pixel_click_color)} on_change={pick_color}/>
           <button onclick={new_pixel}>{ "Change" }</button>
         </div>
       }
@@ -1,8 +1,15 @@
 use yew::prelude::*;
 use bmp_rust::bmp::BMP;
 use gloo_console::log;
-use wasm_bindgen::{JsCast, JsValue};
-use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, Path2d};
+use wasm_bindgen::{Clamped, JsCast, JsValue};
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, ImageData, Path2d, WheelEvent};
+use gloo_events::{EventListener, EventListenerOptions};
+use gloo_utils::document;
+
+use bmp_editor::shapes::{DragShape, shape_args, shape_rows};
+use bmp_editor::stroke::Stroke;
+use bmp_editor::render::{area_size, image_area, rgba_area};
+use bmp_editor::viewport::Viewport;
 
 #[derive(PartialEq, Clone, Copy)]
 pub enum PixelRedrawRange {
@@ -24,25 +31,72 @@ impl PixelRedrawRange {
   }
 }
 
+//tools that are used by dragging on the canvas
+#[derive(PartialEq, Clone, Copy)]
+pub enum DragTool {
+  None,
+  Pencil,
+  Shape(DragShape),
+}
+
 #[derive(PartialEq, Properties)]
 pub struct PixelsProps {
   pub current_bmp: Option<BMP>,
   pub send_pixel_click: Callback<[u16; 2]>,
   pub should_redraw: bool,
   pub only_redraw_coords: PixelRedrawRange,
+  pub drag_tool: DragTool,
+  pub tool_color: [u8; 4],
+  pub send_stroke: Callback<Vec<[u16; 2]>>,
+  //endpoints, or center and lengths for ellipses
+  pub send_shape: Callback<[[u16; 2]; 2]>,
 }
 
 pub enum PixelsMessage {
   Show,
   SendClick([u16; 2]),
   PostRenderUpdate,
+  //multiply the zoom, around a point on the canvas
+  Zoom(f64, [f64; 2]),
+  ZoomFit,
+  ZoomActual,
+  PanStart([i32; 2]),
+  PanMove([i32; 2]),
+  PanEnd,
+  StrokeStart([u16; 2]),
+  StrokeMove([u16; 2]),
+  StrokeEnd,
+  ShapeStart([u16; 2]),
+  ShapeMove([u16; 2]),
+  ShapeEnd,
 }
 
+const CANVAS_SIZE: f64 = 650.0;
+//how much one scroll of the mouse wheel (or one press of the zoom buttons) zooms
+const ZOOM_STEP: f64 = 1.25;
+
 pub struct Pixels {
   display: String,
   canvas_ref: NodeRef,
   canvas_ref_top: NodeRef,
   should_update: bool,
+  viewport: Option<Viewport>,
+  //last mouse position while panning
+  pan_last: Option<[i32; 2]>,
+  //a shift+drag pan shouldn't also count as a click
+  panned: bool,
+  //zooming or panning needs the whole canvas redrawn, even if the image didn't change
+  redraw_all: bool,
+  wheel_listener: Option<EventListener>,
+  //image at 1:1, drawn onto the canvas at the current zoom
+  offscreen: Option<HtmlCanvasElement>,
+  //rendered is also called after our own updates (zooming, panning), when should_redraw is still left over from the last props
+  props_changed: bool,
+  //pencil stroke being drawn, shown on the top canvas until the mouse is let go
+  stroke: Option<Stroke>,
+  //where the shape drag started, and the shape as it is now
+  shape_start: Option<[u16; 2]>,
+  shape_preview: Option<[[u16; 2]; 2]>,
 }
 
 impl Component for Pixels {
@@ -50,7 +104,20 @@ impl Component for Pixels {
   type Properties = PixelsProps;
 
   fn create(_ctx: &Context<Self>) -> Self {
-    Self { display: "none".to_string(), canvas_ref: NodeRef::default(), canvas_ref_top: NodeRef::default(), should_update: true }
+    Self { display: "none".to_string(), canvas_ref: NodeRef::default(), canvas_ref_top: NodeRef::default(), should_update: true, viewport: None, pan_last: None, panned: false, redraw_all: false, wheel_listener: None, offscreen: None, props_changed: true, stroke: None, shape_start: None, shape_preview: None }
+  }
+
+  fn changed(&mut self, ctx: &Context<Self>, _old_props: &Self::Properties) -> bool {
+    self.props_changed = true;
+    //new image, or the image changed size (eg rotated), so fit it to the canvas again
+    if let Some(current_bmp) = ctx.props().current_bmp.as_ref() {
+      let image_size = get_image_size(current_bmp);
+      if self.viewport.map(|viewport| viewport.image_size) != Some(image_size) {
+        self.viewport = Some(Viewport::fit(image_size, [CANVAS_SIZE, CANVAS_SIZE]));
+        self.redraw_all = true;
+      }
+    }
+    true
   }
 
   fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
@@ -60,6 +127,10 @@ impl Component for Pixels {
         true
       },
       Self::Message::SendClick(coords) => {
+        if self.panned {
+          self.panned = false;
+          return false;
+        }
         let _ = ctx.props().send_pixel_click.emit(coords);
         false
       },
@@ -67,99 +138,193 @@ impl Component for Pixels {
         self.should_update = false;
         true
       },
+      Self::Message::Zoom(zoom_factor, point) => {
+        if let Some(viewport) = self.viewport.as_mut() {
+          viewport.zoom_at(viewport.zoom*zoom_factor, point);
+          self.redraw_all = true;
+        }
+        true
+      },
+      Self::Message::ZoomFit => {
+        if let Some(viewport) = self.viewport.as_mut() {
+          *viewport = Viewport::fit(viewport.image_size, viewport.canvas_size);
+          self.redraw_all = true;
+        }
+        true
+      },
+      Self::Message::ZoomActual => {
+        if let Some(viewport) = self.viewport.as_mut() {
+          viewport.actual_size();
+          self.redraw_all = true;
+        }
+        true
+      },
+      Self::Message::PanStart(position) => {
+        self.pan_last = Some(position);
+        self.panned = false;
+        false
+      },
+      Self::Message::PanMove(position) => {
+        if let (Some(pan_last), Some(viewport)) = (self.pan_last, self.viewport.as_mut()) {
+          viewport.pan([f64::from(position[0]-pan_last[0]), f64::from(position[1]-pan_last[1])]);
+          self.pan_last = Some(position);
+          self.panned = true;
+          self.redraw_all = true;
+          true
+        } else {
+          false
+        }
+      },
+      Self::Message::PanEnd => {
+        self.pan_last = None;
+        false
+      },
+      Self::Message::StrokeStart(pixel) => {
+        self.clear_top();
+        self.draw_preview_rows(ctx, &[[pixel[1], pixel[0], pixel[0]]]);
+        self.stroke = Some(Stroke::new(pixel));
+        false
+      },
+      Self::Message::StrokeMove(pixel) => {
+        if let Some(stroke) = self.stroke.as_mut() {
+          let added = stroke.add_pixel(pixel);
+          self.draw_preview_rows(ctx, &added.iter().map(|pixel| [pixel[1], pixel[0], pixel[0]]).collect::<Vec<[u16; 3]>>());
+        }
+        false
+      },
+      Self::Message::StrokeEnd => {
+        if let Some(stroke) = self.stroke.take() {
+          self.clear_top();
+          //the whole stroke is one edit, so one undo
+          ctx.props().send_stroke.emit(stroke.pixels);
+        }
+        false
+      },
+      Self::Message::ShapeStart(pixel) => {
+        self.clear_top();
+        self.shape_start = Some(pixel);
+        self.shape_preview = None;
+        false
+      },
+      Self::Message::ShapeMove(pixel) => {
+        if let (Some(shape_start), Some(viewport), DragTool::Shape(shape)) = (self.shape_start, self.viewport, ctx.props().drag_tool) {
+          self.clear_top();
+          self.shape_preview = shape_args(shape, shape_start, pixel, viewport.image_size);
+          if let Some(shape_preview) = self.shape_preview {
+            self.draw_preview_rows(ctx, &shape_rows(shape, shape_preview));
+          }
+        }
+        false
+      },
+      Self::Message::ShapeEnd => {
+        if self.shape_start.take().is_some() {
+          self.clear_top();
+          if let Some(shape_preview) = self.shape_preview.take() {
+            ctx.props().send_shape.emit(shape_preview);
+          }
+        }
+        false
+      },
     }
   }
 
   fn view(&self, ctx: &Context<Self>) -> Html {
     let link = ctx.link().clone();
 
-    if ctx.props().current_bmp.is_some() {
+    if ctx.props().current_bmp.is_some() && self.viewport.is_some() {
       if self.display == "none".to_string() {
         link.send_message(Self::Message::Show);
       }
 
-      //let pixels_all: Vec<Path2d> = self.pixels_all.clone();
-
-      let current_bmp = ctx.props().current_bmp.clone().unwrap();
+      let viewport = self.viewport.unwrap();
       let canvas_ref_top = self.canvas_ref_top.clone();
       let canvas_ref_top2 = self.canvas_ref_top.clone();
+      let panning = self.pan_last.is_some();
+      let stroking = self.stroke.is_some();
+      let shaping = self.shape_start.is_some();
+      let drag_tool = ctx.props().drag_tool;
 
-      let pixel_mousemove_callback = Callback::from(move |e: MouseEvent| {
+      let pixel_mousemove = ctx.link().batch_callback(move |e: MouseEvent| {
+        if panning {
+          return Some(Self::Message::PanMove([e.client_x(), e.client_y()]));
+        }
+        if stroking {
+          return viewport.pixel_at([f64::from(e.offset_x()), f64::from(e.offset_y())]).map(Self::Message::StrokeMove);
+        } else if shaping {
+          return viewport.pixel_at([f64::from(e.offset_x()), f64::from(e.offset_y())]).map(Self::Message::ShapeMove);
+        }
+        //highlight the pixel under the mouse
         let canvas: Option<HtmlCanvasElement> = canvas_ref_top.cast();
         if canvas.is_some() {
-          let dib_header = &current_bmp.get_dib_header().unwrap();
-          let width: u32 = dib_header.width;
-          let height: u32 = dib_header.height.unsigned_abs();
-          let pixel_wh;
-          if height > width {
-            pixel_wh = (650.0/f64::from(height)).floor() as i32;
-          } else {
-            pixel_wh = (650.0/f64::from(width)).floor() as i32;
-          }
-          let x = (f64::from(e.offset_x())/f64::from(pixel_wh)).floor() as i32;
-          let y = (f64::from(e.offset_y())/f64::from(pixel_wh)).floor() as i32;
-          if x as u32 >= width || y as u32 >= height {
-            return;
-          }
           let canvas: HtmlCanvasElement = canvas.unwrap();
           let context: CanvasRenderingContext2d = canvas.get_context("2d").unwrap().unwrap().dyn_into().unwrap();
-          context.clear_rect(0.0, 0.0, 650.0, 650.0);
+          context.clear_rect(0.0, 0.0, CANVAS_SIZE, CANVAS_SIZE);
+          let pixel = viewport.pixel_at([f64::from(e.offset_x()), f64::from(e.offset_y())]);
+          if pixel.is_none() {
+            return None;
+          }
+          let pixel_rect = viewport.pixel_rect(pixel.unwrap());
           let pixel_path = Path2d::new().unwrap();
-          let top_left = [pixel_wh*x, pixel_wh*y];
-          let bottom_right = [pixel_wh*(x+1), pixel_wh*(y+1)];
-          pixel_path.move_to(f64::from(top_left[0]), f64::from(top_left[1]));
-          pixel_path.line_to(f64::from(bottom_right[0]), f64::from(top_left[1]));
-          pixel_path.line_to(f64::from(bottom_right[0]), f64::from(bottom_right[1]));
-          pixel_path.line_to(f64::from(top_left[0]), f64::from(bottom_right[1]));
-          pixel_path.line_to(f64::from(top_left[0]), f64::from(top_left[1]));
+          pixel_path.rect(pixel_rect[0], pixel_rect[1], pixel_rect[2].max(1.0), pixel_rect[3].max(1.0));
           context.set_fill_style(&JsValue::from("rgba(255, 255, 230, 0.5)".to_string()));
           context.fill_with_path_2d(&pixel_path);
         }
+        None
       });
 
-      let pixel_mousemove = {
-        pixel_mousemove_callback.clone()
-      };
-
-      let pixel_mouseout_callback = Callback::from(move |_e: MouseEvent| {
+      let pixel_mouseout = ctx.link().batch_callback(move |_e: MouseEvent| {
         let canvas: Option<HtmlCanvasElement> = canvas_ref_top2.cast();
-        if canvas.is_some() {
+        if canvas.is_some() && !stroking && !shaping {
           let canvas: HtmlCanvasElement = canvas.unwrap();
           let context: CanvasRenderingContext2d = canvas.get_context("2d").unwrap().unwrap().dyn_into().unwrap();
-          context.clear_rect(0.0, 0.0, 650.0, 650.0);
+          context.clear_rect(0.0, 0.0, CANVAS_SIZE, CANVAS_SIZE);
         }
+        //leaving the canvas finishes the stroke or shape
+        vec![Self::Message::PanEnd, Self::Message::StrokeEnd, Self::Message::ShapeEnd]
       });
 
-      let pixel_mouseout = {
-        pixel_mouseout_callback.clone()
-      };
-
-      let pixel_click = {
-        let current_bmp2 = ctx.props().current_bmp.clone().unwrap();
-        ctx.link().batch_callback(move |e: MouseEvent| {
-          let dib_header = &current_bmp2.get_dib_header().unwrap();
-          let width: u32 = dib_header.width;
-          let height: u32 = dib_header.height.unsigned_abs();
-          let pixel_wh;
-          if height > width {
-            pixel_wh = (650.0/f64::from(height)).floor() as i32;
-          } else {
-            pixel_wh = (650.0/f64::from(width)).floor() as i32;
-          }
-          let x = (f64::from(e.offset_x())/f64::from(pixel_wh)).floor() as u16;
-          let y = (f64::from(e.offset_y())/f64::from(pixel_wh)).floor() as u16;
-          if u32::from(x) >= width || u32::from(y) >= height {
-            return None;
+      //middle mouse button, or shift and left mouse button, drags the image around
+      let pixel_mousedown = ctx.link().batch_callback(move |e: MouseEvent| {
+        if e.button() == 1 || (e.button() == 0 && e.shift_key()) {
+          e.prevent_default();
+          Some(Self::Message::PanStart([e.client_x(), e.client_y()]))
+        } else if e.button() == 0 {
+          let pixel = viewport.pixel_at([f64::from(e.offset_x()), f64::from(e.offset_y())]);
+          match drag_tool {
+            DragTool::Pencil => pixel.map(Self::Message::StrokeStart),
+            DragTool::Shape(_) => pixel.map(Self::Message::ShapeStart),
+            DragTool::None => None,
           }
-          return Some(Self::Message::SendClick([x, y]));
-        })
-      };
+        } else {
+          None
+        }
+      });
+
+      let pixel_mouseup = ctx.link().batch_callback(|_e: MouseEvent| vec![Self::Message::PanEnd, Self::Message::StrokeEnd, Self::Message::ShapeEnd]);
+
+      let pixel_click = ctx.link().batch_callback(move |e: MouseEvent| {
+        let pixel = viewport.pixel_at([f64::from(e.offset_x()), f64::from(e.offset_y())]);
+        pixel.map(Self::Message::SendClick)
+      });
+
+      let zoom_in = ctx.link().callback(|_| Self::Message::Zoom(ZOOM_STEP, [CANVAS_SIZE/2.0, CANVAS_SIZE/2.0]));
+      let zoom_out = ctx.link().callback(|_| Self::Message::Zoom(1.0/ZOOM_STEP, [CANVAS_SIZE/2.0, CANVAS_SIZE/2.0]));
+      let zoom_fit = ctx.link().callback(|_| Self::Message::ZoomFit);
+      let zoom_actual = ctx.link().callback(|_| Self::Message::ZoomActual);
 
       html! {
         <div style={"display: ".to_string()+&self.display}>
+          <div id="zoom-controls">
+            <button onclick={zoom_out}>{ "-" }</button>
+            <span class="zoom-level">{ format!("{}%", (viewport.zoom*100.0).round()) }</span>
+            <button onclick={zoom_in}>{ "+" }</button>
+            <button onclick={zoom_fit}>{ "Fit" }</button>
+            <button onclick={zoom_actual}>{ "1:1" }</button>
+            <span class="zoom-tip">{ "Scroll to zoom, drag with the middle mouse button or shift to move around" }</span>
+          </div>
           <canvas id="pixels" width="650" height="650" ref={self.canvas_ref.clone()}></canvas>
           <br/>
-          <canvas id="top-pixels" width="650" height="650" ref={self.canvas_ref_top.clone()} onclick={pixel_click} onmousemove={pixel_mousemove} onmouseout={pixel_mouseout}></canvas>
+          <canvas id="top-pixels" width="650" height="650" ref={self.canvas_ref_top.clone()} onclick={pixel_click} onmousemove={pixel_mousemove} onmouseout={pixel_mouseout} onmousedown={pixel_mousedown} onmouseup={pixel_mouseup}></canvas>
         </div>
       }
     } else {
@@ -171,70 +336,143 @@ impl Component for Pixels {
 
   fn rendered(&mut self, ctx: &Context<Self>, _first_render: bool) {
     log!("Rendering");
+    //yew's wheel listeners are passive, so they can't stop the page from scrolling. add one that can
+    if self.wheel_listener.is_none() {
+      if let Some(canvas_top) = self.canvas_ref_top.cast::<HtmlCanvasElement>() {
+        let link = ctx.link().clone();
+        self.wheel_listener = Some(EventListener::new_with_options(&canvas_top, "wheel", EventListenerOptions::enable_prevent_default(), move |e| {
+          let wheel_event: &WheelEvent = e.dyn_ref().unwrap();
+          wheel_event.prevent_default();
+          let zoom_factor = if wheel_event.delta_y() < 0.0 { ZOOM_STEP } else { 1.0/ZOOM_STEP };
+          link.send_message(Self::Message::Zoom(zoom_factor, [f64::from(wheel_event.offset_x()), f64::from(wheel_event.offset_y())]));
+        }));
+      }
+    }
     let canvas: Option<HtmlCanvasElement> = self.canvas_ref.cast();
-    if canvas.is_some() && ctx.props().should_redraw {
+    let props_changed = self.props_changed;
+    self.props_changed = false;
+    if canvas.is_none() || self.viewport.is_none() || ctx.props().current_bmp.is_none() {
+      return;
+    }
+    let unwrapped_bmp = ctx.props().current_bmp.as_ref().unwrap();
+    let viewport = self.viewport.unwrap();
+    //the image is kept at 1:1 on an offscreen canvas, which only changes when the image does.
+    //zooming and panning just draw (part of) it onto the real canvas again, scaled
+    let image_size = get_image_size(unwrapped_bmp);
+    let offscreen_stale = self.offscreen.as_ref().map(|offscreen| [offscreen.width(), offscreen.height()] != image_size).unwrap_or(true);
+    //none means the whole canvas
+    let mut redraw_area: Option<[[u16; 2]; 2]> = None;
+    let mut should_draw = self.redraw_all;
+    if offscreen_stale || (props_changed && ctx.props().should_redraw) {
       log!("Redrawing");
-      let canvas: HtmlCanvasElement = canvas.unwrap();
-      let context: CanvasRenderingContext2d = canvas.get_context("2d").unwrap().unwrap().dyn_into().unwrap();
-      //clear canvas
-      let unwrapped_bmp = ctx.props().current_bmp.as_ref().unwrap();
-      let dib_header = unwrapped_bmp.get_dib_header().unwrap();
-      let width: u32 = dib_header.width;
-      let height: u32 = dib_header.height.unsigned_abs();
-      let pixel_wh;
-      if height > width {
-        pixel_wh = (650.0/f64::from(height)).floor() as u32;
+      let offscreen = self.offscreen.get_or_insert_with(|| document().create_element("canvas").unwrap().unchecked_into());
+      let image_area = image_area(unwrapped_bmp).unwrap();
+      let only_redraw_coords = if offscreen_stale {
+        offscreen.set_width(image_size[0]);
+        offscreen.set_height(image_size[1]);
+        PixelRedrawRange::Empty
       } else {
-        pixel_wh = (650.0/f64::from(width)).floor() as u32;
-      }
-      let pixel_data = unwrapped_bmp.get_pixel_data().unwrap();
-      let only_redraw_coords = ctx.props().only_redraw_coords;
-      if only_redraw_coords.is_empty() {
-        context.clear_rect(0.0, 0.0, 650.0, 650.0);
-        for y in 0..height {
-          for x in 0..width {
-            //ctx.props().only_redraw_coords.
-            let top_left = [pixel_wh*x, pixel_wh*y];
-            /*let pixel_path = Path2d::new().unwrap();
-            let bottom_right = [pixel_wh*(x+1), pixel_wh*(y+1)];
-            pixel_path.move_to(top_left[0] as f64, top_left[1] as f64);
-            pixel_path.line_to(bottom_right[0] as f64, top_left[1] as f64);
-            pixel_path.line_to(bottom_right[0] as f64, bottom_right[1] as f64);
-            pixel_path.line_to(top_left[0] as f64, bottom_right[1] as f64);
-            pixel_path.line_to(top_left[0] as f64, top_left[1] as f64);*/
-            let color = unwrapped_bmp.get_color_of_pixel_efficient(x as usize, y as usize, &dib_header, &pixel_data).unwrap();
-            context.set_fill_style(&JsValue::from(format!("rgba({},{},{},{})", color[0], color[1], color[2], (f64::from(color[3])/255.0))));
-            context.fill_rect(top_left[0].into(), top_left[1].into(), pixel_wh.into(), pixel_wh.into());
-            //context.fill_with_path_2d(&pixel_path);
-          }
-        }
-      } else {
-        if let PixelRedrawRange::Rect(rect_coords) = only_redraw_coords {
+        ctx.props().only_redraw_coords
+      };
+      let changed_area = match only_redraw_coords {
+        PixelRedrawRange::Empty => image_area,
+        PixelRedrawRange::Rect(rect_coords) => {
           log!("redrawing only rect portion");
-          //only change the pixels of the rectangular area
-          for y_add in 0..(rect_coords[1][1]-rect_coords[0][1]+1) {
-            for x_add in 0..(rect_coords[1][0]-rect_coords[0][0]+1) {
-              let coord: [u16; 2] = [rect_coords[0][0]+x_add, rect_coords[0][1]+y_add];
-              let top_left = [pixel_wh*u32::from(coord[0]), pixel_wh*u32::from(coord[1])];
-              let color = unwrapped_bmp.get_color_of_pixel_efficient(coord[0] as usize, coord[1] as usize, &dib_header, &pixel_data).unwrap();
-              context.set_fill_style(&JsValue::from(format!("rgba({},{},{},{})", color[0], color[1], color[2], (f64::from(color[3])/255.0))));
-              context.fill_rect(top_left[0].into(), top_left[1].into(), pixel_wh.into(), pixel_wh.into());
-            }
-          }
-        } else if let PixelRedrawRange::Point(coord) = only_redraw_coords {
+          image_area.and_then(|image_area| intersect_areas(image_area, rect_coords))
+        },
+        PixelRedrawRange::Point(coord) => {
           log!("redrawing only point portion");
-          let top_left = [pixel_wh*u32::from(coord[0]), pixel_wh*u32::from(coord[1])];
-          let color = unwrapped_bmp.get_color_of_pixel_efficient(coord[0] as usize, coord[1] as usize, &dib_header, &pixel_data).unwrap();
-          context.set_fill_style(&JsValue::from(format!("rgba({},{},{},{})", color[0], color[1], color[2], (f64::from(color[3])/255.0))));
-          context.fill_rect(top_left[0].into(), top_left[1].into(), pixel_wh.into(), pixel_wh.into());
+          image_area.and_then(|image_area| intersect_areas(image_area, [coord, coord]))
+        },
+      };
+      if let Some(changed_area) = changed_area {
+        let offscreen_context: CanvasRenderingContext2d = offscreen.get_context("2d").unwrap().unwrap().dyn_into().unwrap();
+        let rgba = rgba_area(unwrapped_bmp, changed_area).unwrap();
+        let changed_size = area_size(changed_area);
+        let image_data = ImageData::new_with_u8_clamped_array_and_sh(Clamped(&rgba), changed_size[0], changed_size[1]).unwrap();
+        offscreen_context.put_image_data(&image_data, f64::from(changed_area[0][0]), f64::from(changed_area[0][1])).unwrap();
+        if !self.redraw_all && !only_redraw_coords.is_empty() {
+          redraw_area = Some(changed_area);
         }
+        should_draw = true;
+      } else if only_redraw_coords.is_empty() {
+        should_draw = true;
       }
-      //we can't use first render, have to set our own attribute
-      //force an update so self.pixels_all is accurate
-      if self.should_update {
-        let link = ctx.link();
-        link.send_message(Self::Message::PostRenderUpdate);
+    }
+    if !should_draw {
+      return;
+    }
+    self.redraw_all = false;
+    let canvas: HtmlCanvasElement = canvas.unwrap();
+    let context: CanvasRenderingContext2d = canvas.get_context("2d").unwrap().unwrap().dyn_into().unwrap();
+    //otherwise zoomed in pixels are blurry
+    context.set_image_smoothing_enabled(false);
+    let visible_pixels = viewport.visible_pixels();
+    let blit_area = match redraw_area {
+      Some(redraw_area) => visible_pixels.and_then(|visible| intersect_areas(visible, redraw_area)),
+      None => {
+        context.clear_rect(0.0, 0.0, CANVAS_SIZE, CANVAS_SIZE);
+        visible_pixels
+      },
+    };
+    if let Some(blit_area) = blit_area {
+      let offscreen = self.offscreen.as_ref().unwrap();
+      let blit_size = area_size(blit_area);
+      //same rounding as the hover highlight, so they line up
+      let top_left = viewport.pixel_rect(blit_area[0]);
+      let bottom_right = viewport.pixel_rect(blit_area[1]);
+      let dest = [top_left[0], top_left[1], bottom_right[0]+bottom_right[2]-top_left[0], bottom_right[1]+bottom_right[3]-top_left[1]];
+      if redraw_area.is_some() {
+        //transparent pixels would be drawn on top of the old ones otherwise
+        context.clear_rect(dest[0], dest[1], dest[2], dest[3]);
       }
+      context.draw_image_with_html_canvas_element_and_sw_and_sh_and_dx_and_dy_and_dw_and_dh(offscreen, f64::from(blit_area[0][0]), f64::from(blit_area[0][1]), f64::from(blit_size[0]), f64::from(blit_size[1]), dest[0], dest[1], dest[2], dest[3]).unwrap();
+    }
+    //we can't use first render, have to set our own attribute
+    //force an update so self.pixels_all is accurate
+    if self.should_update {
+      let link = ctx.link();
+      link.send_message(Self::Message::PostRenderUpdate);
     }
   }
 }
+
+impl Pixels {
+  fn clear_top(&self) {
+    if let Some(canvas) = self.canvas_ref_top.cast::<HtmlCanvasElement>() {
+      let context: CanvasRenderingContext2d = canvas.get_context("2d").unwrap().unwrap().dyn_into().unwrap();
+      context.clear_rect(0.0, 0.0, CANVAS_SIZE, CANVAS_SIZE);
+    }
+  }
+
+  //preview of the stroke or shape, the real image is only changed when the mouse is let go
+  //rows are [y, first x, last x]
+  fn draw_preview_rows(&self, ctx: &Context<Self>, rows: &[[u16; 3]]) {
+    let canvas: Option<HtmlCanvasElement> = self.canvas_ref_top.cast();
+    if let (Some(canvas), Some(viewport)) = (canvas, self.viewport) {
+      let color = ctx.props().tool_color;
+      let context: CanvasRenderingContext2d = canvas.get_context("2d").unwrap().unwrap().dyn_into().unwrap();
+      context.set_fill_style(&JsValue::from(format!("rgba({},{},{},{})", color[0], color[1], color[2], (f64::from(color[3])/255.0))));
+      for row in rows {
+        let first_rect = viewport.pixel_rect([row[1], row[0]]);
+        let last_rect = viewport.pixel_rect([row[2], row[0]]);
+        context.fill_rect(first_rect[0], first_rect[1], (last_rect[0]+last_rect[2]-first_rect[0]).max(1.0), first_rect[3].max(1.0));
+      }
+    }
+  }
+}
+
+fn get_image_size(bmp: &BMP) -> [u32; 2] {
+  let dib_header = bmp.get_dib_header().unwrap();
+  [dib_header.width, dib_header.height.unsigned_abs()]
+}
+
+//overlap of two areas (top left and bottom right, inclusive), none if they don't overlap
+fn intersect_areas(area1: [[u16; 2]; 2], area2: [[u16; 2]; 2]) -> Option<[[u16; 2]; 2]> {
+  let top_left = [area1[0][0].max(area2[0][0]), area1[0][1].max(area2[0][1])];
+  let bottom_right = [area1[1][0].min(area2[1][0]), area1[1][1].min(area2[1][1])];
+  if top_left[0] > bottom_right[0] || top_left[1] > bottom_right[1] {
+    return None;
+  }
+  Some([top_left, bottom_right])
+}
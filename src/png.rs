@@ -0,0 +1,275 @@
+use bmp_rust::bmp::{BMP, ErrorKind};
+
+use crate::render::{image_area, rgba_area};
+
+//png export, written here instead of adding an image crate. pixels are 8 bit rgba, every row gets the
+//filter that makes it smallest, and the data is compressed with deflate using the fixed huffman codes
+
+const SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+//RGBA
+const COLOR_TYPE: u8 = 6;
+//deflate can look back 32kb
+const WINDOW_SIZE: usize = 32768;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+//how many earlier positions with the same 3 bytes are checked for the longest match
+const MAX_CHAIN: usize = 64;
+
+const LENGTH_BASES: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA_BITS: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASES: [u16; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+const DISTANCE_EXTRA_BITS: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+
+pub fn bmp_to_png(bmp: &BMP) -> Result<Vec<u8>, ErrorKind> {
+  let area = image_area(bmp)?.ok_or(ErrorKind::InvalidParameter("image has no pixels".to_string()))?;
+  let rgba = rgba_area(bmp, area)?;
+  Ok(encode_png(u32::from(area[1][0])+1, u32::from(area[1][1])+1, &rgba))
+}
+
+//rgba is rows from the top, 4 bytes per pixel (like canvas ImageData)
+pub fn encode_png(width: u32, height: u32, rgba: &[u8]) -> Vec<u8> {
+  let mut png: Vec<u8> = SIGNATURE.to_vec();
+  let mut header: Vec<u8> = Vec::with_capacity(13);
+  header.extend(width.to_be_bytes());
+  header.extend(height.to_be_bytes());
+  //bit depth, color type, compression, filter method, no interlacing
+  header.extend([8, COLOR_TYPE, 0, 0, 0]);
+  write_chunk(&mut png, b"IHDR", &header);
+  write_chunk(&mut png, b"IDAT", &zlib(&filter_rows(width as usize*4, rgba)));
+  write_chunk(&mut png, b"IEND", &[]);
+  png
+}
+
+//length, type, data, and the crc of the type and data
+fn write_chunk(png: &mut Vec<u8>, chunk_type: &[u8; 4], data: &[u8]) {
+  png.extend((data.len() as u32).to_be_bytes());
+  let start = png.len();
+  png.extend(chunk_type);
+  png.extend(data);
+  let crc = crc32(&png[start..]);
+  png.extend(crc.to_be_bytes());
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+  let mut crc = 0xFFFFFFFFu32;
+  for byte in bytes {
+    crc ^= u32::from(*byte);
+    for _ in 0..8 {
+      crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB88320 } else { crc >> 1 };
+    }
+  }
+  !crc
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+  let (mut a, mut b) = (1u32, 0u32);
+  for byte in bytes {
+    a = (a+u32::from(*byte)) % 65521;
+    b = (b+a) % 65521;
+  }
+  (b << 16) | a
+}
+
+//each row starts with its filter type: 0 none, 1 sub (left), 2 up, 3 average, 4 paeth
+fn filter_rows(row_size: usize, pixels: &[u8]) -> Vec<u8> {
+  let mut filtered: Vec<u8> = Vec::with_capacity(pixels.len()+pixels.len()/row_size.max(1));
+  let empty_row = vec![0; row_size];
+  for (y, row) in pixels.chunks_exact(row_size).enumerate() {
+    let above = if y == 0 { &empty_row[..] } else { &pixels[(y-1)*row_size..y*row_size] };
+    //the usual heuristic: smallest sum of the bytes as signed values
+    let best = (0..5u8).map(|filter| filter_row(filter, row, above)).min_by_key(|candidate| candidate.iter().map(|byte| u32::from((*byte as i8).unsigned_abs())).sum::<u32>()).unwrap();
+    filtered.extend(best);
+  }
+  filtered
+}
+
+fn filter_row(filter: u8, row: &[u8], above: &[u8]) -> Vec<u8> {
+  let mut filtered: Vec<u8> = Vec::with_capacity(row.len()+1);
+  filtered.push(filter);
+  for i in 0..row.len() {
+    //4 bytes per pixel
+    let left = if i >= 4 { row[i-4] } else { 0 };
+    let upper_left = if i >= 4 { above[i-4] } else { 0 };
+    let predicted = match filter {
+      0 => 0,
+      1 => left,
+      2 => above[i],
+      3 => ((u16::from(left)+u16::from(above[i]))/2) as u8,
+      _ => paeth(left, above[i], upper_left),
+    };
+    filtered.push(row[i].wrapping_sub(predicted));
+  }
+  filtered
+}
+
+fn paeth(left: u8, above: u8, upper_left: u8) -> u8 {
+  let estimate = i16::from(left)+i16::from(above)-i16::from(upper_left);
+  let (to_left, to_above, to_upper_left) = ((estimate-i16::from(left)).abs(), (estimate-i16::from(above)).abs(), (estimate-i16::from(upper_left)).abs());
+  if to_left <= to_above && to_left <= to_upper_left {
+    left
+  } else if to_above <= to_upper_left {
+    above
+  } else {
+    upper_left
+  }
+}
+
+//zlib header (deflate, 32kb window, no dictionary), one deflate block, then the adler32 checksum
+fn zlib(data: &[u8]) -> Vec<u8> {
+  let mut compressed: Vec<u8> = vec![0x78, 0x01];
+  compressed.extend(deflate(data));
+  compressed.extend(adler32(data).to_be_bytes());
+  compressed
+}
+
+//deflate bits are packed starting from the lowest bit of each byte
+struct BitWriter {
+  bytes: Vec<u8>,
+  bit_count: u32,
+}
+
+impl BitWriter {
+  fn write_bits(&mut self, value: u32, count: u8) {
+    for i in 0..count {
+      if self.bit_count.is_multiple_of(8) {
+        self.bytes.push(0);
+      }
+      let bit = ((value >> i) & 1) as u8;
+      *self.bytes.last_mut().unwrap() |= bit << (self.bit_count % 8);
+      self.bit_count += 1;
+    }
+  }
+
+  //huffman codes go most significant bit first
+  fn write_code(&mut self, code: u32, length: u8) {
+    let reversed = code.reverse_bits() >> (32-u32::from(length));
+    self.write_bits(reversed, length);
+  }
+
+  //fixed literal/length codes
+  fn write_symbol(&mut self, symbol: u16) {
+    let symbol = u32::from(symbol);
+    match symbol {
+      0..=143 => self.write_code(0x30+symbol, 8),
+      144..=255 => self.write_code(0x190+symbol-144, 9),
+      256..=279 => self.write_code(symbol-256, 7),
+      _ => self.write_code(0xC0+symbol-280, 8),
+    }
+  }
+}
+
+//most recent position of every 3 byte sequence (by hash), and for every position the one before it with the same hash
+struct MatchFinder {
+  head: Vec<Option<usize>>,
+  previous: Vec<Option<usize>>,
+}
+
+impl MatchFinder {
+  fn hash(data: &[u8], i: usize) -> usize {
+    ((usize::from(data[i]) << 10) ^ (usize::from(data[i+1]) << 5) ^ usize::from(data[i+2])) & 0x7FFF
+  }
+
+  fn insert(&mut self, data: &[u8], i: usize) {
+    if i+MIN_MATCH <= data.len() {
+      let hash = Self::hash(data, i);
+      self.previous[i] = self.head[hash];
+      self.head[hash] = Some(i);
+    }
+  }
+
+  //length and distance of the longest earlier match
+  fn longest_match(&self, data: &[u8], i: usize) -> (usize, usize) {
+    let (mut best_length, mut best_distance) = (0, 0);
+    if i+MIN_MATCH > data.len() {
+      return (0, 0);
+    }
+    let mut candidate = self.head[Self::hash(data, i)];
+    let mut checked = 0;
+    while let Some(start) = candidate {
+      if i-start > WINDOW_SIZE || checked == MAX_CHAIN {
+        break;
+      }
+      let length = data[start..].iter().zip(&data[i..]).take(MAX_MATCH).take_while(|(a, b)| a == b).count();
+      if length > best_length {
+        best_length = length;
+        best_distance = i-start;
+      }
+      candidate = self.previous[start];
+      checked += 1;
+    }
+    (best_length, best_distance)
+  }
+}
+
+fn deflate(data: &[u8]) -> Vec<u8> {
+  let mut writer = BitWriter { bytes: Vec::new(), bit_count: 0 };
+  //last block, fixed huffman codes
+  writer.write_bits(1, 1);
+  writer.write_bits(1, 2);
+  let mut finder = MatchFinder { head: vec![None; 0x8000], previous: vec![None; data.len()] };
+  let mut i = 0;
+  while i < data.len() {
+    let (length, distance) = finder.longest_match(data, i);
+    if length >= MIN_MATCH {
+      write_match(&mut writer, length, distance);
+      for position in i..i+length {
+        finder.insert(data, position);
+      }
+      i += length;
+    } else {
+      writer.write_symbol(u16::from(data[i]));
+      finder.insert(data, i);
+      i += 1;
+    }
+  }
+  //end of block
+  writer.write_symbol(256);
+  writer.bytes
+}
+
+fn write_match(writer: &mut BitWriter, length: usize, distance: usize) {
+  let length_code = LENGTH_BASES.iter().rposition(|base| usize::from(*base) <= length).unwrap();
+  writer.write_symbol(257+length_code as u16);
+  writer.write_bits((length-usize::from(LENGTH_BASES[length_code])) as u32, LENGTH_EXTRA_BITS[length_code]);
+  let distance_code = DISTANCE_BASES.iter().rposition(|base| usize::from(*base) <= distance).unwrap();
+  //distance codes are all 5 bits
+  writer.write_code(distance_code as u32, 5);
+  writer.write_bits((distance-usize::from(DISTANCE_BASES[distance_code])) as u32, DISTANCE_EXTRA_BITS[distance_code]);
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn checksums() {
+    assert_eq!(crc32(b"IEND"), 0xAE426082);
+    assert_eq!(adler32(b"Wikipedia"), 0x11E60398);
+  }
+
+  #[test]
+  fn png_chunks() {
+    let rgba: Vec<u8> = [255, 0, 0, 128].repeat(400);
+    let png = encode_png(20, 20, &rgba);
+    assert_eq!(png[..8], SIGNATURE);
+    //IHDR is 13 bytes long
+    assert_eq!(png[8..16], [0, 0, 0, 13, b'I', b'H', b'D', b'R']);
+    assert_eq!(png[16..29], [0, 0, 0, 20, 0, 0, 0, 20, 8, 6, 0, 0, 0]);
+    assert!(png.ends_with(&[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82]));
+    //the repeated pixel is compressed
+    assert!(png.len() < rgba.len()/10);
+  }
+
+  #[test]
+  fn filters_undo_with_prediction() {
+    let above = [10, 20, 30, 40, 50, 60, 70, 80];
+    let row = [12, 22, 33, 200, 52, 61, 75, 90];
+    for filter in 0..5 {
+      let filtered = filter_row(filter, &row, &above);
+      assert_eq!(filtered[0], filter);
+      assert_eq!(filtered.len(), 9);
+    }
+    assert_eq!(filter_row(2, &row, &above)[1..], [2, 2, 3, 160, 2, 1, 5, 10]);
+    assert_eq!(paeth(10, 20, 10), 20);
+  }
+}
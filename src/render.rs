@@ -0,0 +1,122 @@
+use std::collections::VecDeque;
+
+use bmp_rust::bmp::{BMP, DIBHEADER, ErrorKind};
+
+use crate::bitfields::MaskedLayout;
+use crate::indexed::IndexedLayout;
+
+//turning the image into rgba bytes, in the same layout as canvas ImageData (rows from the top, 4 bytes per pixel),
+//so it can be put onto a canvas all at once instead of drawing every pixel
+
+//reads pixel colors of any image. indexed images are looked up in their color table here, and 16, 24 and 32 bit images
+//are read with their masks, since bmp_rust misreads both. anything else (eg compressed) is left to bmp_rust
+pub enum PixelReader<'a> {
+  Indexed(&'a BMP, IndexedLayout, Vec<[u8; 4]>),
+  Masked(&'a BMP, MaskedLayout),
+  Direct(&'a BMP, Box<DIBHEADER>, VecDeque<Vec<Vec<u8>>>),
+}
+
+impl<'a> PixelReader<'a> {
+  pub fn new(bmp: &'a BMP) -> Result<Self, ErrorKind> {
+    match IndexedLayout::of(bmp)? {
+      Some(layout) => {
+        let colors = layout.colors(bmp);
+        Ok(PixelReader::Indexed(bmp, layout, colors))
+      },
+      None => match MaskedLayout::of(bmp)? {
+        Some(layout) => Ok(PixelReader::Masked(bmp, layout)),
+        None => Ok(PixelReader::Direct(bmp, Box::new(bmp.get_dib_header()?), bmp.get_pixel_data()?)),
+      },
+    }
+  }
+
+  pub fn color(&self, x: u16, y: u16) -> Result<[u8; 4], ErrorKind> {
+    match self {
+      PixelReader::Indexed(bmp, layout, colors) => {
+        let index = layout.index_at(bmp, x, y)?;
+        //indexes past the end of the table show as black
+        Ok(colors.get(usize::from(index)).copied().unwrap_or([0, 0, 0, 255]))
+      },
+      PixelReader::Masked(bmp, layout) => layout.color_at(bmp, x, y),
+      PixelReader::Direct(bmp, dib_header, pixel_data) => bmp.get_color_of_pixel_efficient(x as usize, y as usize, dib_header, pixel_data),
+    }
+  }
+}
+
+//width and height of an area (top left and bottom right, inclusive)
+pub fn area_size(area: [[u16; 2]; 2]) -> [u32; 2] {
+  [u32::from(area[1][0]-area[0][0])+1, u32::from(area[1][1]-area[0][1])+1]
+}
+
+//whole image
+pub fn image_area(bmp: &BMP) -> Result<Option<[[u16; 2]; 2]>, ErrorKind> {
+  let dib_header = bmp.get_dib_header()?;
+  let height = dib_header.height.unsigned_abs();
+  if dib_header.width == 0 || height == 0 {
+    return Ok(None);
+  }
+  Ok(Some([[0, 0], [(dib_header.width-1) as u16, (height-1) as u16]]))
+}
+
+pub fn rgba_area(bmp: &BMP, area: [[u16; 2]; 2]) -> Result<Vec<u8>, ErrorKind> {
+  let reader = PixelReader::new(bmp)?;
+  let size = area_size(area);
+  let mut rgba: Vec<u8> = Vec::with_capacity((size[0]*size[1]*4) as usize);
+  for y in area[0][1]..=area[1][1] {
+    for x in area[0][0]..=area[1][0] {
+      rgba.extend_from_slice(&reader.color(x, y)?);
+    }
+  }
+  Ok(rgba)
+}
+
+//the other way: a new 32 bit image (the same kind BMP::new makes) from rgba bytes, like canvas ImageData
+pub fn bmp_from_rgba(width: u32, height: u32, rgba: &[u8]) -> Result<BMP, ErrorKind> {
+  //checked, since a huge width and height would overflow instead of just not matching
+  let expected_len = (width as usize).checked_mul(height as usize).and_then(|pixels| pixels.checked_mul(4));
+  if width == 0 || height == 0 || expected_len != Some(rgba.len()) {
+    return Err(ErrorKind::InvalidParameter(format!("{} bytes is not a {}x{} image", rgba.len(), width, height)));
+  }
+  let mut bmp = BMP::new(height as i32, width, None);
+  let offset = bmp.get_offset() as usize;
+  //bottom row first, and bgra. rows of 32 bit pixels don't need padding
+  let bgra: Vec<u8> = rgba.chunks_exact(width as usize*4).rev().flat_map(|row| row.chunks_exact(4)).flat_map(|pixel| [pixel[2], pixel[1], pixel[0], pixel[3]]).collect();
+  bmp.contents[offset..].copy_from_slice(&bgra);
+  Ok(bmp)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::indexed::new_indexed;
+
+  #[test]
+  fn rgba_area_is_row_major() {
+    let mut bmp = BMP::new(3, 4, Some([0, 0, 0, 255]));
+    bmp.change_color_of_pixel(1, 0, [255, 0, 0, 255]).unwrap();
+    bmp.change_color_of_pixel(2, 1, [0, 255, 0, 128]).unwrap();
+    assert_eq!(image_area(&bmp).unwrap(), Some([[0, 0], [3, 2]]));
+    let rgba = rgba_area(&bmp, [[1, 0], [2, 1]]).unwrap();
+    assert_eq!(area_size([[1, 0], [2, 1]]), [2, 2]);
+    assert_eq!(rgba, vec![
+      255, 0, 0, 255, 0, 0, 0, 255,
+      0, 0, 0, 255, 0, 255, 0, 128,
+    ]);
+  }
+
+  #[test]
+  fn rgba_roundtrip() {
+    let rgba: Vec<u8> = (0..24).collect();
+    let bmp = bmp_from_rgba(3, 2, &rgba).unwrap();
+    assert_eq!(bmp.get_color_of_pixel(1, 0).unwrap(), [4, 5, 6, 7]);
+    assert_eq!(rgba_area(&bmp, image_area(&bmp).unwrap().unwrap()).unwrap(), rgba);
+    assert!(bmp_from_rgba(3, 3, &rgba).is_err());
+    assert!(bmp_from_rgba(u32::MAX, u32::MAX, &rgba).is_err());
+  }
+
+  #[test]
+  fn indexed_colors_come_from_table() {
+    let bmp = new_indexed(3, 1, 4, &[[0, 0, 0, 255], [255, 0, 0, 255]], &[1, 0, 1]).unwrap();
+    assert_eq!(rgba_area(&bmp, [[0, 0], [2, 0]]).unwrap(), vec![255, 0, 0, 255, 0, 0, 0, 255, 255, 0, 0, 255]);
+  }
+}
@@ -0,0 +1,241 @@
+use bmp_rust::bmp::{BMP, ErrorKind};
+
+use crate::export::headers;
+use crate::header::copy_resolution;
+use crate::indexed::{IndexedLayout, color_table, new_indexed};
+
+//run length encoded (BI_RLE8 and BI_RLE4) images. bmp_rust reads the compressed bytes as if they were pixels,
+//so they are decoded into a normal indexed image when loaded, and can be encoded again when saving.
+//the data is pairs of bytes: a count and the index to repeat, or 0 and an escape:
+//0 is end of line, 1 is end of bitmap, 2 is a delta (move right and up), and anything else is that many literal indexes
+
+const END_OF_LINE: u8 = 0;
+const END_OF_BITMAP: u8 = 1;
+const DELTA: u8 = 2;
+//8192x8192, the decoded image (and the rgba copy drawn from it) has to fit in memory
+const MAX_PIXELS: u64 = 1 << 26;
+
+//the size is only in the header, so a tiny file could claim to be huge and make decoding allocate gigabytes.
+//every 2 bytes of data cover at most a run of 255 pixels or the rest of a row, so bigger images are rejected
+//(deltas can skip more, but encoders don't write images that are mostly skipped)
+pub fn check_rle_size(width: u32, height: u32, data_len: usize) -> Result<(), String> {
+  let pixels = u64::from(width)*u64::from(height);
+  if pixels > MAX_PIXELS {
+    return Err(format!("The run length encoded image is {}x{}, but only images up to {} pixels can be decoded", width, height, MAX_PIXELS));
+  }
+  if pixels > (data_len as u64/2)*u64::from(width.max(255)) {
+    return Err(format!("The run length encoded data ({} bytes) is too short for a {}x{} image", data_len, width, height));
+  }
+  Ok(())
+}
+
+//BI_RLE8 or BI_RLE4 compression value in the header
+fn compression_of(bitcount: u16) -> Result<u32, ErrorKind> {
+  match bitcount {
+    8 => Ok(1),
+    4 => Ok(2),
+    _ => Err(ErrorKind::InvalidParameter(format!("only 8 and 4 bit images can be run length encoded, not {} bit", bitcount))),
+  }
+}
+
+pub fn is_rle(bmp: &BMP) -> bool {
+  bmp.get_dib_header().map(|dib_header| matches!(dib_header.compression.as_deref(), Some("BI_RLE8") | Some("BI_RLE4"))).unwrap_or(false)
+}
+
+//none if the image isn't run length encoded. pixels skipped over by deltas get index 0
+pub fn decode_rle(bmp: &BMP) -> Result<Option<BMP>, ErrorKind> {
+  if !is_rle(bmp) {
+    return Ok(None);
+  }
+  let dib_header = bmp.get_dib_header()?;
+  let bitcount = dib_header.bitcount;
+  compression_of(bitcount)?;
+  let width = dib_header.width as usize;
+  //rle images are always stored bottom up
+  let height = dib_header.height.unsigned_abs() as usize;
+  let colors = color_table(bmp)?;
+  if colors.is_empty() {
+    return Err(ErrorKind::InvalidParameter("run length encoded image has no color table".to_string()));
+  }
+  let data = &bmp.contents[(bmp.get_offset() as usize).min(bmp.contents.len())..];
+  check_rle_size(width as u32, height as u32, data.len()).map_err(ErrorKind::InvalidParameter)?;
+  let mut indexes: Vec<u8> = vec![0; width*height];
+  //y counts up from the bottom row
+  let (mut x, mut y) = (0usize, 0usize);
+  let mut set = |x: usize, y: usize, index: u8| {
+    //files can point outside the image, those pixels are ignored
+    if x < width && y < height {
+      indexes[(height-1-y)*width+x] = index;
+    }
+  };
+  //4 bit indexes are packed two to a byte, the high half first
+  let nibble = |byte: u8, i: usize| if i.is_multiple_of(2) { byte >> 4 } else { byte & 0x0F };
+  let mut i = 0;
+  while i+1 < data.len() {
+    let (count, value) = (data[i], data[i+1]);
+    i += 2;
+    if count > 0 {
+      for n in 0..usize::from(count) {
+        set(x, y, if bitcount == 8 { value } else { nibble(value, n) });
+        x += 1;
+      }
+      continue;
+    }
+    match value {
+      END_OF_LINE => {
+        x = 0;
+        y += 1;
+      },
+      END_OF_BITMAP => break,
+      DELTA => {
+        if i+1 >= data.len() {
+          break;
+        }
+        x += usize::from(data[i]);
+        y += usize::from(data[i+1]);
+        i += 2;
+      },
+      literal_count => {
+        let literal_count = usize::from(literal_count);
+        let byte_count = if bitcount == 8 { literal_count } else { literal_count.div_ceil(2) };
+        for n in 0..literal_count {
+          let byte = match data.get(i+if bitcount == 8 { n } else { n/2 }) {
+            Some(byte) => *byte,
+            None => break,
+          };
+          set(x, y, if bitcount == 8 { byte } else { nibble(byte, n) });
+          x += 1;
+        }
+        //literal runs are padded to a multiple of 2 bytes
+        i += byte_count+byte_count % 2;
+      },
+    }
+  }
+  let max_index = (colors.len()-1) as u8;
+  let indexes: Vec<u8> = indexes.into_iter().map(|index| index.min(max_index)).collect();
+  let mut decoded = new_indexed(width as u32, height as u32, bitcount, &colors, &indexes)?;
+  copy_resolution(bmp, &mut decoded)?;
+  Ok(Some(decoded))
+}
+
+//compress an uncompressed 8 or 4 bit indexed image
+pub fn encode_rle(bmp: &BMP) -> Result<BMP, ErrorKind> {
+  let layout = IndexedLayout::of(bmp)?.ok_or(ErrorKind::InvalidParameter("only indexed images can be run length encoded".to_string()))?;
+  let compression = compression_of(layout.bitcount)?;
+  let mut data: Vec<u8> = Vec::new();
+  for y in (0..layout.height).rev() {
+    let row = (0..layout.width).map(|x| layout.index_at(bmp, x as u16, y as u16)).collect::<Result<Vec<u8>, ErrorKind>>()?;
+    encode_row(&row, layout.bitcount, &mut data);
+    data.extend([0, END_OF_LINE]);
+  }
+  //the last end of line is replaced by end of bitmap
+  data.truncate(data.len()-2);
+  data.extend([0, END_OF_BITMAP]);
+  let table: Vec<u8> = layout.colors(bmp).iter().flat_map(|color| [color[2], color[1], color[0], 0]).collect();
+  let mut contents = headers(layout.width, layout.height, layout.bitcount, compression, &table, layout.table_len as u32, data.len());
+  contents.extend(data);
+  let mut encoded = BMP::new(1, 1, None);
+  encoded.contents = contents;
+  copy_resolution(bmp, &mut encoded)?;
+  Ok(encoded)
+}
+
+//runs of 3 or more of the same index are repeated, anything else goes in literal runs
+//(which need at least 3 indexes, shorter ones are written as runs of 1 or 2)
+fn encode_row(row: &[u8], bitcount: u16, data: &mut Vec<u8>) {
+  let run_length = |start: usize| row[start..].iter().take(255).take_while(|index| **index == row[start]).count();
+  let mut i = 0;
+  while i < row.len() {
+    let run = run_length(i);
+    if run >= 3 || row.len()-i < 3 {
+      let run = run.min(row.len()-i);
+      data.extend([run as u8, if bitcount == 8 { row[i] } else { row[i] << 4 | row[i] }]);
+      i += run;
+      continue;
+    }
+    //literal run until the next run worth repeating
+    let mut end = i;
+    while end < row.len() && end-i < 255 && run_length(end) < 3 {
+      end += 1;
+    }
+    let literal = &row[i..end];
+    if literal.len() < 3 {
+      for index in literal {
+        data.extend([1, if bitcount == 8 { *index } else { *index << 4 }]);
+      }
+    } else {
+      data.extend([0, literal.len() as u8]);
+      let start = data.len();
+      if bitcount == 8 {
+        data.extend(literal);
+      } else {
+        data.extend(literal.chunks(2).map(|pair| pair[0] << 4 | pair.get(1).copied().unwrap_or(0)));
+      }
+      if (data.len()-start) % 2 == 1 {
+        data.push(0);
+      }
+    }
+    i = end;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const COLORS: [[u8; 4]; 3] = [[0, 0, 0, 255], [255, 0, 0, 255], [0, 0, 255, 255]];
+
+  fn indexes_of(bmp: &BMP) -> Vec<u8> {
+    let layout = IndexedLayout::of(bmp).unwrap().unwrap();
+    (0..layout.height).flat_map(|y| (0..layout.width).map(move |x| (x, y))).map(|(x, y)| layout.index_at(bmp, x as u16, y as u16).unwrap()).collect()
+  }
+
+  fn rle_bmp(width: u32, height: u32, bitcount: u16, data: &[u8]) -> BMP {
+    let table: Vec<u8> = COLORS.iter().flat_map(|color| [color[2], color[1], color[0], 0]).collect();
+    let mut contents = headers(width, height, bitcount, compression_of(bitcount).unwrap(), &table, COLORS.len() as u32, data.len());
+    contents.extend(data);
+    let mut bmp = BMP::new(1, 1, None);
+    bmp.contents = contents;
+    bmp
+  }
+
+  #[test]
+  fn decode_escapes() {
+    //bottom row: 3 reds, then a literal blue, black, blue. then a delta to x=1 of the top row, and 2 blues
+    let bmp = rle_bmp(6, 2, 8, &[3, 1, 0, 3, 2, 0, 2, 0, 0, 0, 0, 2, 1, 0, 2, 2, 0, 1]);
+    assert!(is_rle(&bmp));
+    let decoded = decode_rle(&bmp).unwrap().unwrap();
+    assert!(!is_rle(&decoded));
+    assert_eq!(indexes_of(&decoded), vec![0, 2, 2, 0, 0, 0, 1, 1, 1, 2, 0, 2]);
+    //4 bit: a run alternating between two indexes, then an end of line and a literal of 3
+    let bmp = rle_bmp(4, 2, 4, &[4, 0x12, 0, 0, 0, 3, 0x21, 0x20, 0, 1]);
+    assert_eq!(indexes_of(&decode_rle(&bmp).unwrap().unwrap()), vec![2, 1, 2, 0, 1, 2, 1, 2]);
+    assert!(decode_rle(&new_indexed(1, 1, 8, &COLORS, &[1]).unwrap()).unwrap().is_none());
+  }
+
+  #[test]
+  fn huge_sizes_are_rejected() {
+    //just an end of bitmap, claiming to be 65535x65535
+    assert!(decode_rle(&rle_bmp(65535, 65535, 8, &[0, 1])).is_err());
+    assert!(check_rle_size(8192, 8193, 1 << 30).is_err());
+    assert!(check_rle_size(1000, 1000, 20).is_err());
+    //a row of end of lines is enough for empty rows
+    assert!(check_rle_size(1000, 10, 20).is_ok());
+  }
+
+  #[test]
+  fn encode_roundtrip() {
+    let indexes: Vec<u8> = vec![
+      1, 1, 1, 1, 2, 0, 2, 0, 0, 0, 1,
+      2, 2, 0, 1, 1, 1, 1, 1, 1, 0, 2,
+    ];
+    for bitcount in [8, 4] {
+      let bmp = new_indexed(11, 2, bitcount, &COLORS, &indexes).unwrap();
+      let encoded = encode_rle(&bmp).unwrap();
+      assert!(is_rle(&encoded));
+      assert!(encoded.contents.len() < bmp.contents.len()+16);
+      assert_eq!(indexes_of(&decode_rle(&encoded).unwrap().unwrap()), indexes);
+    }
+    assert!(encode_rle(&new_indexed(2, 1, 1, &COLORS[..2], &[0, 1]).unwrap()).is_err());
+  }
+}
@@ -0,0 +1,255 @@
+use std::fmt;
+
+use crate::editor::{EditorCommand, color_text};
+use crate::parse::{input_to_color, input_to_coords, input_to_u16, input_to_u32, input_to_u8};
+
+//human readable edit scripts, one operation per line, eg:
+//  line 0,0 20,20 (255,0,0,255)
+//  gaussian 3
+//blank lines and lines starting with # are ignored
+
+pub const SCRIPT_HELP: &str = "pixel x,y color
+pixels x,y x,y ... color
+fill x,y color
+line x,y x,y color
+rect x,y x,y color
+ellipse x,y xlength ylength color
+invert
+greyscale
+rotate
+gaussian radius
+box radius
+median radius
+colortable index color
+resolution xpixelspermeter ypixelspermeter";
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct ScriptError {
+  //starting from 1
+  pub line: usize,
+  pub message: String,
+}
+
+impl fmt::Display for ScriptError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "Line {}: {}", self.line, self.message)
+  }
+}
+
+pub fn command_to_line(command: &EditorCommand) -> String {
+  fn coords_text(coords: &[u16; 2]) -> String {
+    format!("{},{}", coords[0], coords[1])
+  }
+  match command {
+    EditorCommand::ChangePixels(pixels, color) => {
+      format!("pixels {} {}", pixels.iter().map(coords_text).collect::<Vec<String>>().join(" "), color_text(*color))
+    },
+    EditorCommand::ChangePixel(coord, color) => format!("pixel {} {}", coords_text(coord), color_text(*color)),
+    EditorCommand::FillBucket(coord, color) => format!("fill {} {}", coords_text(coord), color_text(*color)),
+    EditorCommand::Invert => "invert".to_string(),
+    EditorCommand::Greyscale => "greyscale".to_string(),
+    EditorCommand::Rotate => "rotate".to_string(),
+    EditorCommand::DrawLine(endpoints, color) => format!("line {} {} {}", coords_text(&endpoints[0]), coords_text(&endpoints[1]), color_text(*color)),
+    EditorCommand::DrawRect(endpoints, color) => format!("rect {} {} {}", coords_text(&endpoints[0]), coords_text(&endpoints[1]), color_text(*color)),
+    EditorCommand::DrawEllipse(ellipse_args, color) => format!("ellipse {} {} {} {}", coords_text(&ellipse_args[0]), ellipse_args[1][0], ellipse_args[1][1], color_text(*color)),
+    EditorCommand::GaussianBlur(radius) => format!("gaussian {}", radius),
+    EditorCommand::BoxBlur(radius) => format!("box {}", radius),
+    EditorCommand::MedianFilter(radius) => format!("median {}", radius),
+    EditorCommand::ChangeTableColor(index, color) => format!("colortable {} {}", index, color_text(*color)),
+    EditorCommand::SetResolution(x, y) => format!("resolution {} {}", x, y),
+  }
+}
+
+pub fn to_script(commands: &[EditorCommand]) -> String {
+  commands.iter().map(|command| command_to_line(command)+"\n").collect()
+}
+
+//split on spaces, except for spaces inside brackets, so "(255, 0, 0, 255)" stays together
+fn split_args(line: &str) -> Vec<String> {
+  let mut args: Vec<String> = Vec::new();
+  let mut current = String::new();
+  let mut depth = 0;
+  for c in line.chars() {
+    if c == '(' {
+      depth += 1;
+    } else if c == ')' && depth > 0 {
+      depth -= 1;
+    }
+    if c.is_whitespace() && depth == 0 {
+      if !current.is_empty() {
+        args.push(current);
+        current = String::new();
+      }
+    } else {
+      current.push(c);
+    }
+  }
+  if !current.is_empty() {
+    args.push(current);
+  }
+  args
+}
+
+//returns none for blank lines and comments
+pub fn parse_line(line: &str) -> Result<Option<EditorCommand>, String> {
+  let line = line.trim();
+  if line.is_empty() || line.starts_with("#") {
+    return Ok(None);
+  }
+  let args = split_args(line);
+  let operation = args[0].to_lowercase();
+  let args = &args[1..];
+  let expect_args = |num: usize| -> Result<(), String> {
+    if args.len() != num {
+      Err(format!("{} takes {} argument{}, found {}", operation, num, if num == 1 { "" } else { "s" }, args.len()))
+    } else {
+      Ok(())
+    }
+  };
+  let command = match operation.as_str() {
+    "pixel" => {
+      expect_args(2)?;
+      EditorCommand::ChangePixel(input_to_coords(&args[0])?, input_to_color(&args[1])?)
+    },
+    "pixels" => {
+      if args.len() < 2 {
+        return Err(format!("pixels takes at least one coordinate and a color, found {} arguments", args.len()));
+      }
+      let pixels = args[..args.len()-1].iter().map(|arg| input_to_coords(arg)).collect::<Result<Vec<[u16; 2]>, String>>()?;
+      EditorCommand::ChangePixels(pixels, input_to_color(&args[args.len()-1])?)
+    },
+    "fill" => {
+      expect_args(2)?;
+      EditorCommand::FillBucket(input_to_coords(&args[0])?, input_to_color(&args[1])?)
+    },
+    "line" | "rect" => {
+      expect_args(3)?;
+      let endpoints = [input_to_coords(&args[0])?, input_to_coords(&args[1])?];
+      let color = input_to_color(&args[2])?;
+      if operation == "line" {
+        EditorCommand::DrawLine(endpoints, color)
+      } else {
+        EditorCommand::DrawRect(endpoints, color)
+      }
+    },
+    "ellipse" => {
+      expect_args(4)?;
+      EditorCommand::DrawEllipse([input_to_coords(&args[0])?, [input_to_u16(&args[1])?, input_to_u16(&args[2])?]], input_to_color(&args[3])?)
+    },
+    "invert" => {
+      expect_args(0)?;
+      EditorCommand::Invert
+    },
+    "greyscale" | "grayscale" => {
+      expect_args(0)?;
+      EditorCommand::Greyscale
+    },
+    "rotate" => {
+      expect_args(0)?;
+      EditorCommand::Rotate
+    },
+    "gaussian" => {
+      expect_args(1)?;
+      EditorCommand::GaussianBlur(input_to_u8(&args[0])?)
+    },
+    "box" => {
+      expect_args(1)?;
+      EditorCommand::BoxBlur(input_to_u8(&args[0])?)
+    },
+    "median" => {
+      expect_args(1)?;
+      EditorCommand::MedianFilter(input_to_u8(&args[0])?)
+    },
+    "colortable" => {
+      expect_args(2)?;
+      EditorCommand::ChangeTableColor(input_to_u8(&args[0])?, input_to_color(&args[1])?)
+    },
+    "resolution" => {
+      expect_args(2)?;
+      EditorCommand::SetResolution(input_to_u32(&args[0])?, input_to_u32(&args[1])?)
+    },
+    _ => return Err(format!("Unknown operation \"{}\"", operation)),
+  };
+  Ok(Some(command))
+}
+
+//all the errors are returned, not just the first one
+pub fn parse_script(script: &str) -> Result<Vec<EditorCommand>, Vec<ScriptError>> {
+  parse_script_lines(script).map(|lines| lines.into_iter().map(|(_, command)| command).collect())
+}
+
+//same, but with the line number of each command, so errors running it can say which line it was
+pub fn parse_script_lines(script: &str) -> Result<Vec<(usize, EditorCommand)>, Vec<ScriptError>> {
+  let mut commands: Vec<(usize, EditorCommand)> = Vec::new();
+  let mut errors: Vec<ScriptError> = Vec::new();
+  for (index, line) in script.lines().enumerate() {
+    match parse_line(line) {
+      Ok(Some(command)) => commands.push((index+1, command)),
+      Ok(None) => {},
+      Err(message) => errors.push(ScriptError { line: index+1, message }),
+    }
+  }
+  if errors.is_empty() {
+    Ok(commands)
+  } else {
+    Err(errors)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use bmp_rust::bmp::BMP;
+  use crate::editor::Document;
+  use crate::history::DEFAULT_HISTORY_BUDGET;
+
+  #[test]
+  fn parses_lines() {
+    assert_eq!(parse_line("line 0,0 20,20 (255,0,0,255)").unwrap(), Some(EditorCommand::DrawLine([[0, 0], [20, 20]], [255, 0, 0, 255])));
+    assert_eq!(parse_line("  rect (1, 2) (3, 4) (0, 0, 0)").unwrap(), Some(EditorCommand::DrawRect([[1, 2], [3, 4]], [0, 0, 0, 255])));
+    assert_eq!(parse_line("ellipse 10,10 5 3 (1,2,3,4)").unwrap(), Some(EditorCommand::DrawEllipse([[10, 10], [5, 3]], [1, 2, 3, 4])));
+    assert_eq!(parse_line("Gaussian 3").unwrap(), Some(EditorCommand::GaussianBlur(3)));
+    assert_eq!(parse_line("# a comment").unwrap(), None);
+    assert_eq!(parse_line("   ").unwrap(), None);
+  }
+
+  #[test]
+  fn script_roundtrip() {
+    let commands = vec![
+      EditorCommand::ChangePixels(vec![[1, 2], [3, 4]], [5, 6, 7, 8]),
+      EditorCommand::ChangePixel([0, 1], [255, 255, 255, 255]),
+      EditorCommand::FillBucket([2, 2], [0, 0, 0, 255]),
+      EditorCommand::Invert,
+      EditorCommand::Greyscale,
+      EditorCommand::Rotate,
+      EditorCommand::DrawLine([[0, 0], [20, 20]], [255, 0, 0, 255]),
+      EditorCommand::DrawRect([[1, 1], [5, 5]], [0, 255, 0, 255]),
+      EditorCommand::DrawEllipse([[10, 10], [4, 2]], [0, 0, 255, 255]),
+      EditorCommand::GaussianBlur(3),
+      EditorCommand::BoxBlur(2),
+      EditorCommand::MedianFilter(1),
+      EditorCommand::ChangeTableColor(3, [10, 20, 30, 255]),
+      EditorCommand::SetResolution(2835, 3780),
+    ];
+    assert_eq!(parse_script(&to_script(&commands)).unwrap(), commands);
+  }
+
+  #[test]
+  fn errors_have_line_numbers() {
+    let errors = parse_script("invert\n\nline 0,0 (255,0,0,255)\nblurr 3\ngaussian 300\npixel 1,1 (1,2,3,999)").unwrap_err();
+    assert_eq!(errors.iter().map(|error| error.line).collect::<Vec<usize>>(), vec![3, 4, 5, 6]);
+    assert_eq!(errors[0].to_string(), "Line 3: line takes 3 arguments, found 2");
+    assert_eq!(errors[1].message, "Unknown operation \"blurr\"");
+  }
+
+  #[test]
+  fn rects_run_with_any_corners() {
+    let mut document = Document::new(BMP::new(8, 8, None), DEFAULT_HISTORY_BUDGET);
+    //reversed and single pixel rects are drawn, not a crash
+    assert_eq!(document.apply_script("rect 5,5 2,2 (255,0,0)\nrect 3,3 3,3 (255,0,0)").unwrap().len(), 2);
+    let errors = document.apply_script("invert\nrect 5,5 2,2 (255,0,0)\nrect 5,5 9,2 (255,0,0)").unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].line, 3);
+    assert!(errors[0].message.contains("(9, 2) is outside the 8x8 image"));
+  }
+}
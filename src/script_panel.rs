@@ -0,0 +1,128 @@
+use yew::prelude::*;
+use web_sys::{HtmlInputElement, HtmlTextAreaElement};
+use gloo::file::File;
+use gloo::file::callbacks::FileReader;
+
+use bmp_editor::script::SCRIPT_HELP;
+
+//write, import or export edit scripts (one operation per line), and run them on the current image
+
+#[derive(PartialEq, Properties)]
+pub struct ScriptPanelProps {
+  pub show: bool,
+  pub script_errors: Vec<String>,
+  pub run_script_callback: Callback<String>,
+  pub export_script_callback: Callback<bool>,
+}
+
+pub enum ScriptPanelMessage {
+  Show,
+  Hide,
+  Run,
+  Export,
+  Import(File),
+  Imported(String),
+}
+
+pub struct ScriptPanel {
+  display: String,
+  script_ref: NodeRef,
+  reader: Option<FileReader>,
+}
+
+impl Component for ScriptPanel {
+  type Message = ScriptPanelMessage;
+  type Properties = ScriptPanelProps;
+
+  fn create(_ctx: &Context<Self>) -> Self {
+    Self { display: "none".to_string(), script_ref: NodeRef::default(), reader: None }
+  }
+
+  fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+    match msg {
+      Self::Message::Show => {
+        self.display = "block".to_string();
+        true
+      },
+      Self::Message::Hide => {
+        self.display = "none".to_string();
+        true
+      },
+      Self::Message::Run => {
+        let script_textarea: HtmlTextAreaElement = self.script_ref.cast().unwrap();
+        ctx.props().run_script_callback.emit(script_textarea.value());
+        false
+      },
+      Self::Message::Export => {
+        ctx.props().export_script_callback.emit(true);
+        false
+      },
+      Self::Message::Import(file) => {
+        let link = ctx.link().clone();
+        self.reader = Some(gloo::file::callbacks::read_as_text(&file, move |res| {
+          if let Ok(script) = res {
+            link.send_message(Self::Message::Imported(script));
+          }
+        }));
+        false
+      },
+      Self::Message::Imported(script) => {
+        //put it in the textarea so it can be checked before running
+        let script_textarea: HtmlTextAreaElement = self.script_ref.cast().unwrap();
+        script_textarea.set_value(&script);
+        self.reader = None;
+        false
+      },
+    }
+  }
+
+  fn view(&self, ctx: &Context<Self>) -> Html {
+    let link = ctx.link().clone();
+
+    if self.display == "none".to_string() && ctx.props().show {
+      link.send_message(Self::Message::Show);
+    } else if self.display == "block".to_string() && !ctx.props().show {
+      link.send_message(Self::Message::Hide);
+    }
+
+    let run = ctx.link().callback(|_| Self::Message::Run);
+
+    let export = ctx.link().callback(|_| Self::Message::Export);
+
+    let import = ctx.link().batch_callback(|e: Event| {
+      let file_input: HtmlInputElement = e.target_unchecked_into();
+      let file = file_input.files().unwrap().item(0).map(File::from);
+      //so the same file can be imported again
+      file_input.set_value("");
+      file.map(Self::Message::Import)
+    });
+
+    let script_errors = ctx.props().script_errors.iter().map(|error| {
+      html! {
+        <li>{ error }</li>
+      }
+    }).collect::<Html>();
+
+    html! {
+      <div id={"script-panel"} style={"display: ".to_string()+&self.display}>
+        <h3>{ "Edit Script" }</h3>
+        <textarea ref={&self.script_ref} id="script-input" rows="8" placeholder="line 0,0 20,20 (255,0,0,255)"></textarea>
+        <br/>
+        <button onclick={run}>{ "Run Script" }</button>
+        <button onclick={export}>{ "Export History" }</button>
+        <label for="script-upload">{ " Import: " }</label>
+        <input id="script-upload" type="file" name="script-upload" accept=".txt,text/plain" multiple={false} onchange={import}/>
+        if ctx.props().script_errors.len() > 0 {
+          <ul class="script-errors">
+            { script_errors }
+          </ul>
+        }
+        <details>
+          <summary>{ "Operations" }</summary>
+          <pre>{ SCRIPT_HELP }</pre>
+          <p>{ "Colors are (r, g, b, a), and lines starting with # are ignored." }</p>
+        </details>
+      </div>
+    }
+  }
+}
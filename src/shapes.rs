@@ -0,0 +1,94 @@
+use crate::stroke::line_pixels;
+
+//drawing lines, rectangles and ellipses by dragging on the canvas instead of typing coordinates
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum DragShape {
+  Line,
+  Rect,
+  Ellipse,
+}
+
+//turn where the drag started and ended into the arguments for the shape's command:
+//endpoints for lines and rectangles, or the center and x and y lengths for ellipses (dragging out from the center).
+//none if the drag is too small to make the shape
+pub fn shape_args(shape: DragShape, start: [u16; 2], end: [u16; 2], image_size: [u32; 2]) -> Option<[[u16; 2]; 2]> {
+  match shape {
+    DragShape::Line => {
+      if start == end {
+        return None;
+      }
+      Some([start, end])
+    },
+    DragShape::Rect => {
+      //a drag along one row or column is a line, not a rectangle. the top left goes first,
+      //and corners next to each other are fine, the editor fills rects itself instead of using bmp_rust
+      if start[0] == end[0] || start[1] == end[1] {
+        return None;
+      }
+      Some([[start[0].min(end[0]), start[1].min(end[1])], [start[0].max(end[0]), start[1].max(end[1])]])
+    },
+    DragShape::Ellipse => {
+      //keep the whole ellipse inside the image
+      let max_x_length = u32::from(start[0]).min(image_size[0].saturating_sub(1).saturating_sub(u32::from(start[0])));
+      let max_y_length = u32::from(start[1]).min(image_size[1].saturating_sub(1).saturating_sub(u32::from(start[1])));
+      let x_length = u32::from(start[0].abs_diff(end[0])).min(max_x_length) as u16;
+      let y_length = u32::from(start[1].abs_diff(end[1])).min(max_y_length) as u16;
+      if x_length == 0 || y_length == 0 {
+        return None;
+      }
+      Some([start, [x_length, y_length]])
+    },
+  }
+}
+
+//rows of pixels the shape covers, as [y, first x, last x], for previewing it while dragging.
+//rectangles and ellipses are drawn filled in. rectangle corners can be in any order
+pub fn shape_rows(shape: DragShape, args: [[u16; 2]; 2]) -> Vec<[u16; 3]> {
+  match shape {
+    DragShape::Line => line_pixels(args[0], args[1]).iter().map(|pixel| [pixel[1], pixel[0], pixel[0]]).collect(),
+    DragShape::Rect => {
+      let (left, right) = (args[0][0].min(args[1][0]), args[0][0].max(args[1][0]));
+      (args[0][1].min(args[1][1])..=args[0][1].max(args[1][1])).map(|y| [y, left, right]).collect()
+    },
+    DragShape::Ellipse => {
+      let [center, [x_length, y_length]] = args;
+      (-i32::from(y_length)..=i32::from(y_length)).map(|y| {
+        let x = ((1.0-f64::from(y*y)/f64::from(y_length).powi(2))*f64::from(x_length).powi(2)).sqrt().round() as u16;
+        [(i32::from(center[1])+y) as u16, center[0]-x, center[0]+x]
+      }).collect()
+    },
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn rect_args_are_ordered() {
+    assert_eq!(shape_args(DragShape::Rect, [8, 2], [3, 6], [10, 10]), Some([[3, 2], [8, 6]]));
+    assert_eq!(shape_args(DragShape::Rect, [3, 2], [3, 6], [10, 10]), None);
+    assert_eq!(shape_args(DragShape::Rect, [4, 3], [3, 2], [10, 10]), Some([[3, 2], [4, 3]]));
+    assert_eq!(shape_rows(DragShape::Rect, [[3, 2], [4, 3]]), vec![[2, 3, 4], [3, 3, 4]]);
+    assert_eq!(shape_args(DragShape::Line, [8, 2], [3, 6], [10, 10]), Some([[8, 2], [3, 6]]));
+    assert_eq!(shape_args(DragShape::Line, [8, 2], [8, 2], [10, 10]), None);
+  }
+
+  #[test]
+  fn ellipse_args_stay_in_image() {
+    assert_eq!(shape_args(DragShape::Ellipse, [5, 5], [8, 1], [20, 20]), Some([[5, 5], [3, 4]]));
+    assert_eq!(shape_args(DragShape::Ellipse, [2, 5], [19, 9], [20, 20]), Some([[2, 5], [2, 4]]));
+    assert_eq!(shape_args(DragShape::Ellipse, [0, 5], [9, 9], [20, 20]), None);
+  }
+
+  #[test]
+  fn shape_rows_cover_shape() {
+    assert_eq!(shape_rows(DragShape::Rect, [[1, 1], [3, 3]]), vec![[1, 1, 3], [2, 1, 3], [3, 1, 3]]);
+    assert_eq!(shape_rows(DragShape::Line, [[0, 0], [2, 0]]), vec![[0, 0, 0], [0, 1, 1], [0, 2, 2]]);
+    let ellipse = shape_rows(DragShape::Ellipse, [[10, 10], [4, 2]]);
+    assert_eq!(ellipse.len(), 5);
+    assert_eq!(ellipse[0], [8, 10, 10]);
+    assert_eq!(ellipse[2], [10, 6, 14]);
+  }
+}
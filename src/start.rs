@@ -3,6 +3,8 @@ use yew::prelude::*;
 #[derive(PartialEq, Properties)]
 pub struct StartProps {
   pub create_load_callback: Callback<bool>,
+  pub show_restore: bool,
+  pub restore_callback: Callback<bool>,
 }
 
 pub enum StartMessage {
@@ -10,6 +12,7 @@ pub enum StartMessage {
   Hide,
   SendCreate,
   SendLoad,
+  SendRestore,
 }
 
 pub struct Start {
@@ -43,6 +46,10 @@ impl Component for Start {
         let _ = ctx.props().create_load_callback.emit(false);
         self.display = "inline-block".to_string();
         true
+      },
+      Self::Message::SendRestore => {
+        let _ = ctx.props().restore_callback.emit(true);
+        true
       }
     }
   }
@@ -60,10 +67,18 @@ impl Component for Start {
       vec![Self::Message::SendLoad, Self::Message::Hide]
     });
   
+    //the last session is saved in indexeddb, so it can be restored after reloading or closing the tab
+    let restore_session = ctx.link().batch_callback(move |_| {
+      vec![Self::Message::SendRestore, Self::Message::Hide]
+    });
+  
     html! {
       <div id="start" style={"display: ".to_string()+&self.display}>
         <button onclick={create_new}>{ "Create New" }</button>
         <button onclick={load_from_file}>{ "Load from File" }</button>
+        if ctx.props().show_restore {
+          <button onclick={restore_session}>{ "Restore Last Session" }</button>
+        }
       </div>
     }
   }
@@ -0,0 +1,162 @@
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen_futures::JsFuture;
+use js_sys::{Array, Object, Promise, Reflect, Uint8Array};
+use web_sys::{Event, IdbDatabase, IdbOpenDbRequest, IdbRequest, IdbTransactionMode};
+
+use bmp_editor::macros::Macro;
+use bmp_editor::palette::Palette;
+
+use crate::tools::ToolsTypes;
+
+//saving and restoring the working session in indexeddb, so reloading or closing the tab doesn't lose anything
+//recorded macros and the palette are also kept here
+
+const DB_NAME: &str = "bmp-editor";
+//version 2 added the macros store, version 3 the palette store
+const DB_VERSION: u32 = 3;
+const SESSION_STORE: &str = "session";
+//there is only ever one saved session
+const SESSION_KEY: &str = "last";
+//macros are stored by name, serialized with Macro::to_bytes
+const MACRO_STORE: &str = "macros";
+const PALETTE_STORE: &str = "palette";
+//only one palette (and its recent colors) is saved
+const PALETTE_KEY: &str = "current";
+
+pub struct Session {
+  pub bmp_contents: Vec<u8>,
+  pub tool_color: [u8; 4],
+  pub selected_tool: ToolsTypes,
+  //serialized with History::to_bytes
+  pub history: Vec<u8>,
+}
+
+//turn an indexeddb request into a future that resolves to the request's result
+async fn request_result(request: &IdbRequest) -> Result<JsValue, JsValue> {
+  let promise = Promise::new(&mut |resolve, reject| {
+    let request2 = request.clone();
+    let onsuccess = Closure::once_into_js(move |_e: Event| {
+      let _ = resolve.call1(&JsValue::NULL, &request2.result().unwrap_or(JsValue::UNDEFINED));
+    });
+    let request3 = request.clone();
+    let onerror = Closure::once_into_js(move |_e: Event| {
+      let error = match request3.error() {
+        Ok(Some(dom_exception)) => JsValue::from(dom_exception),
+        _ => JsValue::from("IndexedDB request failed"),
+      };
+      let _ = reject.call1(&JsValue::NULL, &error);
+    });
+    request.set_onsuccess(Some(onsuccess.unchecked_ref()));
+    request.set_onerror(Some(onerror.unchecked_ref()));
+  });
+  JsFuture::from(promise).await
+}
+
+async fn open_db() -> Result<IdbDatabase, JsValue> {
+  let factory = web_sys::window().unwrap().indexed_db()?.ok_or(JsValue::from("IndexedDB is not supported"))?;
+  let open_request: IdbOpenDbRequest = factory.open_with_u32(DB_NAME, DB_VERSION)?;
+  //first time opening (or new version), create the object stores that don't exist yet
+  let onupgradeneeded = Closure::once_into_js(move |e: Event| {
+    let open_request: IdbOpenDbRequest = e.target().unwrap().unchecked_into();
+    let db: IdbDatabase = open_request.result().unwrap().unchecked_into();
+    for store_name in [SESSION_STORE, MACRO_STORE, PALETTE_STORE] {
+      if !db.object_store_names().contains(store_name) {
+        db.create_object_store(store_name).unwrap();
+      }
+    }
+  });
+  open_request.set_onupgradeneeded(Some(onupgradeneeded.unchecked_ref()));
+  let db = request_result(&open_request).await?;
+  Ok(db.unchecked_into())
+}
+
+pub async fn save_session(session: Session) -> Result<(), JsValue> {
+  let db = open_db().await?;
+  let transaction = db.transaction_with_str_and_mode(SESSION_STORE, IdbTransactionMode::Readwrite)?;
+  let store = transaction.object_store(SESSION_STORE)?;
+  let value = Object::new();
+  Reflect::set(&value, &JsValue::from("bmp"), &Uint8Array::from(&session.bmp_contents[..]))?;
+  Reflect::set(&value, &JsValue::from("toolColor"), &Uint8Array::from(&session.tool_color[..]))?;
+  Reflect::set(&value, &JsValue::from("selectedTool"), &JsValue::from(session.selected_tool.to_string()))?;
+  Reflect::set(&value, &JsValue::from("history"), &Uint8Array::from(&session.history[..]))?;
+  request_result(&store.put_with_key(&value, &JsValue::from(SESSION_KEY))?).await?;
+  Ok(())
+}
+
+//returns none if there is no saved session
+pub async fn load_session() -> Result<Option<Session>, JsValue> {
+  let db = open_db().await?;
+  let transaction = db.transaction_with_str(SESSION_STORE)?;
+  let store = transaction.object_store(SESSION_STORE)?;
+  let value = request_result(&store.get(&JsValue::from(SESSION_KEY))?).await?;
+  if value.is_undefined() {
+    return Ok(None);
+  }
+  let bmp_contents = Uint8Array::new(&Reflect::get(&value, &JsValue::from("bmp"))?).to_vec();
+  let tool_color_vec = Uint8Array::new(&Reflect::get(&value, &JsValue::from("toolColor"))?).to_vec();
+  let selected_tool = Reflect::get(&value, &JsValue::from("selectedTool"))?.as_string().unwrap_or_default();
+  let history = Uint8Array::new(&Reflect::get(&value, &JsValue::from("history"))?).to_vec();
+  if bmp_contents.len() == 0 || tool_color_vec.len() != 4 {
+    return Ok(None);
+  }
+  Ok(Some(Session {
+    bmp_contents,
+    tool_color: [tool_color_vec[0], tool_color_vec[1], tool_color_vec[2], tool_color_vec[3]],
+    selected_tool: ToolsTypes::from_name(&selected_tool),
+    history,
+  }))
+}
+
+//saving a macro with the same name as an existing one replaces it
+pub async fn save_macro(editor_macro: Macro) -> Result<(), JsValue> {
+  let db = open_db().await?;
+  let transaction = db.transaction_with_str_and_mode(MACRO_STORE, IdbTransactionMode::Readwrite)?;
+  let store = transaction.object_store(MACRO_STORE)?;
+  request_result(&store.put_with_key(&Uint8Array::from(&editor_macro.to_bytes()[..]), &JsValue::from(editor_macro.name))?).await?;
+  Ok(())
+}
+
+pub async fn delete_macro(name: String) -> Result<(), JsValue> {
+  let db = open_db().await?;
+  let transaction = db.transaction_with_str_and_mode(MACRO_STORE, IdbTransactionMode::Readwrite)?;
+  let store = transaction.object_store(MACRO_STORE)?;
+  request_result(&store.delete(&JsValue::from(name))?).await?;
+  Ok(())
+}
+
+//sorted by name, since that is the order indexeddb returns them in. invalid macros are skipped
+pub async fn load_macros() -> Result<Vec<Macro>, JsValue> {
+  let db = open_db().await?;
+  let transaction = db.transaction_with_str(MACRO_STORE)?;
+  let store = transaction.object_store(MACRO_STORE)?;
+  let values: Array = request_result(&store.get_all()?).await?.unchecked_into();
+  Ok(values.iter().filter_map(|value| Macro::from_bytes(&Uint8Array::new(&value).to_vec())).collect())
+}
+
+pub async fn save_palette(palette: Palette, recent_colors: Vec<[u8; 4]>) -> Result<(), JsValue> {
+  let db = open_db().await?;
+  let transaction = db.transaction_with_str_and_mode(PALETTE_STORE, IdbTransactionMode::Readwrite)?;
+  let store = transaction.object_store(PALETTE_STORE)?;
+  let value = Object::new();
+  Reflect::set(&value, &JsValue::from("name"), &JsValue::from(palette.name))?;
+  Reflect::set(&value, &JsValue::from("colors"), &Uint8Array::from(&Palette::colors_to_bytes(&palette.colors)[..]))?;
+  Reflect::set(&value, &JsValue::from("recent"), &Uint8Array::from(&Palette::colors_to_bytes(&recent_colors)[..]))?;
+  request_result(&store.put_with_key(&value, &JsValue::from(PALETTE_KEY))?).await?;
+  Ok(())
+}
+
+//returns none if no palette was saved yet
+pub async fn load_palette() -> Result<Option<(Palette, Vec<[u8; 4]>)>, JsValue> {
+  let db = open_db().await?;
+  let transaction = db.transaction_with_str(PALETTE_STORE)?;
+  let store = transaction.object_store(PALETTE_STORE)?;
+  let value = request_result(&store.get(&JsValue::from(PALETTE_KEY))?).await?;
+  if value.is_undefined() {
+    return Ok(None);
+  }
+  let name = Reflect::get(&value, &JsValue::from("name"))?.as_string().unwrap_or_default();
+  let colors = Palette::colors_from_bytes(&Uint8Array::new(&Reflect::get(&value, &JsValue::from("colors"))?).to_vec());
+  let recent_colors = Palette::colors_from_bytes(&Uint8Array::new(&Reflect::get(&value, &JsValue::from("recent"))?).to_vec());
+  Ok(Some((Palette { name, colors }, recent_colors)))
+}
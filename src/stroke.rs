@@ -0,0 +1,109 @@
+use std::collections::HashSet;
+
+//pixels painted by dragging the mouse. mousemove events can be far apart when the mouse moves fast,
+//so the pixels in between are filled in with a line
+
+//every pixel on the line between two pixels, including both ends (bresenham's line algorithm)
+pub fn line_pixels(start: [u16; 2], end: [u16; 2]) -> Vec<[u16; 2]> {
+  let mut pixels: Vec<[u16; 2]> = Vec::new();
+  let (mut x, mut y) = (i32::from(start[0]), i32::from(start[1]));
+  let (end_x, end_y) = (i32::from(end[0]), i32::from(end[1]));
+  let dx = (end_x-x).abs();
+  let dy = -(end_y-y).abs();
+  let step_x = if x < end_x { 1 } else { -1 };
+  let step_y = if y < end_y { 1 } else { -1 };
+  let mut error = dx+dy;
+  loop {
+    pixels.push([x as u16, y as u16]);
+    if x == end_x && y == end_y {
+      break;
+    }
+    let error2 = error*2;
+    if error2 >= dy {
+      error += dy;
+      x += step_x;
+    }
+    if error2 <= dx {
+      error += dx;
+      y += step_y;
+    }
+  }
+  pixels
+}
+
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct Stroke {
+  pub pixels: Vec<[u16; 2]>,
+  //the same pixels, to quickly check if one is already in the stroke (long strokes can have thousands)
+  added: HashSet<[u16; 2]>,
+}
+
+impl Stroke {
+  pub fn new(start: [u16; 2]) -> Self {
+    Self { pixels: vec![start], added: HashSet::from([start]) }
+  }
+
+  //add the pixel the mouse moved to, returns the pixels that weren't already in the stroke (so only those need drawing)
+  pub fn add_pixel(&mut self, pixel: [u16; 2]) -> Vec<[u16; 2]> {
+    let last = match self.pixels.last() {
+      Some(last) => *last,
+      None => {
+        self.pixels.push(pixel);
+        self.added.insert(pixel);
+        return vec![pixel];
+      },
+    };
+    let mut added: Vec<[u16; 2]> = Vec::new();
+    for line_pixel in line_pixels(last, pixel) {
+      //going back over the stroke shouldn't add the same pixel twice
+      if self.added.insert(line_pixel) {
+        self.pixels.push(line_pixel);
+        added.push(line_pixel);
+      }
+    }
+    added
+  }
+
+  pub fn bounds(&self) -> Option<[[u16; 2]; 2]> {
+    pixels_bounds(&self.pixels)
+  }
+}
+
+//top left and bottom right (inclusive) of all the pixels
+pub fn pixels_bounds(pixels: &[[u16; 2]]) -> Option<[[u16; 2]; 2]> {
+  let first = pixels.first()?;
+  let mut bounds = [*first, *first];
+  for pixel in pixels {
+    bounds[0] = [bounds[0][0].min(pixel[0]), bounds[0][1].min(pixel[1])];
+    bounds[1] = [bounds[1][0].max(pixel[0]), bounds[1][1].max(pixel[1])];
+  }
+  Some(bounds)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn line_pixels_have_no_gaps() {
+    assert_eq!(line_pixels([0, 0], [3, 0]), vec![[0, 0], [1, 0], [2, 0], [3, 0]]);
+    assert_eq!(line_pixels([2, 2], [2, 2]), vec![[2, 2]]);
+    let diagonal = line_pixels([5, 0], [0, 9]);
+    assert_eq!(diagonal.first(), Some(&[5, 0]));
+    assert_eq!(diagonal.last(), Some(&[0, 9]));
+    for pair in diagonal.windows(2) {
+      assert!((i32::from(pair[0][0])-i32::from(pair[1][0])).abs() <= 1);
+      assert!((i32::from(pair[0][1])-i32::from(pair[1][1])).abs() <= 1);
+    }
+  }
+
+  #[test]
+  fn stroke_interpolates_and_skips_repeats() {
+    let mut stroke = Stroke::new([0, 0]);
+    assert_eq!(stroke.add_pixel([0, 3]), vec![[0, 1], [0, 2], [0, 3]]);
+    assert_eq!(stroke.add_pixel([0, 1]), Vec::<[u16; 2]>::new());
+    stroke.add_pixel([2, 1]);
+    assert_eq!(stroke.pixels.len(), 6);
+    assert_eq!(stroke.bounds(), Some([[0, 0], [2, 3]]));
+  }
+}
@@ -1,10 +1,13 @@
 use yew::prelude::*;
-use web_sys::HtmlInputElement;
+use web_sys::{HtmlInputElement, HtmlSelectElement};
 use std::fmt;
 use std::collections::HashMap;
 use gloo_console::log;
 
+use bmp_editor::parse::{input_to_coords, input_to_u16, input_to_u8};
+
 use crate::image_actions::KeybindActions;
+use crate::color_picker::ColorPicker;
 
 //gives instructions on how to use tool, and also provides the interface to actually use tool
 
@@ -12,7 +15,9 @@ use crate::image_actions::KeybindActions;
 pub enum ToolsTypes {
   NoneSelected,
   ClickFill,
+  Pencil,
   BucketFill,
+  Eyedropper,
   Invert,
   Line,
   Rect,
@@ -29,7 +34,9 @@ impl ToolsTypes {
     match self {
       ToolsTypes::NoneSelected => "none",
       ToolsTypes::ClickFill => "fills",
+      ToolsTypes::Pencil => "fills",
       ToolsTypes::BucketFill => "fills",
+      ToolsTypes::Eyedropper => "fills",
       ToolsTypes::Invert => "fills",
       ToolsTypes::Line => "shapes",
       ToolsTypes::Rect => "shapes",
@@ -45,7 +52,9 @@ impl ToolsTypes {
     match self {
       ToolsTypes::NoneSelected => "none-selected",
       ToolsTypes::ClickFill => "click-fill",
+      ToolsTypes::Pencil => "pencil",
       ToolsTypes::BucketFill => "bucket-fill",
+      ToolsTypes::Eyedropper => "eyedropper",
       ToolsTypes::Invert => "invert",
       ToolsTypes::Line => "line",
       ToolsTypes::Rect => "rect",
@@ -57,6 +66,25 @@ impl ToolsTypes {
       ToolsTypes::Rotate => "rotate",
     }
   }
+  //opposite of as_str, anything unknown is none selected
+  pub fn from_name(name: &str) -> Self {
+    match name {
+      "click-fill" => ToolsTypes::ClickFill,
+      "pencil" => ToolsTypes::Pencil,
+      "bucket-fill" => ToolsTypes::BucketFill,
+      "eyedropper" => ToolsTypes::Eyedropper,
+      "invert" => ToolsTypes::Invert,
+      "line" => ToolsTypes::Line,
+      "rect" => ToolsTypes::Rect,
+      "ellipse" => ToolsTypes::Ellipse,
+      "greyscale" => ToolsTypes::Greyscale,
+      "gaussian" => ToolsTypes::Gaussian,
+      "box" => ToolsTypes::Box,
+      "median" => ToolsTypes::Median,
+      "rotate" => ToolsTypes::Rotate,
+      _ => ToolsTypes::NoneSelected,
+    }
+  }
 }
 
 impl fmt::Display for ToolsTypes {
@@ -74,7 +102,12 @@ pub struct ToolsProps {
   pub rect_callback: Callback<[[u16; 2]; 2]>,
   pub ellipse_callback: Callback<[[u16; 2]; 2]>,
   pub blur_callback: Callback<u8>,
+  pub eyedropper_size_callback: Callback<u8>,
+  pub eyedropper_size: u8,
   pub tool_color: [u8; 4],
+  //shapes drawn by dragging on the canvas, so the inputs match them
+  pub drag_endpoints: Option<[[u16; 2]; 2]>,
+  pub drag_ellipse_args: Option<[[u16; 2]; 2]>,
   pub show: bool,
   pub keybinds: HashMap<String, KeybindActions>,
 }
@@ -88,6 +121,7 @@ pub enum ToolsMessage {
   Ellipse([[u16; 2]; 2]),
   ChangeToolColor([u8; 4]),
   Blur(u8),
+  EyedropperSize(u8),
   Greyscale,
   Rotate,
 }
@@ -139,6 +173,10 @@ impl Component for Tools {
         let _ = ctx.props().blur_callback.emit(blur_radius);
         false
       },
+      Self::Message::EyedropperSize(size) => {
+        let _ = ctx.props().eyedropper_size_callback.emit(size);
+        false
+      },
       Self::Message::Greyscale => {
         let _ = ctx.props().filter_callback.emit("greyscale".to_string());
         false
@@ -169,6 +207,7 @@ impl Component for Tools {
     let mut blur_display: String = "none".to_string();
     let mut greyscale_display: String = "none".to_string();
     let mut rotate_display: String = "none".to_string();
+    let mut eyedropper_display: String = "none".to_string();
 
     let selected_tool = ctx.props().selected_tool;
     
@@ -178,11 +217,22 @@ impl Component for Tools {
         selected_tool_info = "Click a pixel to change it's fill to the currently selected color.".to_string();
         color_picker_display = "block".to_string();
       },
+      ToolsTypes::Pencil => {
+        selected_tool_name += "Pencil";
+        selected_tool_info = "Click and drag on the image to draw with the currently selected color. Each stroke can be undone at once.".to_string();
+        color_picker_display = "block".to_string();
+      },
       ToolsTypes::BucketFill => {
         selected_tool_name += "Bucket Fill";
         selected_tool_info = "Click a pixel to change all surrounding pixels with the same color to the currently selected color.".to_string();
         color_picker_display = "block".to_string();
       },
+      ToolsTypes::Eyedropper => {
+        selected_tool_name += "Eyedropper";
+        selected_tool_info = "Click a pixel to make its color the currently selected color. A bigger sample size uses the average color of the pixels around it.".to_string();
+        color_picker_display = "block".to_string();
+        eyedropper_display = "block".to_string();
+      },
       ToolsTypes::Invert => {
         selected_tool_name += "Invert";
         selected_tool_info = "Click the button below to invert the image colors.".to_string();
@@ -190,19 +240,19 @@ impl Component for Tools {
       },
       ToolsTypes::Line => {
         selected_tool_name += "Line";
-        selected_tool_info = "Specify endpoint coordinates and color to create a line.".to_string();
+        selected_tool_info = "Drag on the image, or specify endpoint coordinates and color, to create a line.".to_string();
         end_points_display = "block".to_string();
         color_picker_display = "block".to_string();
       },
       ToolsTypes::Rect => {
         selected_tool_name += "Rect";
-        selected_tool_info = "Specify endpoint coordinates and color to create a rectangle.".to_string();
+        selected_tool_info = "Drag on the image from one corner to the other, or specify endpoint coordinates and color, to create a rectangle.".to_string();
         end_points_display = "block".to_string();
         color_picker_display = "block".to_string();
       },
       ToolsTypes::Ellipse => {
         selected_tool_name += "Ellipse";
-        selected_tool_info = "Specify coordinates, lengths, colors, and create a ellipse.".to_string();
+        selected_tool_info = "Drag out from the center on the image, or specify coordinates, lengths, colors, and create a ellipse.".to_string();
         ellipse_display = "block".to_string();
         color_picker_display = "block".to_string();
       },
@@ -246,8 +296,6 @@ impl Component for Tools {
       return None;
     });
 
-    let tc_input_ref = NodeRef::default();
-
     let first_endpoint_ref = NodeRef::default();
     let second_endpoint_ref = NodeRef::default();
 
@@ -259,48 +307,32 @@ impl Component for Tools {
 
     let tool_color = ctx.props().tool_color;
 
-    let color_text = format!("({}, {}, {}, {})", tool_color[0], tool_color[1], tool_color[2], tool_color[3]);
+    let coords_text = |coords: [u16; 2]| format!("({}, {})", coords[0], coords[1]);
+    let drag_endpoints = ctx.props().drag_endpoints;
+    let drag_ellipse_args = ctx.props().drag_ellipse_args;
 
-    fn parse_vec_input(input: String) -> String {
-      input.replace("(", "").replace(")", "").replace(" ", "")
-    }
-    
-    fn input_to_color(input: String) -> Vec<u8> {
-      return parse_vec_input(input).split(",").map(|value| value.parse::<u8>().unwrap()).collect();
-    }
-
-    fn input_to_u16_vec(input: String) -> Vec<u16> {
-      return parse_vec_input(input).split(",").map(|value| value.parse::<u16>().unwrap()).collect();
-    }
-
-    let new_tool_color = {
-      let tc_input_ref2 = tc_input_ref.clone();
-      ctx.link().callback(move |_| {
-        let tc_input: HtmlInputElement = tc_input_ref2.clone().cast().unwrap();
-        let new_color_vec = input_to_color(tc_input.value());
-        let new_color: [u8; 4] = [new_color_vec[0], new_color_vec[1], new_color_vec[2], new_color_vec[3]];
-        Self::Message::ChangeToolColor(new_color)
-      })
-    };
+    let new_tool_color = ctx.link().callback(Self::Message::ChangeToolColor);
 
     let invert = ctx.link().callback(|_| Self::Message::Filter("invert".to_string()));
 
     let create = {
       let first_endpoint_ref2 = first_endpoint_ref.clone();
       let second_endpoint_ref2 = second_endpoint_ref.clone();
-      ctx.link().callback(move |_| {
+      ctx.link().batch_callback(move |_| {
         let first_endpoint_input: HtmlInputElement = first_endpoint_ref2.cast().unwrap();
-        let first_endpoint_vec: Vec<u16> = input_to_u16_vec(first_endpoint_input.value());
-        let first_endpoint: [u16; 2] = [first_endpoint_vec[0], first_endpoint_vec[1]];
         let second_endpoint_input: HtmlInputElement = second_endpoint_ref2.cast().unwrap();
-        let second_endpoint_vec: Vec<u16> = input_to_u16_vec(second_endpoint_input.value());
-        let second_endpoint: [u16; 2] = [second_endpoint_vec[0], second_endpoint_vec[1]];
-        let endpoints: [[u16; 2]; 2] = [first_endpoint, second_endpoint];
+        let endpoints: [[u16; 2]; 2] = match (input_to_coords(&first_endpoint_input.value()), input_to_coords(&second_endpoint_input.value())) {
+          (Ok(first_endpoint), Ok(second_endpoint)) => [first_endpoint, second_endpoint],
+          (Err(e), _) | (_, Err(e)) => {
+            log!(e);
+            return None;
+          },
+        };
         if let ToolsTypes::Line = selected_tool {
-          Self::Message::Line(endpoints)
+          Some(Self::Message::Line(endpoints))
         } else {
           //"else if let ToolsTypes::Rect = selected_tool" would be better but would not match all arms
-          Self::Message::Rect(endpoints)
+          Some(Self::Message::Rect(endpoints))
         }
       })
     };
@@ -310,28 +342,49 @@ impl Component for Tools {
       let center_input_ref2 = center_input_ref.clone();
       let xlength_input_ref2 = xlength_input_ref.clone();
       let ylength_input_ref2 = ylength_input_ref.clone();
-      ctx.link().callback(move |_| {
+      ctx.link().batch_callback(move |_| {
         let center_input: HtmlInputElement = center_input_ref2.cast().unwrap();
-        let center_input_vec: Vec<u16> = input_to_u16_vec(center_input.value());
         let xlength_input: HtmlInputElement = xlength_input_ref2.cast().unwrap();
-        let xlength: u16 = xlength_input.value().parse().unwrap();
         let ylength_input: HtmlInputElement = ylength_input_ref2.cast().unwrap();
-        let ylength: u16 = ylength_input.value().parse().unwrap();
-        let ellipse_args: [[u16; 2]; 2] = [[center_input_vec[0], center_input_vec[1]], [xlength, ylength]];
-        Self::Message::Ellipse(ellipse_args)
+        let ellipse_args = input_to_coords(&center_input.value()).and_then(|center| {
+          Ok([center, [input_to_u16(&xlength_input.value())?, input_to_u16(&ylength_input.value())?]])
+        });
+        match ellipse_args {
+          Ok(ellipse_args) => Some(Self::Message::Ellipse(ellipse_args)),
+          Err(e) => {
+            log!(e);
+            None
+          },
+        }
       })
     };
 
     //blur only needs blur radius
     let blur = {
       let blur_radius_ref2 = blur_radius_ref.clone();
-      ctx.link().callback(move |_| {
+      ctx.link().batch_callback(move |_| {
         let blur_radius_input: HtmlInputElement = blur_radius_ref2.cast().unwrap();
-        let blur_radius: u8 = blur_radius_input.value().parse().unwrap();
-        Self::Message::Blur(blur_radius)
+        match input_to_u8(&blur_radius_input.value()) {
+          Ok(blur_radius) => Some(Self::Message::Blur(blur_radius)),
+          Err(e) => {
+            log!(e);
+            None
+          },
+        }
       })
     };
 
+    let eyedropper_size = ctx.link().batch_callback(|e: Event| {
+      let select: HtmlSelectElement = e.target_unchecked_into();
+      select.value().parse::<u8>().ok().map(Self::Message::EyedropperSize)
+    });
+
+    let eyedropper_sizes = [1, 3, 5, 9].iter().map(|size| {
+      html! {
+        <option value={size.to_string()} selected={*size == ctx.props().eyedropper_size}>{ format!("{}x{}", size, size) }</option>
+      }
+    }).collect::<Html>();
+
     let greyscale = {
       ctx.link().callback(move |_| {
         Self::Message::Greyscale
@@ -357,28 +410,33 @@ impl Component for Tools {
             }
           </p>
           <div style={"display: ".to_string()+&color_picker_display}>
-            <label for="tool-color">{"Color: "}</label>
-            <input name="tool-color" value={color_text} ref={tc_input_ref}/>
-            <button onclick={new_tool_color}>{ "Change" }</button>
+            <span>{"Color: "}</span>
+            <ColorPicker color={tool_color} on_change={new_tool_color}/>
+          </div>
+          <div style={"display: ".to_string()+&eyedropper_display}>
+            <label for="eyedropper-size">{"Sample Size: "}</label>
+            <select name="eyedropper-size" onchange={eyedropper_size}>
+              { eyedropper_sizes }
+            </select>
           </div>
           <div style={"display: ".to_string()+&ellipse_display}>
             <label for="center">{"Center: "}</label>
-            <input name="center" placeholder="(0, 0)" ref={&center_input_ref}/>
+            <input name="center" placeholder="(0, 0)" value={drag_ellipse_args.map(|args| coords_text(args[0]))} ref={&center_input_ref}/>
             <br/>
             <label for="x-length">{"X Length: "}</label>
-            <input name="x-length" ref={&xlength_input_ref}/>
+            <input name="x-length" value={drag_ellipse_args.map(|args| args[1][0].to_string())} ref={&xlength_input_ref}/>
             <br/>
             <label for="y-length">{"Y Length: "}</label>
-            <input name="y-length" ref={&ylength_input_ref}/>
+            <input name="y-length" value={drag_ellipse_args.map(|args| args[1][1].to_string())} ref={&ylength_input_ref}/>
             <br/>
             <button onclick={ellipse}>{ "Create" }</button>
           </div>
           <div style={"display: ".to_string()+&end_points_display}>
             <label for="first-endpoint">{"First Endpoint: "}</label>
-            <input name="first-endpoint" placeholder="(0, 0)" ref={first_endpoint_ref}/>
+            <input name="first-endpoint" placeholder="(0, 0)" value={drag_endpoints.map(|endpoints| coords_text(endpoints[0]))} ref={first_endpoint_ref}/>
             <br/>
             <label for="second-endpoint">{"Second Endpoint: "}</label>
-            <input name="second-endpoint" placeholder="(0, 0)" ref={second_endpoint_ref}/>
+            <input name="second-endpoint" placeholder="(0, 0)" value={drag_endpoints.map(|endpoints| coords_text(endpoints[1]))} ref={second_endpoint_ref}/>
             <br/>
             <button onclick={create}>{ "Create" }</button>
           </div>
@@ -397,7 +455,7 @@ impl Component for Tools {
           <div style={"display: ".to_string()+&rotate_display}>
             <button onclick={rotate}>{ "Rotate 90 degrees" }</button>
           </div>
-          <p class="shortcut-tip">{ "Tip: Press the " }<code>{"["}</code>{ " and " }<code>{"]"}</code>{ " keys to cycle through the tools. Also, you can use " }<code>{"ctrl+z"}</code>{ " to undo changes." }</p>
+          <p class="shortcut-tip">{ "Tip: Press the " }<code>{"["}</code>{ " and " }<code>{"]"}</code>{ " keys to cycle through the tools. Also, you can use " }<code>{"ctrl+z"}</code>{ " to undo changes, and " }<code>{"ctrl+y"}</code>{ " to redo them." }</p>
         </div>
       </div>
     }
@@ -0,0 +1,161 @@
+use crate::rle::check_rle_size;
+
+//checks that bytes are a bmp this editor (and bmp_rust) can open, before anything reads them.
+//bmp_rust indexes straight into the contents, so a truncated or wrong file would otherwise panic.
+//the error says what is wrong with the file, to show the user
+
+//header sizes bmp_rust can read: core, info, V4 and V5
+const SUPPORTED_HEADERS: [u32; 4] = [12, 40, 108, 124];
+//pixel coordinates are u16
+const MAX_DIMENSION: u32 = 65535;
+
+pub fn validate_bmp(contents: &[u8]) -> Result<(), String> {
+  let u16_at = |start: usize| u16::from_le_bytes([contents[start], contents[start+1]]);
+  let u32_at = |start: usize| u32::from_le_bytes([contents[start], contents[start+1], contents[start+2], contents[start+3]]);
+  if contents.len() < 14 {
+    return Err(format!("The file is only {} bytes, too short to be a BMP (the file header alone is 14 bytes)", contents.len()));
+  }
+  if let Some(format) = other_format(contents) {
+    return Err(format!("This is a {} file, not a BMP", format));
+  }
+  match &contents[..2] {
+    b"BM" => {},
+    b"BA" | b"CI" | b"CP" | b"IC" | b"PT" => return Err("OS/2 bitmap arrays, icons and pointers aren't supported".to_string()),
+    _ => return Err("The file doesn't start with \"BM\", so it isn't a BMP".to_string()),
+  }
+  if contents.len() < 18 {
+    return Err("The file ends before the DIB header".to_string());
+  }
+  let dib_size = u32_at(14);
+  if !SUPPORTED_HEADERS.contains(&dib_size) {
+    return Err(match dib_size {
+      52 | 56 => format!("{} byte (Adobe BITMAPV{}INFOHEADER) DIB headers aren't supported", dib_size, if dib_size == 52 { 2 } else { 3 }),
+      64 => "OS/2 2.x DIB headers aren't supported".to_string(),
+      _ => format!("The DIB header size is {}, which isn't a BMP header", dib_size),
+    });
+  }
+  let headers_end = 14+dib_size as usize;
+  if contents.len() < headers_end {
+    return Err(format!("The file ends in the middle of the {} byte DIB header", dib_size));
+  }
+  //the core header has 16 bit sizes and no compression
+  let (width, height, planes, bitcount, compression) = if dib_size == 12 {
+    (i64::from(u16_at(18)), i64::from(u16_at(20) as i16), u16_at(22), u16_at(24), 0)
+  } else {
+    (i64::from(u32_at(18) as i32), i64::from(u32_at(22) as i32), u16_at(26), u16_at(28), u32_at(30))
+  };
+  if width < 1 {
+    return Err(format!("The width is {}, it has to be at least 1", width));
+  }
+  if height == 0 {
+    return Err("The height is 0".to_string());
+  }
+  if width > i64::from(MAX_DIMENSION) || height.abs() > i64::from(MAX_DIMENSION) {
+    return Err(format!("The image is {}x{}, but the editor only supports up to {} pixels each way", width, height.abs(), MAX_DIMENSION));
+  }
+  if planes != 1 {
+    return Err(format!("The number of planes is {}, it should be 1", planes));
+  }
+  if ![1, 2, 4, 8, 16, 24, 32].contains(&bitcount) {
+    return Err(format!("{} bits per pixel isn't a BMP bit depth", bitcount));
+  }
+  //color masks for BI_BITFIELDS (3 masks) or BI_ALPHABITFIELDS (4 masks) come after a 40 byte header
+  let mut masks_size = 0;
+  match compression {
+    0 => {},
+    1 | 2 => {
+      let (name, needed) = if compression == 1 { ("RLE8", 8) } else { ("RLE4", 4) };
+      if bitcount != needed {
+        return Err(format!("{} compression is only for {}-bit images, but this one is {}-bit", name, needed, bitcount));
+      }
+    },
+    3 | 6 => {
+      if bitcount != 16 && bitcount != 32 {
+        return Err(format!("Bit field masks need a 16 or 32-bit image, but this one is {}-bit", bitcount));
+      }
+      if dib_size == 40 {
+        masks_size = if compression == 3 { 12 } else { 16 };
+      }
+    },
+    4 => return Err("JPEG compressed BMPs aren't supported".to_string()),
+    5 => return Err("PNG compressed BMPs aren't supported".to_string()),
+    _ => return Err(format!("The compression type is {}, which isn't a BMP compression", compression)),
+  }
+  //bmp_rust only knows the intents numbered 0 to 4
+  if dib_size == 124 && u32_at(122) > 4 {
+    return Err(format!("The V5 header's rendering intent ({}) isn't supported", u32_at(122)));
+  }
+  let offset = u32_at(10) as usize;
+  if offset < headers_end+masks_size {
+    return Err(format!("The pixel data offset ({}) points inside the headers, which are {} bytes", offset, headers_end+masks_size));
+  }
+  if offset >= contents.len() {
+    return Err(format!("The pixel data offset ({}) is past the end of the file ({} bytes)", offset, contents.len()));
+  }
+  if bitcount <= 8 {
+    let entry_size = if dib_size == 12 { 3 } else { 4 };
+    if (offset-headers_end)/entry_size == 0 {
+      return Err(format!("The {}-bit image has no color table", bitcount));
+    }
+  }
+  //run length encoded data can be any length, as long as it could cover the image
+  if compression == 1 || compression == 2 {
+    check_rle_size(width as u32, height.unsigned_abs() as u32, contents.len()-offset)?;
+  } else {
+    let row_size = (usize::from(bitcount)*width as usize).div_ceil(32)*4;
+    let needed = row_size*height.unsigned_abs() as usize;
+    let available = contents.len()-offset;
+    if available < needed {
+      return Err(format!("The file is cut off: there are {} bytes of pixels, but a {}x{} {}-bit image needs {}", available, width, height.abs(), bitcount, needed));
+    }
+  }
+  Ok(())
+}
+
+//other image files that end up here, eg with a .bmp extension
+fn other_format(contents: &[u8]) -> Option<&'static str> {
+  if contents.starts_with(&[137, 80, 78, 71]) {
+    Some("PNG")
+  } else if contents.starts_with(&[0xFF, 0xD8, 0xFF]) {
+    Some("JPEG")
+  } else if contents.starts_with(b"GIF8") {
+    Some("GIF")
+  } else {
+    None
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use bmp_rust::bmp::BMP;
+  use crate::indexed::new_indexed;
+  use crate::rle::encode_rle;
+
+  #[test]
+  fn valid_images() {
+    assert_eq!(validate_bmp(&BMP::new(3, 5, None).contents), Ok(()));
+    assert_eq!(validate_bmp(&new_indexed(3, 2, 4, &[[0, 0, 0, 255]], &[0; 6]).unwrap().contents), Ok(()));
+  }
+
+  #[test]
+  fn broken_images() {
+    let contents = BMP::new(3, 5, None).contents;
+    assert!(validate_bmp(&contents[..10]).unwrap_err().contains("too short"));
+    assert!(validate_bmp(&contents[..100]).unwrap_err().contains("middle of the 124 byte DIB header"));
+    assert!(validate_bmp(&contents[..contents.len()-1]).unwrap_err().contains("cut off"));
+    assert_eq!(validate_bmp(&[137, 80, 78, 71, 13, 10, 26, 10, 0, 0, 0, 0, 0, 0]), Err("This is a PNG file, not a BMP".to_string()));
+    let mut wrong_offset = contents.clone();
+    wrong_offset[10..14].copy_from_slice(&20u32.to_le_bytes());
+    assert!(validate_bmp(&wrong_offset).unwrap_err().contains("inside the headers"));
+    let mut wrong_compression = contents.clone();
+    wrong_compression[30..34].copy_from_slice(&1u32.to_le_bytes());
+    assert_eq!(validate_bmp(&wrong_compression), Err("RLE8 compression is only for 8-bit images, but this one is 32-bit".to_string()));
+    let mut no_planes = contents;
+    no_planes[26] = 0;
+    assert!(validate_bmp(&no_planes).unwrap_err().contains("planes"));
+    let mut huge_rle = encode_rle(&new_indexed(2, 2, 8, &[[0, 0, 0, 255]], &[0; 4]).unwrap()).unwrap().contents;
+    huge_rle[18..26].copy_from_slice(&[255, 255, 0, 0, 255, 255, 0, 0]);
+    assert!(validate_bmp(&huge_rle).unwrap_err().contains("only images up to"));
+  }
+}
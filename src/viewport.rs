@@ -0,0 +1,158 @@
+//which part of the image is shown on the canvas, and how big
+//zoom is how many canvas pixels one image pixel takes up (less than 1 when zoomed out past 1:1),
+//offset is the image coordinate at the top left corner of the canvas
+
+pub const MIN_ZOOM: f64 = 0.01;
+pub const MAX_ZOOM: f64 = 64.0;
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Viewport {
+  pub zoom: f64,
+  pub offset: [f64; 2],
+  pub canvas_size: [f64; 2],
+  pub image_size: [u32; 2],
+}
+
+impl Viewport {
+  //whole image visible and centered. whole number zoom if the image is smaller than the canvas, so all the pixels are the same size
+  pub fn fit(image_size: [u32; 2], canvas_size: [f64; 2]) -> Self {
+    let mut zoom = (canvas_size[0]/f64::from(image_size[0].max(1))).min(canvas_size[1]/f64::from(image_size[1].max(1)));
+    if zoom >= 1.0 {
+      zoom = zoom.floor();
+    }
+    let mut viewport = Self { zoom: zoom.clamp(MIN_ZOOM, MAX_ZOOM), offset: [0.0, 0.0], canvas_size, image_size };
+    viewport.center();
+    viewport
+  }
+
+  //1:1, keeping the center of the canvas on the same part of the image
+  pub fn actual_size(&mut self) {
+    self.zoom_at(1.0, [self.canvas_size[0]/2.0, self.canvas_size[1]/2.0]);
+  }
+
+  fn center(&mut self) {
+    for i in 0..2 {
+      self.offset[i] = (f64::from(self.image_size[i])-self.canvas_size[i]/self.zoom)/2.0;
+    }
+  }
+
+  pub fn canvas_to_image(&self, point: [f64; 2]) -> [f64; 2] {
+    [self.offset[0]+point[0]/self.zoom, self.offset[1]+point[1]/self.zoom]
+  }
+
+  pub fn image_to_canvas(&self, point: [f64; 2]) -> [f64; 2] {
+    [(point[0]-self.offset[0])*self.zoom, (point[1]-self.offset[1])*self.zoom]
+  }
+
+  //the image pixel under a point on the canvas, none if it is outside the image
+  pub fn pixel_at(&self, point: [f64; 2]) -> Option<[u16; 2]> {
+    let image_point = self.canvas_to_image(point);
+    let x = image_point[0].floor();
+    let y = image_point[1].floor();
+    if x < 0.0 || y < 0.0 || x >= f64::from(self.image_size[0]) || y >= f64::from(self.image_size[1]) {
+      return None;
+    }
+    Some([x as u16, y as u16])
+  }
+
+  //canvas x, y, width and height of an image pixel. the edges are rounded so neighbouring pixels don't overlap or leave gaps
+  pub fn pixel_rect(&self, pixel: [u16; 2]) -> [f64; 4] {
+    let top_left = self.image_to_canvas([f64::from(pixel[0]), f64::from(pixel[1])]);
+    let bottom_right = self.image_to_canvas([f64::from(pixel[0])+1.0, f64::from(pixel[1])+1.0]);
+    let left = top_left[0].round();
+    let top = top_left[1].round();
+    [left, top, bottom_right[0].round()-left, bottom_right[1].round()-top]
+  }
+
+  //top left and bottom right (inclusive) of the image pixels that are at least partly on the canvas, none if none are
+  pub fn visible_pixels(&self) -> Option<[[u16; 2]; 2]> {
+    let top_left = self.canvas_to_image([0.0, 0.0]);
+    let bottom_right = self.canvas_to_image(self.canvas_size);
+    let mut visible: [[u16; 2]; 2] = [[0, 0], [0, 0]];
+    for i in 0..2 {
+      let start = top_left[i].floor().max(0.0);
+      let end = (bottom_right[i].ceil()-1.0).min(f64::from(self.image_size[i])-1.0);
+      if end < start {
+        return None;
+      }
+      visible[0][i] = start as u16;
+      visible[1][i] = end as u16;
+    }
+    Some(visible)
+  }
+
+  //change the zoom, keeping the image point under the canvas point in the same place (eg, under the mouse)
+  pub fn zoom_at(&mut self, zoom: f64, point: [f64; 2]) {
+    let image_point = self.canvas_to_image(point);
+    self.zoom = zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+    self.offset = [image_point[0]-point[0]/self.zoom, image_point[1]-point[1]/self.zoom];
+    self.clamp_offset();
+  }
+
+  //move the image by an amount of canvas pixels
+  pub fn pan(&mut self, delta: [f64; 2]) {
+    self.offset[0] -= delta[0]/self.zoom;
+    self.offset[1] -= delta[1]/self.zoom;
+    self.clamp_offset();
+  }
+
+  //don't let the image be moved completely off the canvas
+  fn clamp_offset(&mut self) {
+    for i in 0..2 {
+      let min = 1.0-self.canvas_size[i]/self.zoom;
+      let max = f64::from(self.image_size[i])-1.0;
+      self.offset[i] = self.offset[i].clamp(min.min(max), max);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn fit_small_image_uses_whole_pixels() {
+    let viewport = Viewport::fit([100, 50], [650.0, 650.0]);
+    assert_eq!(viewport.zoom, 6.0);
+    assert_eq!(viewport.pixel_at([325.0, 325.0]), Some([50, 25]));
+    assert_eq!(viewport.visible_pixels(), Some([[0, 0], [99, 49]]));
+  }
+
+  #[test]
+  fn fit_large_image_zooms_out() {
+    let viewport = Viewport::fit([1300, 650], [650.0, 650.0]);
+    assert_eq!(viewport.zoom, 0.5);
+    assert_eq!(viewport.pixel_at([649.0, 325.0]), Some([1298, 325]));
+    assert_eq!(viewport.pixel_at([10.0, 10.0]), None);
+  }
+
+  #[test]
+  fn zoom_keeps_point_under_cursor() {
+    let mut viewport = Viewport::fit([100, 100], [650.0, 650.0]);
+    let before = viewport.pixel_at([200.0, 300.0]);
+    viewport.zoom_at(20.0, [200.0, 300.0]);
+    assert_eq!(viewport.pixel_at([200.0, 300.0]), before);
+    assert_eq!(viewport.zoom, 20.0);
+    let visible = viewport.visible_pixels().unwrap();
+    assert!(visible[1][0]-visible[0][0] < 40);
+  }
+
+  #[test]
+  fn pan_moves_and_stays_on_canvas() {
+    let mut viewport = Viewport::fit([10, 10], [100.0, 100.0]);
+    viewport.pan([-10.0, 0.0]);
+    assert_eq!(viewport.pixel_at([0.0, 0.0]), Some([1, 0]));
+    viewport.pan([10000.0, 10000.0]);
+    assert!(viewport.visible_pixels().is_some());
+    viewport.pan([-10000.0, -10000.0]);
+    assert!(viewport.visible_pixels().is_some());
+  }
+
+  #[test]
+  fn pixel_rects_touch() {
+    let viewport = Viewport { zoom: 2.5, offset: [0.0, 0.0], canvas_size: [100.0, 100.0], image_size: [40, 40] };
+    let first = viewport.pixel_rect([0, 0]);
+    let second = viewport.pixel_rect([1, 0]);
+    assert_eq!(first[0]+first[2], second[0]);
+  }
+}